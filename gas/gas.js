@@ -1,42 +1,147 @@
 function doPost(e) {
   try {
-    //値の受取り
-    const parameter = e.parameter;
-
-    // 現在の日時を取得
-    const year = new Date().getFullYear();
-    const month = new Date().getMonth() + 1;
-    const day = new Date().getDate();
-    const hour = new Date().getHours();
-    const minute = new Date().getMinutes();
-    const sheetName = `${year}-${month}`;
-    const datetime = `${year}/${month}/${day} ${hour}:${minute}`;
+    // まとめて送られた場合は JSON の配列、1 件ずつならフォームの値
+    const events =
+      e.postData && e.postData.type === "application/json"
+        ? JSON.parse(e.postData.contents)
+        : [e.parameter];
+    if (!Array.isArray(events)) throw new Error("events must be an array");
 
-    const ss = SpreadsheetApp.getActiveSpreadsheet();
-
-    let sheet = ss.getSheetByName(sheetName);
-    if (!sheet) sheet = createSheet(sheetName);
+    // 秘密鍵が設定されていれば署名を検証する (1 件でも不正なら何も記録しない)
+    const secret =
+      PropertiesService.getScriptProperties().getProperty("SIGNING_SECRET");
+    if (secret && !events.every((event) => verifySignature(event, secret))) {
+      throw new Error("invalid signature");
+    }
 
-    // シートに追記
-    sheet.appendRow([datetime, parameter.name, parameter.status]);
+    for (const event of events) appendEvent(event);
 
     const output = ContentService.createTextOutput();
     output.setMimeType(ContentService.MimeType.JSON);
-    output.setContent({ status: "success" });
+    output.setContent(JSON.stringify({ ok: true, status: "success" }));
 
     return output;
   } catch (error) {
     const output = ContentService.createTextOutput();
     output.setMimeType(ContentService.MimeType.JSON);
-    output.setContent({ status: "error", message: error.message });
+    output.setContent(
+      JSON.stringify({ ok: false, status: "error", message: error.message }),
+    );
+
+    return output;
+  }
+}
+
+// name の業務の状態を返す (アプリの起動時にローカルの状態と突き合わせるため)
+// 今月と先月のシートから、最後に記録された業務の開始/終了を探す
+function doGet(e) {
+  const output = ContentService.createTextOutput();
+  output.setMimeType(ContentService.MimeType.JSON);
+  try {
+    const name = e.parameter.name;
+    if (!name) throw new Error("name is required");
+
+    const ss = SpreadsheetApp.getActiveSpreadsheet();
+    const now = new Date();
+    const months = [0, 1].map(
+      (ago) => new Date(now.getFullYear(), now.getMonth() - ago, 1),
+    );
+    for (const month of months) {
+      const sheet = ss.getSheetByName(
+        `${month.getFullYear()}-${month.getMonth() + 1}`,
+      );
+      if (!sheet) continue;
+
+      // 新しい行から順に探す (1 行目は見出し)
+      const rows = sheet.getDataRange().getValues().slice(1).reverse();
+      const last = rows.find(
+        (row) =>
+          row[1] === name &&
+          row[3] === "work" &&
+          (row[4] === "start" || row[4] === "end"),
+      );
+      if (!last) continue;
 
+      const open = last[4] === "start";
+      output.setContent(
+        JSON.stringify({
+          status: "success",
+          open: open,
+          started_at: open ? new Date(last[0]).toISOString() : null,
+        }),
+      );
+      return output;
+    }
+
+    output.setContent(JSON.stringify({ status: "success", open: false }));
+    return output;
+  } catch (error) {
+    output.setContent(
+      JSON.stringify({ status: "error", message: error.message }),
+    );
     return output;
   }
 }
 
+// イベントを操作した月のシートに追記
+function appendEvent(parameter) {
+  // 操作した日時を取得 (送られていなければ受信した日時)
+  const date = parameter.timestamp ? new Date(parameter.timestamp) : new Date();
+  const year = date.getFullYear();
+  const month = date.getMonth() + 1;
+  const day = date.getDate();
+  const hour = date.getHours();
+  const minute = date.getMinutes();
+  const sheetName = `${year}-${month}`;
+  const datetime = `${year}/${month}/${day} ${hour}:${minute}`;
+
+  const ss = SpreadsheetApp.getActiveSpreadsheet();
+
+  let sheet = ss.getSheetByName(sheetName);
+  if (!sheet) sheet = createSheet(sheetName);
+
+  // 種類と操作・端末は古いアプリからは送られてこないので空欄にする (メモは業務開始のときだけ)
+  sheet.appendRow([
+    datetime,
+    parameter.name,
+    parameter.status,
+    parameter.event_type || "",
+    parameter.action || "",
+    parameter.note || "",
+    parameter.device || "",
+  ]);
+}
+
+// 署名する項目と順番 (アプリの SIGNED_FIELDS と同じ)
+const SIGNED_FIELDS = [
+  "name",
+  "status",
+  "timestamp",
+  "event_type",
+  "action",
+  "elapsed_secs",
+  "note",
+  "device",
+];
+
+// 送られてきた署名が SIGNED_FIELDS の値の HMAC-SHA256 と一致するか
+// (値のない項目は空にし、値の中の \ と改行はエスケープして改行でつなぐ)
+function verifySignature(parameter, secret) {
+  if (!parameter.signature) return false;
+  const message = SIGNED_FIELDS.map((key) =>
+    String(parameter[key] || "")
+      .replace(/\\/g, "\\\\")
+      .replace(/\n/g, "\\n"),
+  ).join("\n");
+  const signature = Utilities.computeHmacSha256Signature(message, secret)
+    .map((byte) => ((byte + 256) % 256).toString(16).padStart(2, "0"))
+    .join("");
+  return signature === parameter.signature;
+}
+
 // gasでシートを作成
 function createSheet(sheetName) {
   const sheet = SpreadsheetApp.getActiveSpreadsheet().insertSheet(sheetName);
-  sheet.appendRow(["日付", "名前", "出退勤"]);
+  sheet.appendRow(["日付", "名前", "出退勤", "種類", "操作", "メモ", "端末"]);
   return sheet;
 }
@@ -0,0 +1,45 @@
+use std::env;
+
+use auto_launch::{AutoLaunch, AutoLaunchBuilder};
+use tracing::warn;
+
+// ログイン項目に登録する名前 (macOS では LaunchAgents の plist の名前になる)
+const APP_NAME: &str = "me.satooru.kintai-app";
+
+// 現在の実行ファイルを起動するログイン項目
+fn auto_launch() -> Result<AutoLaunch, auto_launch::Error> {
+    let exe = env::current_exe()?;
+    AutoLaunchBuilder::new()
+        .set_app_name(APP_NAME)
+        .set_app_path(&exe.to_string_lossy())
+        .set_use_launch_agent(true)
+        .build()
+}
+
+// ログイン時に起動するように登録されているか
+pub fn is_enabled() -> bool {
+    match auto_launch().and_then(|auto| auto.is_enabled()) {
+        Ok(enabled) => enabled,
+        Err(e) => {
+            warn!("ログイン項目の状態を取得できません: {}", e);
+            false
+        }
+    }
+}
+
+// ログイン項目の登録を設定に合わせる
+// (有効なら毎回登録し直すので、アプリを移動して古いパスが登録されていても直る)
+pub fn sync(enabled: bool) {
+    let result = auto_launch().and_then(|auto| {
+        if enabled {
+            auto.enable()
+        } else if auto.is_enabled()? {
+            auto.disable()
+        } else {
+            Ok(())
+        }
+    });
+    if let Err(e) = result {
+        warn!("ログイン項目を更新できません: {}", e);
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::file;
+
+// 書き出す形式のバージョン (形式を変えたら上げて、migrate で古い形式から変換する)
+pub const SCHEMA_VERSION: u32 = 1;
+
+// イベントログ全体のバックアップ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Backup {
+    pub schema_version: u32,
+    pub exported_at: DateTime<Local>,
+    pub events: Vec<BackupEvent>,
+    pub corrections: Vec<BackupCorrection>,
+}
+
+// 記録されたイベント (調整の記録など、集計に使わない行もそのまま含める)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupEvent {
+    pub id: i64,
+    pub kind: String,
+    pub action: String,
+    pub timestamp: DateTime<Local>,
+    pub duration_secs: Option<i64>,
+    pub note: Option<String>,
+}
+
+// イベントの修正・削除の履歴
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupCorrection {
+    pub id: i64,
+    pub event_id: i64,
+    pub kind: String,
+    pub action: String,
+    pub old_timestamp: DateTime<Local>,
+    // 削除した場合は None
+    pub new_timestamp: Option<DateTime<Local>>,
+    pub corrected_at: DateTime<Local>,
+}
+
+// バックアップの読み書きで発生するエラー
+#[derive(Debug)]
+pub enum BackupError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    // このアプリより新しいバージョンで書き出されたので読み込めない
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupError::Io(e) => write!(f, "バックアップを読み書きできません: {}", e),
+            BackupError::Json(e) => write!(f, "バックアップの形式が正しくありません: {}", e),
+            BackupError::UnsupportedVersion(version) => write!(
+                f,
+                "新しいバージョンのアプリで書き出されたバックアップです (形式 {}, 対応しているのは {} まで)",
+                version, SCHEMA_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<io::Error> for BackupError {
+    fn from(e: io::Error) -> Self {
+        BackupError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for BackupError {
+    fn from(e: serde_json::Error) -> Self {
+        BackupError::Json(e)
+    }
+}
+
+// バックアップを JSON ファイルに書き出す
+pub fn write_json(path: &Path, backup: &Backup) -> Result<(), BackupError> {
+    let content = serde_json::to_string_pretty(backup)?;
+    file::write_atomic(path, &content)?;
+    Ok(())
+}
+
+// JSON ファイルからバックアップを読み込む (古い形式なら今の形式に変換する)
+pub fn read_json(path: &Path) -> Result<Backup, BackupError> {
+    let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    migrate(value)
+}
+
+// schema_version を見て今の形式に変換する
+fn migrate(value: serde_json::Value) -> Result<Backup, BackupError> {
+    let version = value
+        .get("schema_version")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(0) as u32;
+    match version {
+        SCHEMA_VERSION => Ok(serde_json::from_value(value)?),
+        // 形式を変えたら、ここに古いバージョンからの変換を足す
+        _ => Err(BackupError::UnsupportedVersion(version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    // 2024-04-01 h 時 (ローカル時刻)
+    fn local(h: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 4, 1, h, 0, 0).unwrap()
+    }
+
+    fn backup() -> Backup {
+        Backup {
+            schema_version: SCHEMA_VERSION,
+            exported_at: local(20),
+            events: vec![BackupEvent {
+                id: 1,
+                kind: "work".to_string(),
+                action: "start".to_string(),
+                timestamp: local(9),
+                duration_secs: None,
+                note: Some("在宅".to_string()),
+            }],
+            corrections: vec![BackupCorrection {
+                id: 1,
+                event_id: 1,
+                kind: "work".to_string(),
+                action: "start".to_string(),
+                old_timestamp: local(10),
+                new_timestamp: Some(local(9)),
+                corrected_at: local(19),
+            }],
+        }
+    }
+
+    #[test]
+    fn json_file_round_trips() {
+        let path = env::temp_dir()
+            .join(format!("kintai-backup-round-trip-{}", std::process::id()))
+            .join("backup.json");
+        write_json(&path, &backup()).unwrap();
+        assert_eq!(read_json(&path).unwrap(), backup());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn current_version_is_read_as_is() {
+        let value = serde_json::to_value(backup()).unwrap();
+        assert_eq!(value["schema_version"], json!(SCHEMA_VERSION));
+        assert_eq!(migrate(value).unwrap(), backup());
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        for version in [json!(SCHEMA_VERSION + 1), json!(null)] {
+            let mut value = serde_json::to_value(backup()).unwrap();
+            value["schema_version"] = version;
+            assert!(matches!(
+                migrate(value),
+                Err(BackupError::UnsupportedVersion(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn malformed_backup_is_a_json_error() {
+        let mut value = serde_json::to_value(backup()).unwrap();
+        value["events"] = json!("work");
+        assert!(matches!(migrate(value), Err(BackupError::Json(_))));
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+// サーバーの時刻で確かめ直す間隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// 前回確かめてからの経過時間と時計の進みがこれ以上食い違ったら、時計が変わったとみなして確かめ直す
+// (スリープ中は経過時間に含まれないので、食い違っただけでは保留しない)
+const JUMP_TOLERANCE: Duration = Duration::from_secs(60);
+
+// サーバーの Date ヘッダーは秒単位なので、その分は許容する
+const DATE_PRECISION: Duration = Duration::from_secs(1);
+
+// 端末の時計のサーバーの時計に対するずれ (正なら端末の時計が進んでいる)
+// now は応答を受け取った時刻で、サーバーの時刻は通信にかかった時間の中ほどのものとみなす
+pub fn skew(now: DateTime<Utc>, server: DateTime<Utc>, round_trip: Duration) -> chrono::Duration {
+    let half = chrono::Duration::from_std(round_trip / 2).unwrap_or(chrono::Duration::zero());
+    now - half - server
+}
+
+// ずれが max を超えているか (通信にかかった時間と Date ヘッダーの精度の分は許容する)
+pub fn implausible(skew: chrono::Duration, max: Duration, round_trip: Duration) -> bool {
+    let tolerance = chrono::Duration::from_std(max + round_trip + DATE_PRECISION)
+        .unwrap_or(chrono::Duration::max_value());
+    skew.abs() > tolerance
+}
+
+// 前回確かめたときから時計が経過時間と食い違って進んだか (時計が変更された・スリープした)
+fn jumped(then: DateTime<Utc>, now: DateTime<Utc>, elapsed: Duration) -> bool {
+    let elapsed = chrono::Duration::from_std(elapsed).unwrap_or(chrono::Duration::max_value());
+    let tolerance =
+        chrono::Duration::from_std(JUMP_TOLERANCE).unwrap_or(chrono::Duration::max_value());
+    ((now - then) - elapsed).abs() > tolerance
+}
+
+// 最後にサーバーの時刻で確かめた結果
+struct Checked {
+    instant: Instant,
+    wall: DateTime<Utc>,
+    // 時計が大きくずれていたらそのずれ
+    skew: Option<chrono::Duration>,
+}
+
+// 端末の時計が大きくずれている間は送信を保留するための状態
+#[derive(Default)]
+pub struct ClockGuard {
+    checked: Mutex<Option<Checked>>,
+}
+
+impl ClockGuard {
+    // サーバーの時刻で確かめる必要があるか
+    // (まだ確かめていない・前回から時間が経った・その間に時計が変わった)
+    pub fn needs_check(&self, now: DateTime<Utc>) -> bool {
+        match &*self.checked.lock().unwrap() {
+            None => true,
+            Some(checked) => {
+                let elapsed = checked.instant.elapsed();
+                elapsed >= CHECK_INTERVAL || jumped(checked.wall, now, elapsed)
+            }
+        }
+    }
+
+    // 確かめた結果を記録する (ずれていたかどうかが前回と変わったら true)
+    pub fn record(&self, now: DateTime<Utc>, skew: Option<chrono::Duration>) -> bool {
+        let mut checked = self.checked.lock().unwrap();
+        let was_skewed = checked
+            .as_ref()
+            .is_some_and(|checked| checked.skew.is_some());
+        *checked = Some(Checked {
+            instant: Instant::now(),
+            wall: now,
+            skew,
+        });
+        was_skewed != skew.is_some()
+    }
+
+    // 時計が大きくずれているので送信を保留しているか (確かめられないときは前回の結果のまま)
+    pub fn held(&self) -> Option<chrono::Duration> {
+        self.checked
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|checked| checked.skew)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    // サーバーの時刻 (2024-04-01 12:00 UTC)
+    fn server() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 4, 1, 12, 0, 0).unwrap()
+    }
+
+    const MAX: Duration = Duration::from_secs(5 * 60);
+    const ROUND_TRIP: Duration = Duration::from_millis(400);
+
+    // 端末の時計が now のときに確かめたずれ (大きくずれていれば Some)
+    fn held_at(now: DateTime<Utc>) -> Option<chrono::Duration> {
+        let skew = skew(now, server(), ROUND_TRIP);
+        implausible(skew, MAX, ROUND_TRIP).then_some(skew)
+    }
+
+    #[test]
+    fn skew_accounts_for_the_round_trip() {
+        // 応答を受け取ったのはサーバーの時刻から通信にかかった時間の半分後
+        let now = server() + chrono::Duration::milliseconds(200);
+        assert_eq!(skew(now, server(), ROUND_TRIP), chrono::Duration::zero());
+        let ahead = server() + chrono::Duration::minutes(3);
+        assert_eq!(
+            skew(ahead, server(), ROUND_TRIP),
+            chrono::Duration::minutes(3) - chrono::Duration::milliseconds(200)
+        );
+    }
+
+    #[test]
+    fn clock_far_in_the_past_or_future_is_implausible() {
+        // 電池が切れて 2001 年に戻った時計と、何年も先に進んだ時計
+        let past = Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap();
+        let future = Utc.with_ymd_and_hms(2037, 12, 31, 23, 59, 0).unwrap();
+        assert!(held_at(past).is_some_and(|skew| skew < chrono::Duration::zero()));
+        assert!(held_at(future).is_some_and(|skew| skew > chrono::Duration::zero()));
+
+        // 上限までのずれと、通信と Date ヘッダーの精度の分は許容する
+        let tolerance = MAX + ROUND_TRIP + DATE_PRECISION;
+        let millisecond = chrono::Duration::milliseconds(1);
+        let ahead = server() + chrono::Duration::from_std(tolerance + ROUND_TRIP / 2).unwrap();
+        assert_eq!(held_at(ahead), None);
+        assert!(held_at(ahead + millisecond).is_some());
+        let behind = server() - chrono::Duration::from_std(tolerance - ROUND_TRIP / 2).unwrap();
+        assert_eq!(held_at(behind), None);
+        assert!(held_at(behind - millisecond).is_some());
+    }
+
+    #[test]
+    fn guard_holds_while_skewed_and_releases_once_corrected() {
+        let guard = ClockGuard::default();
+        assert!(guard.needs_check(server()));
+        assert_eq!(guard.held(), None);
+
+        // ずれ始めたら保留し、変わったことを知らせる
+        let past = Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap();
+        assert!(guard.record(past, held_at(past)));
+        assert!(guard.held().is_some());
+        // ずれたままなら知らせ直さない
+        assert!(!guard.record(past, held_at(past)));
+        assert!(guard.held().is_some());
+
+        // 時計が直ったら保留をやめ、変わったことを知らせる
+        assert!(guard.record(server(), held_at(server())));
+        assert_eq!(guard.held(), None);
+        assert!(!guard.record(server(), held_at(server())));
+    }
+
+    #[test]
+    fn guard_checks_again_when_the_clock_is_changed() {
+        let guard = ClockGuard::default();
+        guard.record(server(), None);
+        assert!(!guard.needs_check(server()));
+        // 少しの食い違いは時計の変更とみなさない
+        assert!(!guard.needs_check(server() + chrono::Duration::seconds(30)));
+        // 経過時間に対して時計が大きく進んだ・戻ったら確かめ直す
+        assert!(guard.needs_check(server() + chrono::Duration::hours(2)));
+        assert!(guard.needs_check(server() - chrono::Duration::days(365)));
+    }
+}
@@ -0,0 +1,1539 @@
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, NaiveTime, TimeZone, Weekday};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use url::Url;
+use zeroize::Zeroizing;
+
+use crate::file;
+use crate::i18n::{Lang, Text};
+use crate::store::{EventAction, EventKind};
+
+// 設定ファイルの名前 (アプリの設定ディレクトリに置く)
+const CONFIG_FILE_NAME: &str = "config.json";
+
+// 設定ファイルの場所を指定する環境変数
+const CONFIG_PATH_ENV: &str = "KINTAI_CONFIG";
+
+// 起動時に指定された設定ファイルの場所
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+// 送信のタイムアウトのデフォルト (秒)
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+
+// 終了時に未送信イベントの送信を待つ時間のデフォルト (秒)
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 5;
+
+// トレイの表示を更新する間隔のデフォルト (秒)
+const DEFAULT_TICK_INTERVAL_SECS: u64 = 1;
+
+// 続けて操作されたときに送信を待つ時間のデフォルト (ミリ秒)
+const DEFAULT_SEND_DEBOUNCE_MS: u64 = 500;
+
+// 送信を保留する時計のずれのデフォルト (分)
+const DEFAULT_MAX_CLOCK_SKEW_MINUTES: u64 = 10;
+
+// まとめて送る間隔のデフォルト (秒)
+const DEFAULT_BATCH_INTERVAL_SECS: u64 = 300;
+
+// 休憩が長すぎると通知するまでのデフォルト (分)
+const DEFAULT_BREAK_WARNING_MINUTES: u64 = 60;
+
+// 休憩を促すまでの連続勤務時間のデフォルト (分)
+const DEFAULT_STRETCH_WARNING_MINUTES: u64 = 4 * 60;
+
+// 1 日の目標勤務時間のデフォルト (分)
+const DEFAULT_DAILY_GOAL_MINUTES: u64 = 8 * 60;
+
+// ポモドーロの集中時間と、その後に勧める休憩のデフォルト (分)
+const DEFAULT_FOCUS_MINUTES: u64 = 25;
+const DEFAULT_FOCUS_BREAK_MINUTES: u64 = 5;
+
+// 平日の所定労働時間のデフォルト (分)
+const DEFAULT_SCHEDULED_MINUTES: u64 = 8 * 60;
+
+// 業務開始/終了のショートカットのデフォルト
+const DEFAULT_ATTENDANCE_SHORTCUT: &str = "CmdOrCtrl+Shift+W";
+// 休憩のショートカットのデフォルト
+const DEFAULT_BREAK_SHORTCUT: &str = "CmdOrCtrl+Shift+B";
+
+// ログのレベルのデフォルト
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+// スリープしていた時間の扱い
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SleepPolicy {
+    // 業務中 (休憩中) の時間として数える
+    #[default]
+    Count,
+    // 数えずに通知する
+    Pause,
+}
+
+// トレイのタイトルに経過時間をどう表示するか
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleMode {
+    // 秒まで表示する
+    #[default]
+    Full,
+    // 分までにして幅を詰める
+    Minutes,
+    // アイコンだけにして経過時間はツールチップに表示する
+    Icon,
+}
+
+// トレイのアイコンをクリックしたときの操作
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrayAction {
+    // メニューを開く (macOS のみ, 他の OS ではメニューは右クリックで開く)
+    Menu,
+    // 休憩中・会議中・一時停止中ならそれを終え、それ以外は業務を開始/終了する
+    #[default]
+    Toggle,
+    // 業務を開始/終了する
+    ToggleWork,
+    // 休憩を開始/終了する
+    ToggleBreak,
+    // 集計ウィンドウを開く
+    Totals,
+    // 何もしない
+    None,
+}
+
+// トレイのアイコンのクリックの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayClick {
+    Left,
+    Double,
+    Right,
+}
+
+// 日付が変わっても業務中だったときの扱い
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MidnightPolicy {
+    // 通知だけする
+    #[default]
+    Warn,
+    // 0:00 で前日の業務を終了して新しい業務を開始する
+    Split,
+}
+
+// 休憩の時間の丸め方
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BreakRounding {
+    // 切り上げる
+    #[default]
+    Up,
+    // 近いほうに丸める (ちょうど半分なら切り上げる)
+    Nearest,
+}
+
+impl BreakRounding {
+    // duration を step 単位に丸める (step が 0 なら丸めない)
+    pub fn round(self, duration: Duration, step: Duration) -> Duration {
+        let step_secs = step.as_secs();
+        if step_secs == 0 {
+            return duration;
+        }
+        let secs = duration.as_secs();
+        let steps = match self {
+            BreakRounding::Up => secs.div_ceil(step_secs),
+            BreakRounding::Nearest => (secs + step_secs / 2) / step_secs,
+        };
+        Duration::from_secs(steps * step_secs)
+    }
+}
+
+// 業務の開始・終了の時刻の丸め方
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockRounding {
+    // 後の区切りに切り上げる
+    Up,
+    // 前の区切りに切り捨てる
+    Down,
+    // 近いほうの区切りに丸める (ちょうど半分なら切り上げる)
+    #[default]
+    Nearest,
+}
+
+impl ClockRounding {
+    // time を時計の step ごとの区切り (5 分なら 9:00, 9:05, ...) に丸める (step が 0 なら丸めない)
+    // 秒未満は切り捨ててから丸める
+    pub fn round(self, time: DateTime<Local>, step: Duration) -> DateTime<Local> {
+        let step_secs = step.as_secs() as i64;
+        if step_secs == 0 {
+            return time;
+        }
+        // UTC ではなくローカルの時計で区切るように、時差を足した秒数で丸める
+        let offset = i64::from(time.offset().local_minus_utc());
+        let secs = time.timestamp() + offset;
+        let rounded = match self {
+            ClockRounding::Up => (secs + step_secs - 1).div_euclid(step_secs) * step_secs,
+            ClockRounding::Down => secs.div_euclid(step_secs) * step_secs,
+            ClockRounding::Nearest => (secs + step_secs / 2).div_euclid(step_secs) * step_secs,
+        };
+        Local
+            .timestamp_opt(rounded - offset, 0)
+            .single()
+            .unwrap_or(time)
+    }
+}
+
+// 集計で週の始まりとする曜日
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+}
+
+impl WeekStart {
+    pub fn weekday(&self) -> Weekday {
+        match self {
+            WeekStart::Monday => Weekday::Mon,
+            WeekStart::Sunday => Weekday::Sun,
+        }
+    }
+}
+
+// 曜日ごとの所定労働時間 (分, 0 なら休日で、その日の実働はすべて残業になる)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+    pub monday: u64,
+    pub tuesday: u64,
+    pub wednesday: u64,
+    pub thursday: u64,
+    pub friday: u64,
+    pub saturday: u64,
+    pub sunday: u64,
+}
+
+impl Default for Schedule {
+    // 平日は 8 時間、土日は休日
+    fn default() -> Self {
+        Self {
+            monday: DEFAULT_SCHEDULED_MINUTES,
+            tuesday: DEFAULT_SCHEDULED_MINUTES,
+            wednesday: DEFAULT_SCHEDULED_MINUTES,
+            thursday: DEFAULT_SCHEDULED_MINUTES,
+            friday: DEFAULT_SCHEDULED_MINUTES,
+            saturday: 0,
+            sunday: 0,
+        }
+    }
+}
+
+impl Schedule {
+    // その曜日の所定労働時間
+    pub fn expected(&self, weekday: Weekday) -> Duration {
+        let minutes = match weekday {
+            Weekday::Mon => self.monday,
+            Weekday::Tue => self.tuesday,
+            Weekday::Wed => self.wednesday,
+            Weekday::Thu => self.thursday,
+            Weekday::Fri => self.friday,
+            Weekday::Sat => self.saturday,
+            Weekday::Sun => self.sunday,
+        };
+        Duration::from_secs(minutes * 60)
+    }
+
+    // last_checked より後、now 以前に今日の start を過ぎたか (所定労働時間が 0 の休日は過ぎても false)
+    pub fn start_passed(
+        &self,
+        start: NaiveTime,
+        last_checked: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> bool {
+        time_passed(start, last_checked, now) && !self.expected(now.weekday()).is_zero()
+    }
+}
+
+// last_checked より後、now 以前に今日の time を過ぎたか
+pub fn time_passed(time: NaiveTime, last_checked: NaiveDateTime, now: NaiveDateTime) -> bool {
+    let at = now.date().and_time(time);
+    last_checked < at && at <= now
+}
+
+// 決まった時刻に休憩を勧めるリマインダー
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BreakReminder {
+    // 休憩を勧める時刻 ("12:00" の形式)
+    pub time: String,
+    // 通知に表示する名前 ("昼休み" など)
+    pub label: String,
+}
+
+impl BreakReminder {
+    // 休憩を勧める時刻を検証して取得
+    pub fn time(&self) -> Result<NaiveTime, ConfigError> {
+        let time = self.time.trim();
+        NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| ConfigError::InvalidReminderTime(time.to_string()))
+    }
+
+    // last_checked より後、now 以前に休憩を勧める時刻を過ぎたか
+    pub fn is_due(
+        &self,
+        last_checked: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> Result<bool, ConfigError> {
+        Ok(time_passed(self.time()?, last_checked, now))
+    }
+}
+
+// 通知を表示しない時間帯 ("22:00" から "07:00" のように end が start より前なら日をまたぐ)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+impl QuietHours {
+    // 時間帯の始まりと終わりを検証して取得
+    pub fn times(&self) -> Result<(NaiveTime, NaiveTime), ConfigError> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| ConfigError::InvalidQuietHours(time.trim().to_string()))
+        };
+        Ok((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    // time が時間帯に入っているか (start は含み、end は含まない)
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        match self.times() {
+            Ok((start, end)) if start <= end => start <= time && time < end,
+            Ok((start, end)) => start <= time || time < end,
+            Err(_) => false,
+        }
+    }
+}
+
+// メニューに表示する文言とサーバーに送る status (省略したものはデフォルトの文言)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Labels {
+    // メニューの「業務開始」「業務終了」「休憩」「休憩解除」
+    pub clock_in: Option<String>,
+    pub clock_out: Option<String>,
+    pub start_break: Option<String>,
+    pub end_break: Option<String>,
+    // サーバーに送る「業務 開始」「業務 終了」「休憩 開始」「休憩 終了」
+    pub work_start: Option<String>,
+    pub work_end: Option<String>,
+    pub break_start: Option<String>,
+    pub break_end: Option<String>,
+}
+
+impl Labels {
+    // メニューに表示する文言 (設定されていなければ None)
+    pub fn menu(&self, text: Text) -> Option<&str> {
+        let label = match text {
+            Text::ClockIn => &self.clock_in,
+            Text::ClockOut => &self.clock_out,
+            Text::Break => &self.start_break,
+            Text::EndBreak => &self.end_break,
+            _ => return None,
+        };
+        label.as_deref().filter(|label| !label.trim().is_empty())
+    }
+
+    // サーバーに送る status (設定されていなければ None)
+    pub fn status(&self, kind: EventKind, action: EventAction) -> Option<&str> {
+        let label = match (kind, action) {
+            (EventKind::Work, EventAction::Start) => &self.work_start,
+            (EventKind::Work, EventAction::End) => &self.work_end,
+            (EventKind::Break, EventAction::Start) => &self.break_start,
+            (EventKind::Break, EventAction::End) => &self.break_end,
+            (EventKind::Meeting | EventKind::Hold, _) => return None,
+        };
+        label.as_deref().filter(|label| !label.trim().is_empty())
+    }
+
+    // 空の文言が設定されていないか検証
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let labels = [
+            ("clock_in", &self.clock_in),
+            ("clock_out", &self.clock_out),
+            ("start_break", &self.start_break),
+            ("end_break", &self.end_break),
+            ("work_start", &self.work_start),
+            ("work_end", &self.work_end),
+            ("break_start", &self.break_start),
+            ("break_end", &self.break_end),
+        ];
+        for (key, label) in labels {
+            if label
+                .as_deref()
+                .is_some_and(|label| label.trim().is_empty())
+            {
+                return Err(ConfigError::EmptyLabel(key));
+            }
+        }
+        Ok(())
+    }
+}
+
+// サーバーに送るフォームの項目名 (省略したものはデフォルトの名前)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FieldNames {
+    pub name: Option<String>,
+    pub status: Option<String>,
+    pub timestamp: Option<String>,
+    pub event_type: Option<String>,
+    pub action: Option<String>,
+    pub elapsed_secs: Option<String>,
+    pub note: Option<String>,
+    pub device: Option<String>,
+    pub signature: Option<String>,
+}
+
+impl FieldNames {
+    // デフォルトの項目名と、実際に送る項目名の組
+    fn names(&self) -> [(&'static str, &str); 9] {
+        [
+            ("name", &self.name),
+            ("status", &self.status),
+            ("timestamp", &self.timestamp),
+            ("event_type", &self.event_type),
+            ("action", &self.action),
+            ("elapsed_secs", &self.elapsed_secs),
+            ("note", &self.note),
+            ("device", &self.device),
+            ("signature", &self.signature),
+        ]
+        .map(|(key, name)| (key, name.as_deref().unwrap_or(key)))
+    }
+
+    // デフォルトの項目名 key の代わりに送る項目名
+    pub fn rename<'a>(&'a self, key: &'a str) -> &'a str {
+        self.names()
+            .into_iter()
+            .find(|(default, _)| *default == key)
+            .map_or(key, |(_, name)| name)
+    }
+
+    // 空の項目名や、同じ項目名が複数の項目に設定されていないか検証
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let names = self.names();
+        for (index, (key, name)) in names.iter().enumerate() {
+            if name.trim().is_empty() {
+                return Err(ConfigError::EmptyFieldName(key));
+            }
+            if names[..index].iter().any(|(_, other)| other == name) {
+                return Err(ConfigError::DuplicateFieldName(name.to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+// 送信に成功したときの通知 (イベントの種類ごとに有効にする)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SuccessNotification {
+    pub work: bool,
+    #[serde(rename = "break")]
+    pub break_time: bool,
+    pub meeting: bool,
+    // 通知と一緒に鳴らすシステムサウンドの名前 ("Glass" など, 空なら鳴らさない, macOS のみ)
+    pub sound: String,
+}
+
+impl SuccessNotification {
+    // event_type ("work" など) の送信に成功したときに通知するか
+    pub fn enabled(&self, event_type: &str) -> bool {
+        match event_type {
+            "work" => self.work,
+            "break" => self.break_time,
+            "meeting" => self.meeting,
+            _ => false,
+        }
+    }
+}
+
+// 勤怠を記録する名前と送信先の組 (案件ごとに切り替えて使う)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    // メニューに表示する名前
+    pub label: String,
+    // 勤怠を記録する名前 (空なら全体の "name")
+    pub name: String,
+    // 勤怠の送信先 URL (空なら全体の "endpoint_url")
+    pub endpoint_url: String,
+}
+
+// アプリの設定
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // 勤怠を記録する名前
+    pub name: String,
+    // 勤怠の送信先 URL (トークンを含むので破棄するときにメモリから消去する)
+    pub endpoint_url: Zeroizing<String>,
+    // 送信先と同じ内容を送る予備の送信先 URL (空なら送らない, 失敗しても送信の失敗として通知しない)
+    pub secondary_endpoint_url: Zeroizing<String>,
+    // イベントを 1 件ずつ送らず、JSON の配列にまとめて送る URL (空ならまとめない)
+    pub batch_endpoint_url: Zeroizing<String>,
+    // まとめて送る間隔 (秒, 業務終了はこの間隔を待たずにすぐ送る)
+    pub batch_interval_secs: u64,
+    // 送信内容に署名するための共有の秘密鍵 (空なら署名しない)
+    pub signing_secret: Zeroizing<String>,
+    // 送信せずに送信する内容をログに出すだけにするか (設定を確かめるとき用)
+    pub dry_run: bool,
+    // 起動時にサーバーの業務の状態を取得して、ローカルの状態と食い違っていれば確認するか
+    pub reconcile_on_startup: bool,
+    // 業務の開始・終了を知らせる Slack・Teams の Incoming Webhook の URL
+    pub webhook_urls: Vec<String>,
+    // 勤務状態を http://127.0.0.1:<port>/status で公開するポート (0 なら公開しない)
+    pub status_port: u16,
+    // 勤務状態が変わるたびにイベントを JSON で送る Unix ドメインソケットのパス (空なら送らない)
+    pub event_socket: String,
+    // 業務開始のときにメモ ("リモート" など) を入力するか
+    pub ask_note: bool,
+    // 業務開始を選んでから実際に時間を数え始めるまでの猶予 (秒, 0 なら猶予なし)
+    // 猶予中に業務を終了したら何も記録しない
+    pub start_grace_secs: u64,
+    // 送信するイベントにこのマシンのホスト名を device として付けるか
+    pub send_device: bool,
+    // 送信に成功したときに通知するイベントの種類と音
+    pub success_notification: SuccessNotification,
+    // 送信に使うプロキシの URL (空なら環境変数 HTTP_PROXY / HTTPS_PROXY に従う)
+    pub proxy_url: String,
+    // プロキシの認証に使うユーザー名とパスワード (ユーザー名が空なら認証しない)
+    pub proxy_username: String,
+    pub proxy_password: Zeroizing<String>,
+    // https 以外の送信先を許可するか
+    pub allow_insecure: bool,
+    // 送信先のサーバー (かその CA) の PEM 形式の証明書のパス (空ならピン留めせず OS の証明書で検証する)
+    pub pinned_certificate: String,
+    // 送信のタイムアウト (秒)
+    pub timeout_secs: u64,
+    // 終了時に未送信イベントの送信を待つ時間 (秒)
+    pub shutdown_timeout_secs: u64,
+    // 操作してから送信するまで待つ時間 (ミリ秒, この間に取り消された操作は送らない)
+    pub send_debounce_ms: u64,
+    // 送信する時刻を UTC にするか (false ならローカルタイムゾーン)
+    pub timestamp_utc: bool,
+    // 端末の時計がサーバーの時計とこれ以上ずれていたら送信を保留する (分, 0 なら確かめない)
+    pub max_clock_skew_minutes: u64,
+    // 休憩が長すぎると通知するまでの時間 (分)
+    pub break_warning_minutes: u64,
+    // 1 日に取れる休憩の合計 (分, 0 なら知らせない)
+    pub daily_break_budget_minutes: u64,
+    // 休憩の合計が上限を超えたら休憩中のトレイのアイコンを赤くするか
+    pub tint_over_break_budget: bool,
+    // 休憩せずに働き続けたら休憩を促すまでの時間 (分, 0 なら通知しない)
+    pub stretch_warning_minutes: u64,
+    // 法令などで休憩が義務付けられている連続勤務時間 (分, 0 なら知らせない)
+    pub mandatory_break_after_minutes: u64,
+    // その時間を超えたら休憩するまでトレイのアイコンを点滅させるか
+    pub flash_for_mandatory_break: bool,
+    // 業務中であることをサーバーに知らせる間隔 (分, 0 なら知らせない)
+    pub heartbeat_minutes: u64,
+    // 記録する休憩の時間を丸める単位 (分, 0 なら丸めない)
+    pub break_rounding_minutes: u64,
+    // 休憩の時間の丸め方 ("up" または "nearest")
+    pub break_rounding: BreakRounding,
+    // 記録・送信する業務の開始・終了の時刻を丸める単位 (分, 0 なら丸めない, トレイの経過時間は丸めない)
+    pub clock_rounding_minutes: u64,
+    // 業務の開始・終了の時刻の丸め方 ("up", "down" または "nearest")
+    pub clock_rounding: ClockRounding,
+    // 集計・書き出しで 1 日の実働時間を 15 分単位に丸めるか (トレイの表示は丸めない)
+    pub round_daily_total: bool,
+    // 1 日の目標勤務時間 (分, 0 なら目標なし)
+    pub daily_goal_minutes: u64,
+    // ポモドーロのように集中時間ごとに区切って休憩を勧めるか
+    pub pomodoro: bool,
+    // 集中時間の長さと、集中時間が終わったときに勧める休憩の長さ (分)
+    pub focus_minutes: u64,
+    pub focus_break_minutes: u64,
+    // 集中時間が終わったら通知するだけでなく、休憩を始めるか確認するか
+    pub ask_focus_break: bool,
+    // 曜日ごとの所定労働時間 (1 日の実働がこれを超えた分を残業とする)
+    pub schedule: Schedule,
+    // 入力がないまま経過したら離席とみなして休憩を始める時間 (分, 0 なら検出しない)
+    pub idle_break_minutes: u64,
+    // 休憩中や入力がないまま経過したら自動で業務を終了する時間 (分, 0 なら終了しない)
+    pub auto_clock_out_minutes: u64,
+    // 所定労働時間のある曜日に業務開始を確認する時刻 ("09:00" の形式, 空なら確認しない)
+    pub auto_clock_in_time: String,
+    // 業務中に休憩を勧める時刻 (休憩中・会議中なら勧めない)
+    pub break_reminders: Vec<BreakReminder>,
+    // 休憩の理由 ("昼食" など, 設定するとメニューの「休憩」を理由を選ぶサブメニューにする)
+    pub break_reasons: Vec<String>,
+    // 通知を表示しない時間帯 (記録や送信はいつもどおり行う)
+    pub quiet_hours: Vec<QuietHours>,
+    // 送信の失敗など、気づかないと記録が失われる通知は通知を表示しない時間帯にも表示するか
+    pub critical_in_quiet_hours: bool,
+    // 画面をロックしたら休憩を始め、ロックを解除したらその休憩を終えるか (macOS のみ)
+    pub break_on_screen_lock: bool,
+    // トレイに今回の業務だけでなく、今日のそれまでの業務も含めた実働時間を表示するか
+    pub show_day_total: bool,
+    // 経過時間の表示で 24 時間以上を日数に分けるか (true なら "1d 01:00:00")
+    pub show_days: bool,
+    // トレイの経過時間の表示形式 ({h}, {hh}, {m}, {s} を置き換える, 空なら "hh:mm:ss")
+    pub title_format: String,
+    // トレイの表示を更新する間隔 (秒, 60 以上なら秒を表示しない)
+    pub tick_interval_secs: u64,
+    // トレイのタイトルの表示 ("full", "minutes" または "icon")
+    pub title_mode: TitleMode,
+    // トレイのアイコンをクリック・ダブルクリックしたときの操作 (右クリックはいつもメニューを開く)
+    pub left_click: TrayAction,
+    pub double_click: TrayAction,
+    // 時刻を 24 時間表記で表示するか (false なら "9:12 AM")
+    pub clock_24h: bool,
+    // メニューの文言とサーバーに送る status を変える場合に指定
+    pub labels: Labels,
+    // サーバーが別の項目名を使う場合に指定 ("name" を "employee" にするなど)
+    pub field_names: FieldNames,
+    // ログイン時にアプリを起動するか
+    pub launch_at_login: bool,
+    // 集計で週の始まりとする曜日 ("monday" または "sunday")
+    pub week_start: WeekStart,
+    // 切り替えて使うプロファイル (空なら name と endpoint_url をそのまま使う)
+    pub profiles: Vec<Profile>,
+    // 選択中のプロファイルの label (見つからなければ最初のプロファイル)
+    pub active_profile: String,
+    // 業務開始/終了を切り替えるショートカット (空なら登録しない)
+    pub attendance_shortcut: String,
+    // 休憩を切り替えるショートカット (空なら登録しない)
+    pub break_shortcut: String,
+    // スリープしていた時間の扱い ("count" または "pause")
+    pub sleep_policy: SleepPolicy,
+    // 日付が変わっても業務中だったときの扱い ("warn" または "split")
+    pub midnight_policy: MidnightPolicy,
+    // ログのレベル ("error", "warn", "info", "debug", "trace")
+    pub log_level: String,
+    // 表示する言語 ("ja" または "en", 省略すると OS の言語に合わせる)
+    pub language: Option<Lang>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            endpoint_url: Zeroizing::new(String::new()),
+            secondary_endpoint_url: Zeroizing::new(String::new()),
+            batch_endpoint_url: Zeroizing::new(String::new()),
+            batch_interval_secs: DEFAULT_BATCH_INTERVAL_SECS,
+            signing_secret: Zeroizing::new(String::new()),
+            dry_run: false,
+            reconcile_on_startup: false,
+            webhook_urls: Vec::new(),
+            status_port: 0,
+            event_socket: String::new(),
+            ask_note: false,
+            start_grace_secs: 0,
+            send_device: true,
+            success_notification: SuccessNotification::default(),
+            proxy_url: String::new(),
+            proxy_username: String::new(),
+            proxy_password: Zeroizing::new(String::new()),
+            pinned_certificate: String::new(),
+            allow_insecure: false,
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+            shutdown_timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            send_debounce_ms: DEFAULT_SEND_DEBOUNCE_MS,
+            timestamp_utc: false,
+            max_clock_skew_minutes: DEFAULT_MAX_CLOCK_SKEW_MINUTES,
+            break_warning_minutes: DEFAULT_BREAK_WARNING_MINUTES,
+            daily_break_budget_minutes: 0,
+            tint_over_break_budget: false,
+            stretch_warning_minutes: DEFAULT_STRETCH_WARNING_MINUTES,
+            mandatory_break_after_minutes: 0,
+            flash_for_mandatory_break: false,
+            heartbeat_minutes: 0,
+            break_rounding_minutes: 0,
+            break_rounding: BreakRounding::default(),
+            clock_rounding_minutes: 0,
+            clock_rounding: ClockRounding::default(),
+            round_daily_total: false,
+            daily_goal_minutes: DEFAULT_DAILY_GOAL_MINUTES,
+            pomodoro: false,
+            focus_minutes: DEFAULT_FOCUS_MINUTES,
+            focus_break_minutes: DEFAULT_FOCUS_BREAK_MINUTES,
+            ask_focus_break: false,
+            schedule: Schedule::default(),
+            idle_break_minutes: 0,
+            auto_clock_out_minutes: 0,
+            auto_clock_in_time: String::new(),
+            break_reminders: Vec::new(),
+            break_reasons: Vec::new(),
+            quiet_hours: Vec::new(),
+            critical_in_quiet_hours: false,
+            break_on_screen_lock: false,
+            show_day_total: false,
+            show_days: false,
+            title_format: String::new(),
+            tick_interval_secs: DEFAULT_TICK_INTERVAL_SECS,
+            title_mode: TitleMode::default(),
+            left_click: TrayAction::default(),
+            double_click: TrayAction::None,
+            clock_24h: true,
+            launch_at_login: false,
+            labels: Labels::default(),
+            field_names: FieldNames::default(),
+            week_start: WeekStart::default(),
+            profiles: Vec::new(),
+            active_profile: String::new(),
+            attendance_shortcut: DEFAULT_ATTENDANCE_SHORTCUT.to_string(),
+            break_shortcut: DEFAULT_BREAK_SHORTCUT.to_string(),
+            sleep_policy: SleepPolicy::default(),
+            midnight_policy: MidnightPolicy::default(),
+            log_level: DEFAULT_LOG_LEVEL.to_string(),
+            language: None,
+        }
+    }
+}
+
+// 設定の読み込み・検証で発生するエラー
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    EmptyName,
+    InvalidUrl(url::ParseError),
+    InsecureUrl(String),
+    InvalidProxyUrl(url::ParseError),
+    EmptyLabel(&'static str),
+    EmptyFieldName(&'static str),
+    DuplicateFieldName(String),
+    InvalidTime(String),
+    InvalidReminderTime(String),
+    InvalidQuietHours(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "設定ファイルを読み込めません: {}", e),
+            ConfigError::Parse(e) => write!(f, "設定ファイルの形式が正しくありません: {}", e),
+            ConfigError::EmptyName => write!(
+                f,
+                "名前が設定されていません。{} の \"name\" を設定してください",
+                CONFIG_FILE_NAME
+            ),
+            ConfigError::InvalidUrl(e) => write!(f, "送信先 URL が正しくありません: {}", e),
+            ConfigError::InsecureUrl(url) => write!(
+                f,
+                "送信先 URL は https である必要があります: {} (許可する場合は \"allow_insecure\" を true にしてください)",
+                redact_url(url)
+            ),
+            ConfigError::InvalidProxyUrl(e) => write!(f, "プロキシの URL が正しくありません: {}", e),
+            ConfigError::EmptyLabel(key) => write!(
+                f,
+                "\"labels\" の \"{}\" が空です。省略するとデフォルトの文言を使います",
+                key
+            ),
+            ConfigError::EmptyFieldName(key) => write!(
+                f,
+                "\"field_names\" の \"{}\" が空です。省略するとデフォルトの項目名を使います",
+                key
+            ),
+            ConfigError::DuplicateFieldName(name) => write!(
+                f,
+                "\"field_names\" で \"{}\" が複数の項目に使われています",
+                name
+            ),
+            ConfigError::InvalidTime(time) => write!(
+                f,
+                "\"auto_clock_in_time\" が正しくありません: {} (\"09:00\" の形式で指定してください)",
+                time
+            ),
+            ConfigError::InvalidReminderTime(time) => write!(
+                f,
+                "\"break_reminders\" の時刻が正しくありません: {} (\"12:00\" の形式で指定してください)",
+                time
+            ),
+            ConfigError::InvalidQuietHours(time) => write!(
+                f,
+                "\"quiet_hours\" の時刻が正しくありません: {} (\"22:00\" の形式で指定してください)",
+                time
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    // 設定ファイルを読み込む (ファイルがなければ OS のユーザー名を使う)
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let mut config = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).map_err(ConfigError::Parse)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(ConfigError::Io(e)),
+        };
+
+        if config.name.trim().is_empty() {
+            config.name = os_username().unwrap_or_default();
+        }
+        Ok(config)
+    }
+
+    // 設定ファイルに書き込む
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        file::write_atomic(path, &content)
+    }
+
+    // 選択中のプロファイル (プロファイルがなければ None)
+    pub fn active(&self) -> Option<&Profile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.label == self.active_profile)
+            .or_else(|| self.profiles.first())
+    }
+
+    // 選択中のプロファイルの名前と送信先を反映した設定
+    pub fn with_active_profile(&self) -> Config {
+        let mut config = self.clone();
+        if let Some(profile) = self.active() {
+            if !profile.name.trim().is_empty() {
+                config.name = profile.name.clone();
+            }
+            if !profile.endpoint_url.trim().is_empty() {
+                config.endpoint_url = Zeroizing::new(profile.endpoint_url.clone());
+            }
+        }
+        config
+    }
+
+    // トレイの表示を更新する間隔 (0 なら 1 秒)
+    pub fn tick_interval(&self) -> Duration {
+        Duration::from_secs(self.tick_interval_secs.max(1))
+    }
+
+    // 業務開始の猶予 (猶予なしなら None)
+    pub fn start_grace(&self) -> Option<Duration> {
+        (self.start_grace_secs > 0).then(|| Duration::from_secs(self.start_grace_secs))
+    }
+
+    // トレイのアイコンをクリックしたときの操作 (右クリックは OS の慣習どおりいつもメニューを開く)
+    pub fn tray_action(&self, click: TrayClick) -> TrayAction {
+        match click {
+            TrayClick::Left => self.left_click,
+            TrayClick::Double => self.double_click,
+            TrayClick::Right => TrayAction::Menu,
+        }
+    }
+
+    // 左クリックでメニューを開くか (トレイを作るときに OS に指定する)
+    pub fn menu_on_left_click(&self) -> bool {
+        self.tray_action(TrayClick::Left) == TrayAction::Menu
+    }
+
+    // ポモドーロの集中時間 (使わない設定なら None)
+    pub fn focus_block(&self) -> Option<Duration> {
+        (self.pomodoro && self.focus_minutes > 0)
+            .then(|| Duration::from_secs(self.focus_minutes * 60))
+    }
+
+    // トレイの経過時間を分単位で表示するか (分の表示にした場合と、秒を表示しても 1 分ごとにしか変わらない場合)
+    pub fn minute_precision(&self) -> bool {
+        self.title_mode == TitleMode::Minutes || self.tick_interval_secs >= 60
+    }
+
+    // 署名に使う秘密鍵 (設定されていなければ None)
+    pub fn signing_secret(&self) -> Option<&str> {
+        Some(self.signing_secret.as_str()).filter(|secret| !secret.is_empty())
+    }
+
+    // 送信に使える設定かどうかを検証
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.name.trim().is_empty() {
+            return Err(ConfigError::EmptyName);
+        }
+        Ok(())
+    }
+
+    // 送信先 URL を検証して取得
+    pub fn endpoint(&self) -> Result<Url, ConfigError> {
+        self.parse_endpoint(&self.endpoint_url)
+    }
+
+    // 送信先 URL として使えるか検証 (https 以外は allow_insecure のときだけ許可)
+    pub fn parse_endpoint(&self, endpoint_url: &str) -> Result<Url, ConfigError> {
+        let url = Url::parse(endpoint_url).map_err(ConfigError::InvalidUrl)?;
+        if url.scheme() != "https" && !self.allow_insecure {
+            return Err(ConfigError::InsecureUrl(endpoint_url.to_string()));
+        }
+        Ok(url)
+    }
+
+    // まとめて送る送信先 URL を検証して取得 (設定されていなければ None)
+    pub fn batch_endpoint(&self) -> Result<Option<Url>, ConfigError> {
+        if self.batch_endpoint_url.trim().is_empty() {
+            return Ok(None);
+        }
+        self.parse_endpoint(self.batch_endpoint_url.trim())
+            .map(Some)
+    }
+
+    // プロキシの URL を検証して取得 (設定されていなければ None)
+    pub fn proxy(&self) -> Result<Option<Url>, ConfigError> {
+        if self.proxy_url.trim().is_empty() {
+            return Ok(None);
+        }
+        Url::parse(self.proxy_url.trim())
+            .map(Some)
+            .map_err(ConfigError::InvalidProxyUrl)
+    }
+
+    // 業務開始を確認する時刻を検証して取得 (設定されていなければ None)
+    pub fn auto_clock_in_time(&self) -> Result<Option<NaiveTime>, ConfigError> {
+        let time = self.auto_clock_in_time.trim();
+        if time.is_empty() {
+            return Ok(None);
+        }
+        NaiveTime::parse_from_str(time, "%H:%M")
+            .map(Some)
+            .map_err(|_| ConfigError::InvalidTime(time.to_string()))
+    }
+}
+
+// Tauri を起動する前に設定ファイルを読み込む (トレイの作り方を決める用, 読み込めなければデフォルト)
+pub fn load_before_launch(context: &tauri::Config) -> Config {
+    CONFIG_PATH_OVERRIDE
+        .get()
+        .cloned()
+        .or_else(|| tauri::api::path::app_config_dir(context).map(|dir| dir.join(CONFIG_FILE_NAME)))
+        .and_then(|path| Config::load(&path).ok())
+        .unwrap_or_default()
+}
+
+// コマンドライン引数 (--config <path>) か環境変数で指定された設定ファイルを使うようにする
+// (引数を優先する, 指定されたファイルが開けない場合はエラー)
+pub fn init_path_override() -> Result<(), String> {
+    let Some(path) = path_from_args(env::var_os(CONFIG_PATH_ENV), env::args().skip(1))? else {
+        return Ok(());
+    };
+    let _ = CONFIG_PATH_OVERRIDE.set(resolve_path(&path)?);
+    Ok(())
+}
+
+// 環境変数の値とコマンドライン引数から指定された設定ファイルのパスを取り出す (引数を優先する)
+fn path_from_args(
+    env_value: Option<OsString>,
+    args: impl IntoIterator<Item = String>,
+) -> Result<Option<PathBuf>, String> {
+    let mut path = env_value.map(PathBuf::from);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            let value = args
+                .next()
+                .ok_or("--config には設定ファイルのパスを指定してください")?;
+            path = Some(value.into());
+        } else if let Some(value) = arg.strip_prefix("--config=") {
+            path = Some(value.into());
+        }
+    }
+    Ok(path)
+}
+
+// 指定された設定ファイルの絶対パス (作業ディレクトリが変わっても同じファイルを指すようにする)
+fn resolve_path(path: &Path) -> Result<PathBuf, String> {
+    let resolved = fs::canonicalize(path)
+        .map_err(|e| format!("設定ファイルを開けません: {} ({})", path.display(), e))?;
+    if !resolved.is_file() {
+        return Err(format!("設定ファイルではありません: {}", path.display()));
+    }
+    Ok(resolved)
+}
+
+// 設定ファイルのパスを取得 (起動時に指定されていればそのパス)
+pub fn config_path(app: &AppHandle) -> Option<PathBuf> {
+    if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+        return Some(path.clone());
+    }
+    app.path_resolver()
+        .app_config_dir()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+}
+
+// ログなどに出すための URL (パスやクエリには送信先の ID などが含まれるので省く)
+pub fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(url) => format!(
+            "{}://{}/...",
+            url.scheme(),
+            url.host_str().unwrap_or_default()
+        ),
+        Err(_) => "(不正な URL)".to_string(),
+    }
+}
+
+// OS のログインユーザー名を取得
+fn os_username() -> Option<String> {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .ok()
+        .filter(|name| !name.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_is_empty_by_default() {
+        let config = Config::default();
+        assert!(config.endpoint_url.is_empty());
+        assert!(matches!(config.endpoint(), Err(ConfigError::InvalidUrl(_))));
+    }
+
+    #[test]
+    fn https_endpoint_is_accepted() {
+        let config = Config::default();
+        let url = config.parse_endpoint("https://example.com/exec").unwrap();
+        assert_eq!(url.scheme(), "https");
+    }
+
+    #[test]
+    fn non_https_endpoint_is_rejected_unless_allowed() {
+        let mut config = Config::default();
+        for url in ["http://example.com/exec", "ftp://example.com/exec"] {
+            assert!(matches!(
+                config.parse_endpoint(url),
+                Err(ConfigError::InsecureUrl(_))
+            ));
+        }
+
+        config.allow_insecure = true;
+        assert!(config.parse_endpoint("http://localhost:8080/exec").is_ok());
+    }
+
+    // 名前と送信先を指定したプロファイル
+    fn profile(label: &str, name: &str, endpoint_url: &str) -> Profile {
+        Profile {
+            label: label.to_string(),
+            name: name.to_string(),
+            endpoint_url: endpoint_url.to_string(),
+        }
+    }
+
+    fn with_profiles() -> Config {
+        let mut config = Config {
+            name: "佐藤".to_string(),
+            ..Default::default()
+        };
+        config.endpoint_url.push_str("https://example.com/default");
+        config.profiles = vec![
+            profile("A 社", "sato-a", "https://example.com/a"),
+            profile("B 社", "", ""),
+        ];
+        config
+    }
+
+    #[test]
+    fn selected_profile_overrides_name_and_endpoint() {
+        let mut config = with_profiles();
+        config.active_profile = "A 社".to_string();
+        let active = config.with_active_profile();
+        assert_eq!(active.name, "sato-a");
+        assert_eq!(active.endpoint_url.as_str(), "https://example.com/a");
+    }
+
+    #[test]
+    fn blank_profile_fields_fall_back_to_global() {
+        let mut config = with_profiles();
+        config.active_profile = "B 社".to_string();
+        let active = config.with_active_profile();
+        assert_eq!(active.name, "佐藤");
+        assert_eq!(active.endpoint_url.as_str(), "https://example.com/default");
+    }
+
+    #[test]
+    fn unknown_profile_falls_back_to_first() {
+        let mut config = with_profiles();
+        config.active_profile = "C 社".to_string();
+        assert_eq!(config.active().map(|p| p.label.as_str()), Some("A 社"));
+
+        config.profiles.clear();
+        assert!(config.active().is_none());
+        assert_eq!(config.with_active_profile(), config);
+    }
+
+    // テストごとに別の設定ファイル
+    fn temp_config(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("kintai-config-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(CONFIG_FILE_NAME)
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn config_path_comes_from_args_or_env() {
+        assert_eq!(path_from_args(None, args(&[])), Ok(None));
+        assert_eq!(
+            path_from_args(Some("/env.json".into()), args(&[])),
+            Ok(Some(PathBuf::from("/env.json")))
+        );
+        // 引数を優先する
+        assert_eq!(
+            path_from_args(Some("/env.json".into()), args(&["--config", "/arg.json"])),
+            Ok(Some(PathBuf::from("/arg.json")))
+        );
+        assert_eq!(
+            path_from_args(None, args(&["--config=/arg.json"])),
+            Ok(Some(PathBuf::from("/arg.json")))
+        );
+        assert!(path_from_args(None, args(&["--config"])).is_err());
+    }
+
+    #[test]
+    fn loads_config_from_explicit_path() {
+        let path = temp_config("explicit");
+        fs::write(
+            &path,
+            r#"{ "name": "佐藤", "endpoint_url": "https://example.com/exec" }"#,
+        )
+        .unwrap();
+
+        let resolved = resolve_path(&path).unwrap();
+        assert!(resolved.is_absolute());
+        let config = Config::load(&resolved).unwrap();
+        assert_eq!(config.name, "佐藤");
+        assert_eq!(config.endpoint_url.as_str(), "https://example.com/exec");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn explicit_path_must_be_an_existing_file() {
+        let path = temp_config("missing");
+        let _ = fs::remove_file(&path);
+        assert!(resolve_path(&path).is_err());
+        assert!(resolve_path(path.parent().unwrap()).is_err());
+    }
+
+    #[test]
+    fn broken_config_file_is_an_error() {
+        let path = temp_config("broken");
+        fs::write(&path, "{ \"name\": ").unwrap();
+        assert!(matches!(Config::load(&path), Err(ConfigError::Parse(_))));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn saved_config_loads_back() {
+        let path = temp_config("saved");
+        let mut config = with_profiles();
+        config.daily_goal_minutes = 420;
+        config.save(&path).unwrap();
+        assert_eq!(Config::load(&path).unwrap(), config);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn break_rounding_boundaries() {
+        let step = Duration::from_secs(15 * 60);
+        let m = |m: u64| Duration::from_secs(m * 60);
+        // 切り上げなら 1 秒でも超えたら次の区切り
+        assert_eq!(BreakRounding::Up.round(m(7), step), m(15));
+        assert_eq!(BreakRounding::Up.round(m(8), step), m(15));
+        assert_eq!(BreakRounding::Up.round(m(15), step), m(15));
+        assert_eq!(
+            BreakRounding::Up.round(m(15) + Duration::from_secs(1), step),
+            m(30)
+        );
+        assert_eq!(
+            BreakRounding::Up.round(Duration::ZERO, step),
+            Duration::ZERO
+        );
+        // 近いほうなら 7 分 30 秒未満は切り捨て、それ以上は切り上げ
+        assert_eq!(BreakRounding::Nearest.round(m(7), step), Duration::ZERO);
+        assert_eq!(
+            BreakRounding::Nearest.round(m(7) + Duration::from_secs(29), step),
+            Duration::ZERO
+        );
+        assert_eq!(
+            BreakRounding::Nearest.round(m(7) + Duration::from_secs(30), step),
+            m(15)
+        );
+        assert_eq!(BreakRounding::Nearest.round(m(8), step), m(15));
+        assert_eq!(BreakRounding::Nearest.round(m(22), step), m(15));
+        assert_eq!(BreakRounding::Nearest.round(m(23), step), m(30));
+    }
+
+    #[test]
+    fn break_rounding_is_off_without_a_step() {
+        let raw = Duration::from_secs(7 * 60 + 13);
+        for rounding in [BreakRounding::Up, BreakRounding::Nearest] {
+            assert_eq!(rounding.round(raw, Duration::ZERO), raw);
+        }
+    }
+
+    #[test]
+    fn empty_labels_are_rejected() {
+        let mut labels = Labels::default();
+        assert!(labels.validate().is_ok());
+        labels.work_end = Some("退勤".to_string());
+        assert!(labels.validate().is_ok());
+        labels.end_break = Some("  ".to_string());
+        assert!(matches!(
+            labels.validate(),
+            Err(ConfigError::EmptyLabel("end_break"))
+        ));
+    }
+
+    // 2024 年 4 月 day 日 h 時 m 分 (4/1 は月曜日)
+    fn naive(day: u32, h: u32, m: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2024, 4, day)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    // from から to まで step ごとに確認したとき、業務開始を確認する時刻
+    fn auto_clock_ins(
+        schedule: &Schedule,
+        start: NaiveTime,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        step: chrono::Duration,
+    ) -> Vec<NaiveDateTime> {
+        let (mut last_checked, mut asked) = (from, Vec::new());
+        while last_checked < to {
+            let now = last_checked + step;
+            if schedule.start_passed(start, last_checked, now) {
+                asked.push(now);
+            }
+            last_checked = now;
+        }
+        asked
+    }
+
+    #[test]
+    fn auto_clock_in_is_asked_once_on_each_working_day() {
+        let start = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let asked = auto_clock_ins(
+            &Schedule::default(),
+            start,
+            naive(1, 0, 0),
+            naive(8, 0, 0),
+            chrono::Duration::minutes(1),
+        );
+        // 月曜から金曜の 9:00 に 1 回ずつ (土日は所定労働時間がないので確認しない)
+        assert_eq!(
+            asked,
+            [
+                naive(1, 9, 0),
+                naive(2, 9, 0),
+                naive(3, 9, 0),
+                naive(4, 9, 0),
+                naive(5, 9, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn auto_clock_in_is_asked_after_sleeping_through_the_time() {
+        let start = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        // 8:30 から 9:40 までスリープしていた
+        assert!(Schedule::default().start_passed(start, naive(1, 8, 30), naive(1, 9, 40)));
+        // 起動した時点で過ぎていれば今日は確認しない
+        assert!(!Schedule::default().start_passed(start, naive(1, 9, 1), naive(1, 9, 2)));
+        // 前日からスリープしていても今日の時刻を過ぎた日だけ確認する
+        assert!(!Schedule::default().start_passed(start, naive(1, 18, 0), naive(2, 8, 0)));
+        assert!(Schedule::default().start_passed(start, naive(1, 18, 0), naive(2, 10, 0)));
+    }
+
+    #[test]
+    fn time_passed_includes_the_exact_minute_once() {
+        let noon = NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+        assert!(time_passed(noon, naive(1, 11, 59), naive(1, 12, 0)));
+        assert!(!time_passed(noon, naive(1, 12, 0), naive(1, 12, 1)));
+        assert!(!time_passed(noon, naive(1, 11, 0), naive(1, 11, 59)));
+    }
+
+    #[test]
+    fn auto_clock_in_time_is_validated() {
+        let mut config = Config::default();
+        assert_eq!(config.auto_clock_in_time().unwrap(), None);
+        config.auto_clock_in_time = " 08:45 ".to_string();
+        assert_eq!(
+            config.auto_clock_in_time().unwrap(),
+            NaiveTime::from_hms_opt(8, 45, 0)
+        );
+        config.auto_clock_in_time = "9時".to_string();
+        assert!(matches!(
+            config.auto_clock_in_time(),
+            Err(ConfigError::InvalidTime(time)) if time == "9時"
+        ));
+    }
+
+    #[test]
+    fn tray_clicks_resolve_to_the_configured_action() {
+        let config = Config::default();
+        assert_eq!(config.tray_action(TrayClick::Left), TrayAction::Toggle);
+        assert_eq!(config.tray_action(TrayClick::Double), TrayAction::None);
+        assert_eq!(config.tray_action(TrayClick::Right), TrayAction::Menu);
+        assert!(!config.menu_on_left_click());
+
+        let config: Config =
+            serde_json::from_str(r#"{"left_click": "menu", "double_click": "toggle_break"}"#)
+                .unwrap();
+        assert_eq!(config.tray_action(TrayClick::Left), TrayAction::Menu);
+        assert_eq!(
+            config.tray_action(TrayClick::Double),
+            TrayAction::ToggleBreak
+        );
+        assert!(config.menu_on_left_click());
+    }
+
+    #[test]
+    fn right_click_always_opens_the_menu() {
+        for action in [
+            TrayAction::Menu,
+            TrayAction::Toggle,
+            TrayAction::ToggleWork,
+            TrayAction::ToggleBreak,
+            TrayAction::Totals,
+            TrayAction::None,
+        ] {
+            let config = Config {
+                left_click: action,
+                double_click: action,
+                ..Config::default()
+            };
+            assert_eq!(config.tray_action(TrayClick::Left), action);
+            assert_eq!(config.tray_action(TrayClick::Double), action);
+            assert_eq!(config.tray_action(TrayClick::Right), TrayAction::Menu);
+        }
+    }
+
+    #[test]
+    fn unknown_tray_action_is_rejected() {
+        assert!(serde_json::from_str::<Config>(r#"{"left_click": "open_menu"}"#).is_err());
+    }
+
+    // from から to まで step ごとに確認したとき、休憩を勧める時刻と名前
+    fn reminders_fired(
+        reminders: &[BreakReminder],
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        step: chrono::Duration,
+    ) -> Vec<(NaiveDateTime, &str)> {
+        let (mut last_checked, mut fired) = (from, Vec::new());
+        while last_checked < to {
+            let now = last_checked + step;
+            for reminder in reminders {
+                if reminder.is_due(last_checked, now).unwrap() {
+                    fired.push((now, reminder.label.as_str()));
+                }
+            }
+            last_checked = now;
+        }
+        fired
+    }
+
+    fn reminder(time: &str, label: &str) -> BreakReminder {
+        BreakReminder {
+            time: time.to_string(),
+            label: label.to_string(),
+        }
+    }
+
+    #[test]
+    fn break_reminders_fire_once_at_each_time() {
+        let reminders = [reminder("12:00", "昼休み"), reminder("15:00", "おやつ")];
+        let fired = reminders_fired(
+            &reminders,
+            naive(1, 8, 0),
+            naive(2, 16, 0),
+            chrono::Duration::seconds(30),
+        );
+        assert_eq!(
+            fired,
+            [
+                (naive(1, 12, 0), "昼休み"),
+                (naive(1, 15, 0), "おやつ"),
+                (naive(2, 12, 0), "昼休み"),
+                (naive(2, 15, 0), "おやつ"),
+            ]
+        );
+    }
+
+    #[test]
+    fn break_reminder_passed_during_sleep_fires_on_wake() {
+        let reminders = [reminder("12:00", "昼休み")];
+        // 11:50 から 12:20 までスリープしていた
+        assert_eq!(
+            reminders_fired(
+                &reminders,
+                naive(1, 11, 50),
+                naive(1, 12, 20),
+                chrono::Duration::minutes(30),
+            ),
+            [(naive(1, 12, 20), "昼休み")]
+        );
+    }
+
+    #[test]
+    fn break_reminder_time_is_validated() {
+        assert!(reminder(" 12:30 ", "")
+            .is_due(naive(1, 12, 0), naive(1, 13, 0))
+            .unwrap());
+        assert!(matches!(
+            reminder("12時", "").is_due(naive(1, 12, 0), naive(1, 13, 0)),
+            Err(ConfigError::InvalidReminderTime(_))
+        ));
+    }
+
+    // 2024-04-01 の h 時 m 分 s 秒 (ローカル時刻)
+    fn clock(h: u32, m: u32, s: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 4, 1, h, m, s).unwrap()
+    }
+
+    #[test]
+    fn clock_rounding_up_moves_to_the_next_slot() {
+        let step = Duration::from_secs(5 * 60);
+        let up = |time| ClockRounding::Up.round(time, step);
+        assert_eq!(up(clock(9, 0, 0)), clock(9, 0, 0));
+        assert_eq!(up(clock(9, 0, 1)), clock(9, 5, 0));
+        assert_eq!(up(clock(9, 4, 59)), clock(9, 5, 0));
+        assert_eq!(up(clock(9, 5, 0)), clock(9, 5, 0));
+        assert_eq!(up(clock(9, 57, 30)), clock(10, 0, 0));
+        // 日をまたいで切り上げる
+        assert_eq!(
+            up(clock(23, 58, 0)),
+            Local.with_ymd_and_hms(2024, 4, 2, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn clock_rounding_down_stays_in_the_slot() {
+        let step = Duration::from_secs(5 * 60);
+        let down = |time| ClockRounding::Down.round(time, step);
+        assert_eq!(down(clock(9, 0, 0)), clock(9, 0, 0));
+        assert_eq!(down(clock(9, 4, 59)), clock(9, 0, 0));
+        assert_eq!(down(clock(9, 5, 0)), clock(9, 5, 0));
+        assert_eq!(down(clock(8, 59, 59)), clock(8, 55, 0));
+        // 秒未満は切り捨ててから丸める
+        let almost = clock(9, 4, 59) + chrono::Duration::milliseconds(900);
+        assert_eq!(down(almost), clock(9, 0, 0));
+        assert_eq!(
+            ClockRounding::Up.round(clock(9, 0, 0) + chrono::Duration::milliseconds(500), step),
+            clock(9, 0, 0)
+        );
+    }
+
+    #[test]
+    fn clock_rounding_nearest_rounds_half_up() {
+        let step = Duration::from_secs(5 * 60);
+        let nearest = |time| ClockRounding::Nearest.round(time, step);
+        assert_eq!(nearest(clock(9, 2, 29)), clock(9, 0, 0));
+        assert_eq!(nearest(clock(9, 2, 30)), clock(9, 5, 0));
+        assert_eq!(nearest(clock(9, 7, 29)), clock(9, 5, 0));
+        assert_eq!(nearest(clock(8, 58, 0)), clock(9, 0, 0));
+        // 15 分ごとでもローカルの時計の区切りに合わせる
+        let quarter = Duration::from_secs(15 * 60);
+        assert_eq!(
+            ClockRounding::Nearest.round(clock(17, 53, 0), quarter),
+            clock(18, 0, 0)
+        );
+        assert_eq!(
+            ClockRounding::Nearest.round(clock(17, 37, 29), quarter),
+            clock(17, 30, 0)
+        );
+    }
+
+    #[test]
+    fn clock_rounding_is_off_without_a_step() {
+        let time = clock(9, 3, 17) + chrono::Duration::milliseconds(250);
+        for rounding in [
+            ClockRounding::Up,
+            ClockRounding::Down,
+            ClockRounding::Nearest,
+        ] {
+            assert_eq!(rounding.round(time, Duration::ZERO), time);
+        }
+    }
+
+    #[test]
+    fn field_names_default_to_the_current_keys() {
+        let names = FieldNames::default();
+        assert!(names.validate().is_ok());
+        for key in ["name", "status", "timestamp", "note", "signature"] {
+            assert_eq!(names.rename(key), key);
+        }
+    }
+
+    #[test]
+    fn field_names_are_renamed_from_the_config() {
+        let config: Config =
+            serde_json::from_str(r#"{"field_names": {"name": "employee", "status": "state"}}"#)
+                .unwrap();
+        let names = &config.field_names;
+        assert!(names.validate().is_ok());
+        assert_eq!(names.rename("name"), "employee");
+        assert_eq!(names.rename("status"), "state");
+        assert_eq!(names.rename("timestamp"), "timestamp");
+    }
+
+    #[test]
+    fn field_names_must_be_distinct_and_not_empty() {
+        // 他の項目のデフォルトの名前と重なる
+        let names = FieldNames {
+            status: Some("name".to_string()),
+            ..FieldNames::default()
+        };
+        assert!(matches!(
+            names.validate(),
+            Err(ConfigError::DuplicateFieldName(name)) if name == "name"
+        ));
+
+        // 2 つの項目に同じ名前を付ける
+        let names = FieldNames {
+            name: Some("employee".to_string()),
+            device: Some("employee".to_string()),
+            ..FieldNames::default()
+        };
+        assert!(matches!(
+            names.validate(),
+            Err(ConfigError::DuplicateFieldName(name)) if name == "employee"
+        ));
+
+        // 名前を入れ替えるのはかまわない
+        let names = FieldNames {
+            name: Some("status".to_string()),
+            status: Some("name".to_string()),
+            ..FieldNames::default()
+        };
+        assert!(names.validate().is_ok());
+
+        let names = FieldNames {
+            note: Some(" ".to_string()),
+            ..FieldNames::default()
+        };
+        assert!(matches!(
+            names.validate(),
+            Err(ConfigError::EmptyFieldName("note"))
+        ));
+    }
+}
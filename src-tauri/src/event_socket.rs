@@ -0,0 +1,255 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use tracing::{debug, warn};
+
+// 読み取らないクライアントで状態遷移が止まらないように、書き込みを待つ時間
+#[cfg(unix)]
+const WRITE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+// 自分だけが入れるフォルダの中でソケットを作って権限を絞ってから path に移す
+// (作った直後のソケットは umask の権限なので、path に直接作ると権限を絞るまでに他のユーザーが接続できる)
+#[cfg(unix)]
+fn bind_private(path: &Path) -> io::Result<std::os::unix::net::UnixListener> {
+    use std::fs::{self, DirBuilder};
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "ソケットの名前がありません"))?;
+    let private = parent.join(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    DirBuilder::new().mode(0o700).create(&private)?;
+
+    let temp = private.join("socket");
+    let bound = UnixListener::bind(&temp).and_then(|listener| {
+        fs::set_permissions(&temp, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&temp, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&temp);
+    let _ = fs::remove_dir(&private);
+    bound
+}
+
+// 接続しているクライアントに 1 行ずつ送るイベント
+#[derive(Debug, Clone, Serialize)]
+pub struct Event<'a> {
+    pub kind: &'a str,
+    pub action: &'a str,
+    pub timestamp: DateTime<Local>,
+    pub note: Option<&'a str>,
+}
+
+// 勤務状態が変わるたびに、ソケットに接続しているクライアントへイベントを送る
+// (ステータスサーバーと違って、クライアントから取りに来なくても送る)
+#[derive(Default)]
+pub struct Publisher {
+    subscribers: Arc<Mutex<Vec<Box<dyn Write + Send>>>>,
+}
+
+impl Publisher {
+    // path の Unix ドメインソケットで接続を受け付けるスレッドを始める
+    // (前回のソケットが残っていれば消す, 他のユーザーからは接続できないようにする)
+    #[cfg(unix)]
+    pub fn start(path: &Path) -> io::Result<Self> {
+        use std::fs;
+        use std::os::unix::fs::FileTypeExt;
+        use std::thread;
+
+        if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            fs::remove_file(path)?;
+        }
+        let listener = bind_private(path)?;
+
+        let publisher = Self::default();
+        let subscribers = Arc::clone(&publisher.subscribers);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream.and_then(|stream| {
+                    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                    Ok(stream)
+                }) {
+                    Ok(stream) => {
+                        debug!("イベントのソケットに接続されました");
+                        subscribers.lock().unwrap().push(Box::new(stream));
+                    }
+                    Err(e) => warn!(
+                        "イベントのソケットへの接続を受け付けられませんでした: {}",
+                        e
+                    ),
+                }
+            }
+        });
+        Ok(publisher)
+    }
+
+    // Windows の名前付きパイプにはまだ対応していない
+    #[cfg(not(unix))]
+    pub fn start(_path: &Path) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "このプラットフォームではイベントのソケットを使えません",
+        ))
+    }
+
+    // イベントを 1 行の JSON にして送る (書き込めなくなったクライアントは外す)
+    pub fn publish(&self, event: &Event) {
+        let mut line = match serde_json::to_string(event) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("イベントを JSON にできませんでした: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+        self.subscribers.lock().unwrap().retain_mut(|subscriber| {
+            let result = subscriber
+                .write_all(line.as_bytes())
+                .and_then(|()| subscriber.flush());
+            if let Err(e) = &result {
+                debug!("イベントのソケットから切断しました: {}", e);
+            }
+            result.is_ok()
+        });
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use chrono::TimeZone;
+
+    fn socket_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("kintai-socket-{}-{}", name, std::process::id()))
+    }
+
+    // 接続して、受け付けたクライアントが count 個になるまで待つ
+    fn subscribe(publisher: &Publisher, path: &Path, count: usize) -> UnixStream {
+        let stream = UnixStream::connect(path).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while publisher.subscribers.lock().unwrap().len() < count {
+            assert!(Instant::now() < deadline, "接続を受け付けませんでした");
+            thread::sleep(Duration::from_millis(10));
+        }
+        stream
+    }
+
+    fn event<'a>(kind: &'a str, action: &'a str, note: Option<&'a str>) -> Event<'a> {
+        Event {
+            kind,
+            action,
+            timestamp: Local.with_ymd_and_hms(2024, 4, 1, 9, 0, 0).unwrap(),
+            note,
+        }
+    }
+
+    #[test]
+    fn subscriber_receives_one_json_line_per_event() {
+        let path = socket_path("lines");
+        let publisher = Publisher::start(&path).unwrap();
+        let mut lines = BufReader::new(subscribe(&publisher, &path, 1)).lines();
+
+        publisher.publish(&event("work", "start", Some("在宅\n午前")));
+        publisher.publish(&event("break", "start", None));
+
+        let first: serde_json::Value =
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(first["kind"], "work");
+        assert_eq!(first["action"], "start");
+        // メモの改行はエスケープされて 1 行に収まる
+        assert_eq!(first["note"], "在宅\n午前");
+        assert_eq!(
+            first["timestamp"]
+                .as_str()
+                .unwrap()
+                .parse::<DateTime<Local>>()
+                .unwrap(),
+            Local.with_ymd_and_hms(2024, 4, 1, 9, 0, 0).unwrap()
+        );
+        let second: serde_json::Value =
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(second["kind"], "break");
+        assert_eq!(second["note"], serde_json::Value::Null);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn every_subscriber_receives_the_event_and_closed_ones_are_dropped() {
+        let path = socket_path("subscribers");
+        let publisher = Publisher::start(&path).unwrap();
+        let closed = subscribe(&publisher, &path, 1);
+        let open = subscribe(&publisher, &path, 2);
+        drop(closed);
+
+        // 切断したクライアントは書き込みに失敗した時点で外す
+        publisher.publish(&event("work", "start", None));
+        publisher.publish(&event("work", "end", None));
+        assert_eq!(publisher.subscribers.lock().unwrap().len(), 1);
+
+        let actions: Vec<String> = BufReader::new(open)
+            .lines()
+            .take(2)
+            .map(|line| {
+                let value: serde_json::Value = serde_json::from_str(&line.unwrap()).unwrap();
+                value["action"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(actions, ["start", "end"]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn stale_socket_is_replaced() {
+        let path = socket_path("stale");
+        drop(Publisher::start(&path).unwrap());
+        // 前回のソケットが残っていても始められる
+        let publisher = Publisher::start(&path).unwrap();
+        drop(subscribe(&publisher, &path, 1));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn socket_is_created_private_to_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = socket_path("private");
+        let publisher = Publisher::start(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // ソケットを作ったフォルダは残さない
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let leftover = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(Result::ok)
+            .any(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(&format!(".{}", name))
+            });
+        assert!(!leftover);
+        drop(subscribe(&publisher, &path, 1));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,99 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+
+// ファイルに書き込む (書き込み途中で落ちても壊れないよう一時ファイルに書いてから置き換える)
+pub fn write_atomic(path: &Path, content: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp_path = with_suffix(path, ".tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)
+}
+
+// 読み込めなかったファイルを "state.json.20240401-090000.bak" のように名前を変えて複製し、そのパスを返す
+// (デフォルトの内容で上書きされても元のファイルを確認できるようにする)
+pub fn back_up(path: &Path) -> io::Result<PathBuf> {
+    let backup = with_suffix(
+        path,
+        &format!(".{}.bak", Local::now().format("%Y%m%d-%H%M%S")),
+    );
+    fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+// ファイル名の後ろに suffix を付けたパス
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    // テストごとに別のディレクトリ
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("kintai-file-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn write_atomic_creates_and_replaces_the_file() {
+        let dir = temp_dir("write");
+        let path = dir.join("nested").join("state.json");
+        write_atomic(&path, "1").unwrap();
+        write_atomic(&path, "2").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "2");
+        // 一時ファイルは残らない
+        assert!(!with_suffix(&path, ".tmp").exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn failed_write_keeps_the_previous_content() {
+        let dir = temp_dir("failed");
+        let path = dir.join("state.json");
+        write_atomic(&path, "前の内容").unwrap();
+        // 一時ファイルに書き込めない状態にする
+        fs::create_dir_all(with_suffix(&path, ".tmp")).unwrap();
+        assert!(write_atomic(&path, "新しい内容").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "前の内容");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn corrupt_file_is_kept_as_a_backup_before_being_overwritten() {
+        let dir = temp_dir("backup");
+        let path = dir.join("state.json");
+        write_atomic(&path, "{ not json").unwrap();
+
+        let backup = back_up(&path).unwrap();
+        let name = backup.file_name().unwrap().to_string_lossy().to_string();
+        assert!(
+            name.starts_with("state.json.") && name.ends_with(".bak"),
+            "{}",
+            name
+        );
+        assert_eq!(backup.parent(), path.parent());
+
+        // デフォルトの内容で上書きしても壊れていた内容は残る
+        write_atomic(&path, "{}").unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "{ not json");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn missing_file_cannot_be_backed_up() {
+        let dir = temp_dir("missing");
+        assert!(back_up(&dir.join("state.json")).is_err());
+    }
+}
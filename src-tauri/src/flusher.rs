@@ -0,0 +1,808 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, Utc};
+use reqwest::Proxy;
+use tauri::{AppHandle, Manager};
+use tokio::sync::oneshot;
+use tracing::{debug, error, info, warn};
+
+use crate::clock::{self, ClockGuard};
+use crate::config::{Config, FieldNames};
+use crate::format::format_duration_text;
+use crate::i18n::{t, t_with, Text};
+use crate::notification::{notify, notify_as, notify_sent, send_failure_message, Urgency};
+use crate::queue::{EventQueue, QueuedEvent};
+use crate::sender::{self, SendError, Sender};
+use crate::store::{status_text, EventAction, EventKind, EventStore};
+use crate::{event_socket, webhook};
+
+// 未送信イベントの再送を試みる間隔 (秒)
+const FLUSH_INTERVAL_SECS: u64 = 60;
+
+// 送信待ちのイベントを送信するスレッドを開始
+pub fn start_flusher(app: &AppHandle, queue: Arc<EventQueue>) {
+    let app_clone = app.clone();
+    thread::spawn(move || {
+        // 失敗を通知済みか (再送のたびに通知しないよう、送信に成功するまでは 1 回だけ)
+        let mut failure_notified = false;
+        // 最後にまとめて送った時刻 (まとめて送る設定のときだけ使う)
+        let mut last_batch = Instant::now();
+        loop {
+            let batch_interval = batch_interval(&app_clone);
+            let batch_due = last_batch.elapsed() >= batch_interval;
+            match flush_queue(&app_clone, &queue, batch_due) {
+                Ok(()) => {
+                    failure_notified = false;
+                    if batch_due {
+                        last_batch = Instant::now();
+                    }
+                }
+                Err(e) => {
+                    if !failure_notified {
+                        notify_as(&app_clone, Urgency::Critical, &send_failure_message(&e));
+                        failure_notified = true;
+                    }
+                }
+            }
+            // まとめて送る設定なら次にまとめて送る時刻にも起きる
+            let mut wait = Duration::from_secs(FLUSH_INTERVAL_SECS);
+            if !batch_interval.is_zero() {
+                wait = wait
+                    .min(batch_interval.saturating_sub(last_batch.elapsed()))
+                    .max(Duration::from_secs(1));
+            }
+            if queue.wait(wait) {
+                // 続けて操作されている間は送らずに待つ (すぐ取り消された操作をまとめて捨てるため)
+                let debounce = send_debounce(&app_clone);
+                while !debounce.is_zero() && queue.wait(debounce) {}
+            }
+        }
+    });
+}
+
+// このマシンの時計がサーバーの時計と大きくずれているか (ずれ始めたとき・直ったときは通知する)
+// 時刻を確かめられなければ前回の結果のままにする
+fn clock_skewed(
+    app: &AppHandle,
+    config: &Config,
+    sender: &Sender,
+    proxy: Option<Proxy>,
+    tls: Option<native_tls::TlsConnector>,
+) -> bool {
+    if config.max_clock_skew_minutes == 0 || config.dry_run {
+        return false;
+    }
+    let guard = app.state::<ClockGuard>();
+    if !guard.needs_check(Utc::now()) {
+        return guard.held().is_some();
+    }
+    let Ok(url) = config.endpoint() else {
+        return guard.held().is_some();
+    };
+
+    let timeout = Duration::from_secs(config.timeout_secs);
+    match sender.fetch_server_time(url.as_str(), timeout, proxy, tls) {
+        Ok((server, round_trip)) => {
+            let now = Utc::now();
+            let skew = clock::skew(now, server, round_trip);
+            let max = Duration::from_secs(config.max_clock_skew_minutes * 60);
+            let held = clock::implausible(skew, max, round_trip).then_some(skew);
+            debug!("時計のずれ: {} 秒", skew.num_seconds());
+            if guard.record(now, held) {
+                match held {
+                    Some(skew) => {
+                        warn!(
+                            "時計が {} 秒ずれているため送信を保留します",
+                            skew.num_seconds()
+                        );
+                        let text = if skew > chrono::Duration::zero() {
+                            Text::ClockAhead
+                        } else {
+                            Text::ClockBehind
+                        };
+                        let duration = skew.abs().to_std().unwrap_or_default();
+                        notify_as(
+                            app,
+                            Urgency::Critical,
+                            &t_with(text, &[&format_duration_text(duration)]),
+                        );
+                    }
+                    None => {
+                        info!("時計が直ったので送信を再開します");
+                        notify(app, t(Text::ClockFixed));
+                    }
+                }
+            }
+        }
+        Err(e) => debug!("サーバーの時刻を取得できませんでした: {}", e),
+    }
+    guard.held().is_some()
+}
+
+// まとめて送る間隔 (まとめて送る設定でなければ送信を待たない)
+fn batch_interval(app: &AppHandle) -> Duration {
+    let config = app.state::<Mutex<Config>>();
+    let config = config.lock().unwrap();
+    if config.batch_endpoint_url.trim().is_empty() {
+        return Duration::ZERO;
+    }
+    Duration::from_secs(config.batch_interval_secs)
+}
+
+// 送信待ちのイベントを古い順に送信 (失敗したらそこで打ち切って次の機会に再送する)
+// まとめて送る設定なら、batch_due か業務終了があるときだけまとめて送る
+pub fn flush_queue(app: &AppHandle, queue: &EventQueue, batch_due: bool) -> Result<(), SendError> {
+    let _flushing = queue.begin_flush();
+    let config = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .with_active_profile();
+
+    match queue.coalesce(Duration::from_millis(config.send_debounce_ms)) {
+        Ok(0) => {}
+        Ok(removed) => info!(
+            "すぐに取り消された操作を送信しませんでした ({} 件)",
+            removed
+        ),
+        Err(e) => warn!("取り消された操作をまとめられませんでした: {}", e),
+    }
+    let pending = queue.pending();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    // 送信の準備ができていなければキューに残したままにする
+    let Some(sender) = app.try_state::<Sender>() else {
+        return Ok(());
+    };
+    let timeout = Duration::from_secs(config.timeout_secs);
+    // 項目名が重複していれば読み込み時に警告しているので、デフォルトの項目名で送る
+    let field_names = match config.field_names.validate() {
+        Ok(()) => config.field_names.clone(),
+        Err(_) => FieldNames::default(),
+    };
+
+    // プロキシの設定がなければ reqwest が環境変数 (HTTP_PROXY / HTTPS_PROXY) に従う
+    let proxy = match config.proxy() {
+        Ok(Some(url)) => match sender::proxy(&config, &url) {
+            Ok(proxy) => Some(proxy),
+            Err(e) => {
+                warn!("送信を保留しました (プロキシを設定できません): {}", e);
+                return Ok(());
+            }
+        },
+        Ok(None) => None,
+        Err(e) => {
+            warn!("送信を保留しました: {}", e);
+            return Ok(());
+        }
+    };
+    // 証明書をピン留めしていれば、送信先にはその証明書でしか接続しない (予備の送信先は除く)
+    let tls = match sender::pinned_tls(&config) {
+        Ok(tls) => tls,
+        Err(e) => {
+            warn!(
+                "送信を保留しました (ピン留めした証明書を読み込めません): {}",
+                e
+            );
+            return Ok(());
+        }
+    };
+
+    // このマシンの時計が大きくずれていれば、時計が直るまで送信を保留する
+    if clock_skewed(app, &config, &sender, proxy.clone(), tls.clone()) {
+        return Ok(());
+    }
+
+    let batch_url = match config.batch_endpoint() {
+        Ok(url) => url,
+        Err(e) => {
+            warn!("送信を保留しました (まとめて送る{})", e);
+            return Ok(());
+        }
+    };
+
+    // まとめて送る設定なら、予備の送信先へのイベント以外はまとめて 1 回で送る
+    let (result, pending) = match &batch_url {
+        Some(batch_url) => send_batched(
+            queue,
+            pending,
+            batch_due,
+            |batch| {
+                sender.send_batch(
+                    batch_url.as_str(),
+                    batch,
+                    &field_names,
+                    timeout,
+                    proxy.clone(),
+                    tls.clone(),
+                    config.signing_secret(),
+                    config.dry_run,
+                )
+            },
+            |event| {
+                if !config.dry_run {
+                    notify_sent(app, &config, event);
+                }
+            },
+        ),
+        None => (Ok(()), pending),
+    };
+
+    let sent = send_each(
+        queue,
+        &config,
+        pending,
+        |url, event, cancelled| {
+            sender.send(
+                url,
+                event,
+                &field_names,
+                timeout,
+                proxy.clone(),
+                tls.clone().filter(|_| !event.secondary),
+                config.signing_secret(),
+                config.dry_run,
+                cancelled,
+            )
+        },
+        |event| {
+            if !config.dry_run && !event.secondary {
+                notify_sent(app, &config, event);
+            }
+        },
+    );
+    result.and(sent)
+}
+
+// 予備の送信先へのイベント以外を send_batch でまとめて送り、送れたらキューから取り除く
+// (on_sent は送れたときにイベントごとに呼ぶ, 1 つずつ送る予備の送信先へのイベントを一緒に返す)
+// batch_due か業務終了があるときだけ送る (終了するときは batch_due で呼ぶ)
+fn send_batched(
+    queue: &EventQueue,
+    pending: Vec<QueuedEvent>,
+    batch_due: bool,
+    send_batch: impl FnOnce(&[QueuedEvent]) -> Result<(), SendError>,
+    mut on_sent: impl FnMut(&QueuedEvent),
+) -> (Result<(), SendError>, Vec<QueuedEvent>) {
+    let (batch, rest): (Vec<_>, Vec<_>) = pending.into_iter().partition(|event| !event.secondary);
+    // 業務終了は間隔を待たずにすぐ送る
+    let clock_out = batch.iter().any(|event| {
+        event.event_type.as_deref() == Some(EventKind::Work.as_str())
+            && event.action.as_deref() == Some(EventAction::End.as_str())
+    });
+    if batch.is_empty() || !(batch_due || clock_out) {
+        return (Ok(()), rest);
+    }
+
+    debug!("まとめて送信します ({} 件)", batch.len());
+    match send_batch(&batch) {
+        Ok(()) => {
+            info!("まとめて送信しました ({} 件)", batch.len());
+            for event in &batch {
+                on_sent(event);
+                // キューを書き換えられなければ、残りも次の機会に送る
+                if let Err(e) = queue.remove(event) {
+                    error!("送信済みイベントを削除できませんでした: {}", e);
+                    return (Ok(()), Vec::new());
+                }
+            }
+            (Ok(()), rest)
+        }
+        Err(e) => {
+            warn!(
+                "まとめて送信するのに失敗しました ({} 件): {} (後で再送します)",
+                batch.len(),
+                e
+            );
+            (Err(e), rest)
+        }
+    }
+}
+
+// イベントを古い順に send で 1 つずつ送り、送れたものをキューから取り除く (on_sent は送れたときに呼ぶ)
+// 送れなかった送信先は、順番が入れ替わらないようその送信先の残りのイベントも次の機会に送る
+// 予備の送信先が失敗しても送信先へのイベントは送り続ける
+fn send_each(
+    queue: &EventQueue,
+    config: &Config,
+    pending: Vec<QueuedEvent>,
+    mut send: impl FnMut(&str, &QueuedEvent, oneshot::Receiver<()>) -> Result<(), SendError>,
+    mut on_sent: impl FnMut(&QueuedEvent),
+) -> Result<(), SendError> {
+    let mut result = Ok(());
+    let mut held: Vec<String> = Vec::new();
+    for event in pending {
+        // 操作したときのプロファイルの送信先に送る (古いキューなら現在の送信先)
+        let endpoint_url = event
+            .endpoint_url
+            .as_deref()
+            .unwrap_or(config.endpoint_url.as_str());
+        if held.iter().any(|held| held == endpoint_url) {
+            continue;
+        }
+
+        // 送信先が不正な場合は送信しない
+        let url = match config.parse_endpoint(endpoint_url) {
+            Ok(url) => url,
+            Err(e) => {
+                warn!("送信を保留しました: {}", e);
+                held.push(endpoint_url.to_string());
+                continue;
+            }
+        };
+
+        debug!("送信します ({} {})", event.status, event.timestamp);
+        let cancelled = queue.begin_send(&event);
+        let sent = send(url.as_str(), &event, cancelled);
+        queue.end_send();
+        match sent {
+            Ok(()) => {
+                info!("送信しました ({})", event.status);
+                on_sent(&event);
+                if let Err(e) = queue.remove(&event) {
+                    error!("送信済みイベントを削除できませんでした: {}", e);
+                    return result;
+                }
+            }
+            // 打ち消すイベントが追加されたら、応答を待たずに次の機会に順番どおり送り直す
+            // (届いていたかわからないのでキューには残す, すぐ取り消した操作ならそのときにまとめられる)
+            Err(SendError::Cancelled) => {
+                info!(
+                    "新しい操作があったため送信を待つのをやめました ({})",
+                    event.status
+                );
+                held.push(endpoint_url.to_string());
+            }
+            // 予備の送信先の失敗はログに残すだけにする
+            Err(e) if event.secondary => {
+                warn!(
+                    "予備の送信先への送信に失敗しました ({}): {} (後で再送します)",
+                    event.status, e
+                );
+                held.push(endpoint_url.to_string());
+            }
+            Err(e) => {
+                warn!(
+                    "送信に失敗しました ({}): {} (後で再送します)",
+                    event.status, e
+                );
+                held.push(endpoint_url.to_string());
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+    }
+    result
+}
+
+// 操作してから送信するまで待つ時間
+fn send_debounce(app: &AppHandle) -> Duration {
+    Duration::from_millis(
+        app.state::<Mutex<Config>>()
+            .lock()
+            .unwrap()
+            .send_debounce_ms,
+    )
+}
+
+// サーバーに送る status (設定の labels で変えられるものはそれを使う)
+pub fn status_label(config: &Config, kind: EventKind, action: EventAction) -> &str {
+    config
+        .labels
+        .status(kind, action)
+        .unwrap_or(status_text(kind, action))
+}
+
+// 指定した時刻でイベントをイベントログに記録
+pub fn record_event(
+    app: &AppHandle,
+    kind: EventKind,
+    action: EventAction,
+    at: DateTime<Local>,
+    note: Option<&str>,
+) {
+    let store = app.state::<EventStore>();
+    if let Err(e) = store.record(kind, action, &at, note) {
+        warn!(
+            "イベントを記録できませんでした ({} {}): {}",
+            kind.as_str(),
+            action.as_str(),
+            e
+        );
+    }
+    publish_event(app, kind, action, at, note);
+}
+
+// イベントのソケットに接続しているクライアントにイベントを送る (ソケットを使わない設定なら何もしない)
+pub fn publish_event(
+    app: &AppHandle,
+    kind: EventKind,
+    action: EventAction,
+    at: DateTime<Local>,
+    note: Option<&str>,
+) {
+    let Some(publisher) = app.try_state::<event_socket::Publisher>() else {
+        return;
+    };
+    publisher.publish(&event_socket::Event {
+        kind: kind.as_str(),
+        action: action.as_str(),
+        timestamp: at,
+        note,
+    });
+}
+
+// ステータスを送信待ちキューに追加して送信スレッドを起こす
+// (note は業務開始のメモで、空なら送らない)
+pub fn send_status(
+    app: &AppHandle,
+    kind: EventKind,
+    action: EventAction,
+    at: DateTime<Local>,
+    note: Option<&str>,
+) {
+    // 一時停止などはローカルにだけ記録する
+    if kind.is_local() {
+        return;
+    }
+    let config = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .with_active_profile();
+    let status = status_label(&config, kind, action);
+    // 理由を選んだ休憩は "休憩 開始 (昼食)" のように理由ごとに別の status にする
+    let status = match note.filter(|_| kind == EventKind::Break) {
+        Some(reason) => format!("{} ({})", status, reason),
+        None => status.to_string(),
+    };
+    let status = status.as_str();
+
+    // 名前が空のまま送信しない
+    if let Err(e) = config.validate() {
+        warn!("送信をスキップしました ({}): {}", status, e);
+        return;
+    }
+
+    let event = QueuedEvent::at(
+        &config.name,
+        status,
+        kind,
+        action,
+        &config.endpoint_url,
+        at.into(),
+        config.timestamp_utc,
+    )
+    .with_note(note);
+    if let Err(e) = enqueue(app, &config, &event) {
+        error!("イベントを保存できませんでした ({}): {}", status, e);
+        return;
+    }
+
+    // チャンネルには業務の開始・終了だけを知らせる
+    if kind == EventKind::Work {
+        webhook::post_all(app, &config, webhook::message(&config.name, status, &at));
+    }
+}
+
+// 業務中であることを送信待ちキューに追加して送信スレッドを起こす
+pub fn send_heartbeat(app: &AppHandle, elapsed: Duration) {
+    let config = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .with_active_profile();
+    if config.validate().is_err() {
+        return;
+    }
+
+    let event = QueuedEvent::heartbeat(
+        &config.name,
+        &config.endpoint_url,
+        elapsed,
+        Utc::now(),
+        config.timestamp_utc,
+    );
+    if let Err(e) = enqueue(app, &config, &event) {
+        error!("業務中であることを保存できませんでした: {}", e);
+    }
+}
+
+// イベントを送信待ちキューに追加して送信スレッドを起こす (予備の送信先があればその分の控えも追加する)
+// 送信先ごとに別々のイベントとして再送するので、予備の送信先が失敗しても送信先には影響しない
+fn enqueue(app: &AppHandle, config: &Config, event: &QueuedEvent) -> io::Result<()> {
+    let event = if config.send_device {
+        event.clone().with_device()
+    } else {
+        event.clone()
+    };
+    let queue = app.state::<Arc<EventQueue>>();
+    for event in event.with_secondary(&config.secondary_endpoint_url) {
+        queue.push(&event)?;
+    }
+    queue.notify();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    use reqwest::StatusCode;
+
+    const PRIMARY: &str = "https://example.com/exec";
+    const SECONDARY: &str = "https://backup.example.com/exec";
+
+    // テストごとに別のキュー
+    fn temp_queue(name: &str) -> EventQueue {
+        let path = env::temp_dir()
+            .join(format!("kintai-flusher-{}-{}", name, std::process::id()))
+            .join("queue.jsonl");
+        let _ = fs::remove_file(&path);
+        EventQueue::new(path)
+    }
+
+    // 今から minute 分後に url へ送るイベント
+    fn event(url: &str, kind: EventKind, action: EventAction, minute: i64) -> QueuedEvent {
+        QueuedEvent::at(
+            "佐藤",
+            "業務",
+            kind,
+            action,
+            url,
+            Utc::now() + chrono::Duration::minutes(minute),
+            true,
+        )
+    }
+
+    // events をキューに入れてから送り、結果と送ろうとしたイベント、送れて通知した数を返す
+    fn flush(
+        queue: &EventQueue,
+        events: &[QueuedEvent],
+        mut send: impl FnMut(&QueuedEvent) -> Result<(), SendError>,
+    ) -> (Result<(), SendError>, Vec<QueuedEvent>, usize) {
+        for event in events {
+            queue.push(event).unwrap();
+        }
+        let (mut tried, mut notified) = (Vec::new(), 0);
+        let result = send_each(
+            queue,
+            &Config::default(),
+            queue.pending(),
+            |_, event, _| {
+                tried.push(event.clone());
+                send(event)
+            },
+            |_| notified += 1,
+        );
+        (result, tried, notified)
+    }
+
+    #[test]
+    fn sent_events_leave_the_queue() {
+        let queue = temp_queue("sent");
+        let events = [
+            event(PRIMARY, EventKind::Work, EventAction::Start, 0),
+            event(PRIMARY, EventKind::Break, EventAction::Start, 1),
+        ];
+        let (result, tried, notified) = flush(&queue, &events, |_| Ok(()));
+        assert!(result.is_ok());
+        assert_eq!(tried, events);
+        assert_eq!(notified, 2);
+        assert!(queue.pending().is_empty());
+    }
+
+    #[test]
+    fn failed_event_and_the_rest_stay_queued_in_order() {
+        let queue = temp_queue("failed");
+        let events = [
+            event(PRIMARY, EventKind::Work, EventAction::Start, 0),
+            event(PRIMARY, EventKind::Break, EventAction::Start, 1),
+            event(PRIMARY, EventKind::Break, EventAction::End, 2),
+        ];
+        let (result, tried, notified) = flush(&queue, &events, |event| {
+            if *event == events[1] {
+                Err(SendError::Status(StatusCode::SERVICE_UNAVAILABLE))
+            } else {
+                Ok(())
+            }
+        });
+        assert!(matches!(result, Err(SendError::Status(_))));
+        // 失敗した後の休憩の終了は送らずに、次の機会に順番どおり送る
+        assert_eq!(tried, events[..2]);
+        assert_eq!(notified, 1);
+        assert_eq!(queue.pending(), events[1..]);
+
+        // 次の機会に送れればキューは空になる
+        let (result, tried, _) = flush(&queue, &[], |_| Ok(()));
+        assert!(result.is_ok());
+        assert_eq!(tried, events[1..]);
+        assert!(queue.pending().is_empty());
+    }
+
+    #[test]
+    fn secondary_failure_does_not_hold_the_primary() {
+        let queue = temp_queue("secondary");
+        let start = event(PRIMARY, EventKind::Work, EventAction::Start, 0);
+        let end = event(PRIMARY, EventKind::Work, EventAction::End, 1);
+        let events = [
+            start.to_secondary(SECONDARY),
+            start.clone(),
+            end.to_secondary(SECONDARY),
+            end.clone(),
+        ];
+        let (result, tried, _) = flush(&queue, &events, |event| {
+            if event.secondary {
+                Err(SendError::Timeout)
+            } else {
+                Ok(())
+            }
+        });
+        // 予備の送信先の失敗は送信の失敗にしない
+        assert!(result.is_ok());
+        assert_eq!(tried, [events[0].clone(), start, end]);
+        assert_eq!(queue.pending(), [events[0].clone(), events[2].clone()]);
+    }
+
+    #[test]
+    fn cancelled_send_stays_queued_without_failing() {
+        let queue = temp_queue("cancelled");
+        let events = [
+            event(PRIMARY, EventKind::Work, EventAction::Start, 0),
+            event(PRIMARY, EventKind::Work, EventAction::End, 1),
+        ];
+        let (result, tried, notified) = flush(&queue, &events, |_| Err(SendError::Cancelled));
+        assert!(result.is_ok());
+        assert_eq!(tried, events[..1]);
+        assert_eq!(notified, 0);
+        assert_eq!(queue.pending(), events);
+    }
+
+    #[test]
+    fn invalid_endpoint_is_held_without_sending() {
+        let queue = temp_queue("invalid");
+        let events = [
+            event(
+                "http://insecure.example.com/exec",
+                EventKind::Work,
+                EventAction::Start,
+                0,
+            ),
+            event(PRIMARY, EventKind::Work, EventAction::Start, 1),
+        ];
+        let (result, tried, _) = flush(&queue, &events, |_| Ok(()));
+        assert!(result.is_ok());
+        assert_eq!(tried, events[1..]);
+        assert_eq!(queue.pending(), events[..1]);
+    }
+
+    // events をキューに入れてからまとめて送り、結果と 1 つずつ送るイベントと、まとめて送った内容を返す
+    fn flush_batch(
+        queue: &EventQueue,
+        events: &[QueuedEvent],
+        batch_due: bool,
+        result: Result<(), SendError>,
+    ) -> (
+        Result<(), SendError>,
+        Vec<QueuedEvent>,
+        Option<Vec<QueuedEvent>>,
+    ) {
+        for event in events {
+            queue.push(event).unwrap();
+        }
+        let mut sent_batch = None;
+        let (result, rest) = send_batched(
+            queue,
+            queue.pending(),
+            batch_due,
+            |batch| {
+                sent_batch = Some(batch.to_vec());
+                result
+            },
+            |_| {},
+        );
+        (result, rest, sent_batch)
+    }
+
+    #[test]
+    fn batch_waits_for_the_interval() {
+        let queue = temp_queue("batch-wait");
+        let events = [
+            event(PRIMARY, EventKind::Work, EventAction::Start, 0),
+            event(PRIMARY, EventKind::Break, EventAction::Start, 1),
+        ];
+        let (result, rest, sent) = flush_batch(&queue, &events, false, Ok(()));
+        assert!(result.is_ok());
+        assert!(rest.is_empty());
+        assert_eq!(sent, None);
+        assert_eq!(queue.pending(), events);
+    }
+
+    #[test]
+    fn batch_is_sent_in_order_when_due() {
+        let queue = temp_queue("batch-due");
+        let start = event(PRIMARY, EventKind::Work, EventAction::Start, 0);
+        let events = [
+            start.clone(),
+            start.to_secondary(SECONDARY),
+            event(PRIMARY, EventKind::Break, EventAction::Start, 1),
+            event(PRIMARY, EventKind::Break, EventAction::End, 2),
+        ];
+        let mut notified = Vec::new();
+        for event in &events {
+            queue.push(event).unwrap();
+        }
+        let (result, rest) = send_batched(
+            &queue,
+            queue.pending(),
+            true,
+            |batch| {
+                // キューに入れた順に 1 回で送る (予備の送信先へのイベントは含めない)
+                assert_eq!(
+                    batch,
+                    [events[0].clone(), events[2].clone(), events[3].clone()]
+                );
+                Ok(())
+            },
+            |event| notified.push(event.clone()),
+        );
+        assert!(result.is_ok());
+        assert_eq!(notified.len(), 3);
+        // 予備の送信先へのイベントだけが 1 つずつ送るために残る
+        assert_eq!(rest, events[1..2]);
+        assert_eq!(queue.pending(), events[1..2]);
+    }
+
+    #[test]
+    fn clock_out_is_sent_without_waiting() {
+        let queue = temp_queue("batch-clock-out");
+        let events = [
+            event(PRIMARY, EventKind::Work, EventAction::Start, 0),
+            event(PRIMARY, EventKind::Work, EventAction::End, 1),
+        ];
+        let (result, _, sent) = flush_batch(&queue, &events, false, Ok(()));
+        assert!(result.is_ok());
+        assert_eq!(sent.as_deref(), Some(&events[..]));
+        assert!(queue.pending().is_empty());
+    }
+
+    #[test]
+    fn exit_flushes_the_batch_immediately() {
+        // 終了するときは間隔を待たずに送る (batch_due で呼ぶ)
+        let queue = temp_queue("batch-exit");
+        let events = [event(PRIMARY, EventKind::Meeting, EventAction::Start, 0)];
+        let (result, _, sent) = flush_batch(&queue, &events, true, Ok(()));
+        assert!(result.is_ok());
+        assert_eq!(sent.as_deref(), Some(&events[..]));
+        assert!(queue.pending().is_empty());
+    }
+
+    #[test]
+    fn failed_batch_stays_queued() {
+        let queue = temp_queue("batch-failed");
+        let events = [
+            event(PRIMARY, EventKind::Work, EventAction::Start, 0),
+            event(PRIMARY, EventKind::Work, EventAction::End, 1),
+        ];
+        let failed = Err(SendError::Status(StatusCode::BAD_GATEWAY));
+        let (result, _, sent) = flush_batch(&queue, &events, true, failed);
+        assert!(matches!(result, Err(SendError::Status(_))));
+        assert_eq!(sent.as_deref(), Some(&events[..]));
+        assert_eq!(queue.pending(), events);
+
+        // 次の機会にまとめて送り直す
+        let (result, _, sent) = flush_batch(&queue, &[], true, Ok(()));
+        assert!(result.is_ok());
+        assert_eq!(sent.as_deref(), Some(&events[..]));
+        assert!(queue.pending().is_empty());
+    }
+}
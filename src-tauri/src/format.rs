@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+
+use crate::i18n::{t_with, Text};
+
+// 時刻を表示用に整形 (use_24h が false なら "9:12 AM")
+pub fn format_clock(time: &DateTime<Local>, use_24h: bool) -> String {
+    if use_24h {
+        time.format("%H:%M").to_string()
+    } else {
+        time.format("%-I:%M %p").to_string()
+    }
+}
+
+// 業務開始までの残り時間を "02:00" の形式にする (秒は切り上げる)
+pub fn format_countdown(duration: Duration) -> String {
+    let secs = duration.as_millis().div_ceil(1000);
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+// 時間を分に切り上げる (残り 30 秒でも 0 分と表示しないように)
+pub fn ceil_minutes(duration: Duration) -> String {
+    duration.as_secs().div_ceil(60).to_string()
+}
+
+// 経過時間を hh:mm:ss のフォーマットに整形
+pub fn format_duration(duration: Duration) -> String {
+    format_duration_with_days(duration, false)
+}
+
+// 経過時間を hh:mm:ss のフォーマットに整形 (separate_days が true なら 24 時間以上を "1d 01:00:00" のように日数に分ける)
+pub fn format_duration_with_days(duration: Duration, separate_days: bool) -> String {
+    let secs = duration.as_secs();
+    let (days, secs) = if separate_days {
+        (secs / 86400, secs % 86400)
+    } else {
+        (0, secs)
+    };
+    let hours = secs / 3600;
+    let minutes = (secs % 3600) / 60;
+    let seconds = secs % 60;
+    if days > 0 {
+        format!("{}d {:02}:{:02}:{:02}", days, hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    }
+}
+
+// 経過時間を hh:mm のフォーマットに整形 (separate_days は format_duration_with_days と同じ)
+pub fn format_duration_minutes(duration: Duration, separate_days: bool) -> String {
+    let formatted = format_duration_with_days(duration, separate_days);
+    formatted[..formatted.len() - 3].to_string()
+}
+
+// 表示形式の {h} (時間), {hh} (2 桁の時間), {m} (分), {s} (秒) を置き換える
+// (知らないプレースホルダーや括弧の対応が取れていない場合は None)
+pub fn format_title(template: &str, duration: Duration) -> Option<String> {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+
+    let mut result = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        c => name.push(c),
+                    }
+                }
+                let value = match name.as_str() {
+                    "h" => hours.to_string(),
+                    "hh" => format!("{:02}", hours),
+                    "m" => format!("{:02}", minutes),
+                    "s" => format!("{:02}", seconds),
+                    _ => return None,
+                };
+                result.push_str(&value);
+            }
+            '}' => return None,
+            c => result.push(c),
+        }
+    }
+    Some(result)
+}
+
+// 時間を H:MM の形式に変換
+pub fn format_hours_minutes(duration: Duration) -> String {
+    let hours = duration.as_secs() / 3600;
+    let minutes = (duration.as_secs() % 3600) / 60;
+    format!("{}:{:02}", hours, minutes)
+}
+
+// 時間を "7時間45分" の形式にする (0 の時間・分は省き、1 分未満は "0分")
+pub fn format_duration_text(duration: Duration) -> String {
+    let hours = duration.as_secs() / 3600;
+    let minutes = (duration.as_secs() % 3600) / 60;
+    match (hours, minutes) {
+        (0, minutes) => t_with(Text::Minutes, &[&minutes.to_string()]),
+        (hours, 0) => t_with(Text::Hours, &[&hours.to_string()]),
+        (hours, minutes) => t_with(
+            Text::HoursMinutes,
+            &[&hours.to_string(), &minutes.to_string()],
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn format_duration_boundaries() {
+        assert_eq!(format_duration(Duration::ZERO), "00:00:00");
+        assert_eq!(format_duration(secs(59)), "00:00:59");
+        assert_eq!(format_duration(secs(60)), "00:01:00");
+        assert_eq!(format_duration(secs(3599)), "00:59:59");
+        assert_eq!(format_duration(secs(3600)), "01:00:00");
+        assert_eq!(format_duration(secs(3600 + 60 + 1)), "01:01:01");
+    }
+
+    #[test]
+    fn format_duration_over_a_day() {
+        assert_eq!(format_duration(secs(90000)), "25:00:00");
+        assert_eq!(format_duration(secs(100 * 3600)), "100:00:00");
+        assert_eq!(format_duration_with_days(secs(90000), true), "1d 01:00:00");
+        assert_eq!(format_duration_with_days(secs(86399), true), "23:59:59");
+        assert_eq!(
+            format_duration_with_days(secs(2 * 86400 + 5), true),
+            "2d 00:00:05"
+        );
+    }
+
+    #[test]
+    fn format_duration_minutes_drops_seconds() {
+        assert_eq!(format_duration_minutes(secs(3599), false), "00:59");
+        assert_eq!(format_duration_minutes(secs(90000), true), "1d 01:00");
+    }
+
+    #[test]
+    fn format_clock_in_12_and_24_hours() {
+        let at = |h, m| Local.with_ymd_and_hms(2024, 4, 1, h, m, 0).unwrap();
+        assert_eq!(format_clock(&at(9, 5), true), "09:05");
+        assert_eq!(format_clock(&at(9, 5), false), "9:05 AM");
+        assert_eq!(format_clock(&at(21, 30), true), "21:30");
+        assert_eq!(format_clock(&at(21, 30), false), "9:30 PM");
+        assert_eq!(format_clock(&at(0, 0), true), "00:00");
+        assert_eq!(format_clock(&at(0, 0), false), "12:00 AM");
+        assert_eq!(format_clock(&at(12, 0), false), "12:00 PM");
+    }
+
+    #[test]
+    fn format_title_placeholders() {
+        let duration = Duration::from_secs(3600 + 2 * 60 + 3);
+        assert_eq!(
+            format_title("{h}:{m}:{s}", duration).as_deref(),
+            Some("1:02:03")
+        );
+        assert_eq!(
+            format_title("{hh}h{m}m", duration).as_deref(),
+            Some("01h02m")
+        );
+        assert_eq!(format_title("作業中", duration).as_deref(), Some("作業中"));
+        assert_eq!(
+            format_title("{h}h", Duration::from_secs(100 * 3600)).as_deref(),
+            Some("100h")
+        );
+    }
+
+    #[test]
+    fn format_title_rejects_invalid_templates() {
+        let duration = Duration::from_secs(60);
+        assert_eq!(format_title("{d}", duration), None);
+        assert_eq!(format_title("{}", duration), None);
+        assert_eq!(format_title("{h", duration), None);
+        assert_eq!(format_title("h}", duration), None);
+    }
+}
@@ -0,0 +1,556 @@
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+// 表示する言語
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    Ja,
+    En,
+}
+
+// 起動時に決めた表示言語
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+// 表示言語を決める (None なら OS の言語から決める, 起動時に 1 回だけ呼ぶ)
+pub fn init(lang: Option<Lang>) {
+    let lang = lang.unwrap_or_else(|| match sys_locale::get_locale() {
+        Some(locale) if !locale.starts_with("ja") => Lang::En,
+        _ => Lang::Ja,
+    });
+    let _ = LANG.set(lang);
+}
+
+// 現在の表示言語
+pub fn lang() -> Lang {
+    LANG.get().copied().unwrap_or_default()
+}
+
+// 画面に表示する文言
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    ClockIn,
+    ClockOut,
+    Break,
+    EndBreak,
+    Meeting,
+    EndMeeting,
+    Hold,
+    EndHold,
+    Profiles,
+    Totals,
+    ExportCsv,
+    ExportMonth,
+    CopyWeeklyReport,
+    WeeklyReportCopied,
+    WeeklyReportFailed,
+    ImportCsv,
+    ImportWhileWorking,
+    ImportDone,
+    ExportJson,
+    ImportJson,
+    ImportJsonConfirm,
+    Settings,
+    OpenDataDir,
+    OpenDataDirFailed,
+    Quit,
+    ClockOutAndQuit,
+    SessionNote,
+    AutoClockedOut,
+    OnBreak,
+    InMeeting,
+    OnHold,
+    GraceCountdown,
+    QuitConfirm,
+    ProfileLocked,
+    ShortcutUnavailable,
+    BreakTooLong,
+    BreakBudgetLeft,
+    BreakBudgetOver,
+    BreakBudgetExceeded,
+    IdleBreakStarted,
+    GoalReached,
+    FocusBlockEnded,
+    BreakReminder,
+    FocusBreakPrompt,
+    OvertimeStarted,
+    SleepExcluded,
+    SessionSummary,
+    TodayTotal,
+    HoursMinutes,
+    Hours,
+    Minutes,
+    SendFailed,
+    SendRejected,
+    ClockAhead,
+    ClockBehind,
+    ClockFixed,
+    RetryOnNextLaunch,
+    StartedAt,
+    Adjust,
+    Undo,
+    AdjustMinutes,
+    AdjustNotWorking,
+    AdjustTooLarge,
+    LaunchAtLogin,
+    LongStretch,
+    MandatoryBreak,
+    TodayEvents,
+    PastMidnight,
+    SplitAtMidnight,
+    SenderUnavailable,
+    AlreadyRunning,
+    ConfigCorrupted,
+    StateCorrupted,
+    ServerSessionOpen,
+    LocalSessionNotOnServer,
+    AutoClockInPrompt,
+    SendSucceeded,
+}
+
+impl Text {
+    // 指定した言語の文言 ("{}" は t_with で埋める)
+    pub fn get(self, lang: Lang) -> &'static str {
+        match lang {
+            Lang::Ja => match self {
+                Text::ClockIn => "業務開始",
+                Text::ClockOut => "業務終了",
+                Text::Break => "休憩",
+                Text::EndBreak => "休憩解除",
+                Text::Meeting => "会議",
+                Text::EndMeeting => "会議終了",
+                Text::Hold => "一時停止",
+                Text::EndHold => "再開",
+                Text::Profiles => "プロファイル",
+                Text::Totals => "集計",
+                Text::ExportCsv => "CSV を書き出す",
+                Text::ExportMonth => "今月の勤務表を書き出す",
+                Text::CopyWeeklyReport => "今週の週報をコピー",
+                Text::WeeklyReportCopied => "今週の週報をクリップボードにコピーしました",
+                Text::WeeklyReportFailed => "週報をコピーできませんでした: {}",
+                Text::ImportCsv => "CSV から記録を取り込む",
+                Text::ImportWhileWorking => "業務中は記録を取り込めません。業務を終了してから取り込んでください",
+                Text::ImportDone => {
+                    "{} 件の記録を取り込みました\n(取り込み済みの記録 {} 件と、読み取れない行 {} 件は飛ばしました)"
+                }
+                Text::ExportJson => "JSON でバックアップ",
+                Text::ImportJson => "JSON のバックアップから復元",
+                Text::ImportJsonConfirm => {
+                    "今の記録をすべて、バックアップの {} 件のイベントで置き換えますか？"
+                }
+                Text::Settings => "設定",
+                Text::OpenDataDir => "ログを開く",
+                Text::OpenDataDirFailed => "データフォルダを開けませんでした: {}",
+                Text::Quit => "終了",
+                Text::ClockOutAndQuit => "業務を終了してアプリを終了",
+                Text::SessionNote => "業務のメモ",
+                Text::AutoClockedOut => {
+                    "長い時間操作がなかったので、最後に操作した {} で業務を終了しました"
+                }
+                Text::OnBreak => "休憩中",
+                Text::InMeeting => "会議中",
+                Text::OnHold => "一時停止",
+                Text::GraceCountdown => "開始まで {}",
+                Text::QuitConfirm => "業務中です。業務を終了してアプリを終了しますか?",
+                Text::ProfileLocked => {
+                    "業務中はプロファイルを切り替えられません。業務を終了してから切り替えてください"
+                }
+                Text::ShortcutUnavailable => {
+                    "ショートカット {} は使用できません。設定ファイルで別のキーを指定してください"
+                }
+                Text::BreakTooLong => "休憩が長すぎます",
+                Text::BreakBudgetLeft => "休憩残り {}分",
+                Text::BreakBudgetOver => "休憩 {}分超過",
+                Text::BreakBudgetExceeded => "今日の休憩が上限の {} を超えました",
+                Text::IdleBreakStarted => "{} 操作がなかったので休憩を開始しました",
+                Text::GoalReached => "今日の目標勤務時間に達しました",
+                Text::FocusBlockEnded => "集中時間が終わりました。{} 分休憩しましょう",
+                Text::BreakReminder => "{}の時間です",
+                Text::FocusBreakPrompt => "休憩を始めますか?",
+                Text::OvertimeStarted => "今日の所定労働時間 {} を超えました。ここからは残業です",
+                Text::SleepExcluded => "スリープしていた {} は勤務時間に含めていません",
+                Text::SessionSummary => "お疲れさまでした\n業務 {} - 休憩 {} - 会議 {} - 一時停止 {} = 実働 {}",
+                Text::TodayTotal => "本日の勤務: {} (休憩 {})",
+                Text::HoursMinutes => "{}時間{}分",
+                Text::Hours => "{}時間",
+                Text::Minutes => "{}分",
+                Text::SendFailed => "サーバーへの送信に失敗しました: {}",
+                Text::SendRejected => "サーバーに記録を受け付けてもらえませんでした: {}",
+                Text::ClockAhead => {
+                    "このマシンの時計が {} 進んでいます。時計を直すまで送信を保留します"
+                }
+                Text::ClockBehind => {
+                    "このマシンの時計が {} 遅れています。時計を直すまで送信を保留します"
+                }
+                Text::ClockFixed => "時計が直ったので送信を再開します",
+                Text::RetryOnNextLaunch => "{} (次回の起動時に再送します)",
+                Text::StartedAt => "開始: {}",
+                Text::Adjust => "経過時間を調整",
+                Text::Undo => "元に戻す",
+                Text::AdjustMinutes => "{} 分",
+                Text::AdjustNotWorking => "経過時間は業務中だけ調整できます",
+                Text::AdjustTooLarge => "経過時間がマイナスになるため調整できません",
+                Text::LaunchAtLogin => "ログイン時に起動",
+                Text::LongStretch => "{} 休憩せずに働いています。少し休憩しませんか?",
+                Text::MandatoryBreak => {
+                    "休憩せずに {} 働きました。休憩が必要な時間です。休憩を取ってください"
+                }
+                Text::TodayEvents => "今日の記録",
+                Text::PastMidnight => "日付が変わりましたが業務中です。業務終了を忘れていませんか?",
+                Text::SplitAtMidnight => "日付が変わったので 0:00 で業務を区切りました",
+                Text::SenderUnavailable => {
+                    "サーバーに送信できない状態です。記録は保存しておき、次回の起動時に送信します"
+                }
+                Text::AlreadyRunning => "既に起動しています。メニューバーのアイコンから操作してください",
+                Text::ConfigCorrupted => {
+                    "設定ファイルが壊れていたので、デフォルトの設定で起動しました。元のファイルは {} に残しています"
+                }
+                Text::StateCorrupted => {
+                    "前回の勤務状態を読み込めなかったので、業務中でない状態で起動しました。元のファイルは {} に残しています"
+                }
+                Text::ServerSessionOpen => {
+                    "サーバーでは {} から業務中になっています。業務を再開しますか?\n(「いいえ」なら今の時刻で業務終了を送信します)"
+                }
+                Text::SendSucceeded => "「{}」を記録しました",
+                Text::LocalSessionNotOnServer => {
+                    "業務中ですが、サーバーには業務開始が記録されていません。業務開始 ({}) を送信し直しますか?"
+                }
+                Text::AutoClockInPrompt => "{} になりました。業務を開始しますか?",
+            },
+            Lang::En => match self {
+                Text::ClockIn => "Clock in",
+                Text::ClockOut => "Clock out",
+                Text::Break => "Break",
+                Text::EndBreak => "End break",
+                Text::Meeting => "Meeting",
+                Text::EndMeeting => "End meeting",
+                Text::Hold => "Pause",
+                Text::EndHold => "Resume",
+                Text::Profiles => "Profiles",
+                Text::Totals => "Totals",
+                Text::ExportCsv => "Export CSV",
+                Text::ExportMonth => "Export this month's timesheet",
+                Text::CopyWeeklyReport => "Copy this week's report",
+                Text::WeeklyReportCopied => "Copied this week's report to the clipboard",
+                Text::WeeklyReportFailed => "Couldn't copy the weekly report: {}",
+                Text::ImportCsv => "Import events from CSV",
+                Text::ImportWhileWorking => "You can't import events while clocked in. Clock out first.",
+                Text::ImportDone => {
+                    "Imported {} events\n(skipped {} already recorded and {} unreadable rows)"
+                }
+                Text::ExportJson => "Back up as JSON",
+                Text::ImportJson => "Restore from JSON backup",
+                Text::ImportJsonConfirm => {
+                    "Replace all recorded events with the {} events in the backup?"
+                }
+                Text::Settings => "Settings",
+                Text::OpenDataDir => "Open logs",
+                Text::OpenDataDirFailed => "Couldn't open the data folder: {}",
+                Text::Quit => "Quit",
+                Text::ClockOutAndQuit => "Clock out and quit",
+                Text::SessionNote => "Session note",
+                Text::AutoClockedOut => {
+                    "You were inactive for a long time, so you were clocked out at {}, your last activity"
+                }
+                Text::OnBreak => "On break",
+                Text::InMeeting => "In meeting",
+                Text::OnHold => "Paused",
+                Text::GraceCountdown => "Starting in {}",
+                Text::QuitConfirm => "You are still clocked in. Clock out and quit?",
+                Text::ProfileLocked => {
+                    "You can't switch profiles while clocked in. Clock out first."
+                }
+                Text::ShortcutUnavailable => {
+                    "The shortcut {} is not available. Choose another key in the config file."
+                }
+                Text::BreakTooLong => "Your break is running long",
+                Text::BreakBudgetLeft => "{} min of break left",
+                Text::BreakBudgetOver => "{} min over break limit",
+                Text::BreakBudgetExceeded => "Today's breaks exceeded the {} limit",
+                Text::IdleBreakStarted => "No input for {}, so a break was started",
+                Text::GoalReached => "You reached today's work-hour goal",
+                Text::FocusBlockEnded => "Focus block finished. Take a {}-minute break.",
+                Text::BreakReminder => "Time for {}.",
+                Text::FocusBreakPrompt => "Start a break now?",
+                Text::OvertimeStarted => {
+                    "You passed today's scheduled {} of work, so you are now working overtime"
+                }
+                Text::SleepExcluded => "{} of sleep was not counted as work time",
+                Text::SessionSummary => {
+                    "Good work today\nWork {} - Break {} - Meeting {} - Hold {} = Worked {}"
+                }
+                Text::TodayTotal => "Today: {} (break {})",
+                Text::HoursMinutes => "{}h {}m",
+                Text::Hours => "{}h",
+                Text::Minutes => "{}m",
+                Text::SendFailed => "Failed to send to the server: {}",
+                Text::SendRejected => "The server did not accept the record: {}",
+                Text::ClockAhead => {
+                    "This Mac's clock is {} fast. Sending is on hold until the clock is fixed"
+                }
+                Text::ClockBehind => {
+                    "This Mac's clock is {} slow. Sending is on hold until the clock is fixed"
+                }
+                Text::ClockFixed => "The clock is fixed. Sending has resumed",
+                Text::RetryOnNextLaunch => "{} (will retry on next launch)",
+                Text::StartedAt => "Started: {}",
+                Text::Adjust => "Adjust elapsed time",
+                Text::Undo => "Undo",
+                Text::AdjustMinutes => "{} min",
+                Text::AdjustNotWorking => "You can only adjust the elapsed time while clocked in",
+                Text::AdjustTooLarge => "The elapsed time can't go below zero",
+                Text::LaunchAtLogin => "Launch at login",
+                Text::LongStretch => "You have worked {} without a break. Time for a rest?",
+                Text::MandatoryBreak => {
+                    "You have worked {} without a break. A break is now required; please take one"
+                }
+                Text::TodayEvents => "Today's events",
+                Text::PastMidnight => {
+                    "It's past midnight and you are still clocked in. Did you forget to clock out?"
+                }
+                Text::SplitAtMidnight => "The session was split at midnight",
+                Text::SenderUnavailable => {
+                    "Sending is unavailable. Records are kept and will be sent on next launch."
+                }
+                Text::AlreadyRunning => "Already running. Use the icon in the menu bar.",
+                Text::ConfigCorrupted => {
+                    "The config file was corrupt, so the default settings are in use. The original was kept at {}"
+                }
+                Text::StateCorrupted => {
+                    "The saved work state could not be read, so you are not clocked in. The original was kept at {}"
+                }
+                Text::ServerSessionOpen => {
+                    "The server shows you clocked in since {}. Resume that session?\n(\"No\" sends a clock-out for now.)"
+                }
+                Text::SendSucceeded => "Recorded \"{}\"",
+                Text::LocalSessionNotOnServer => {
+                    "You are clocked in, but the server has no clock-in. Send the clock-in ({}) again?"
+                }
+                Text::AutoClockInPrompt => "It's {}. Clock in now?",
+            },
+        }
+    }
+}
+
+// 現在の表示言語の文言
+pub fn t(text: Text) -> &'static str {
+    text.get(lang())
+}
+
+// 現在の表示言語の文言の "{}" を順に args で埋める
+pub fn t_with(text: Text, args: &[&str]) -> String {
+    let mut parts = t(text).split("{}");
+    let mut result = parts.next().unwrap_or_default().to_string();
+    for (part, arg) in parts.zip(args.iter().chain(std::iter::repeat(&""))) {
+        result.push_str(arg);
+        result.push_str(part);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // すべての文言
+    const ALL: [Text; 77] = [
+        Text::ClockIn,
+        Text::ClockOut,
+        Text::Break,
+        Text::EndBreak,
+        Text::Meeting,
+        Text::EndMeeting,
+        Text::Hold,
+        Text::EndHold,
+        Text::Profiles,
+        Text::Totals,
+        Text::ExportCsv,
+        Text::ExportMonth,
+        Text::CopyWeeklyReport,
+        Text::WeeklyReportCopied,
+        Text::WeeklyReportFailed,
+        Text::ImportCsv,
+        Text::ImportWhileWorking,
+        Text::ImportDone,
+        Text::ExportJson,
+        Text::ImportJson,
+        Text::ImportJsonConfirm,
+        Text::Settings,
+        Text::OpenDataDir,
+        Text::OpenDataDirFailed,
+        Text::Quit,
+        Text::ClockOutAndQuit,
+        Text::SessionNote,
+        Text::AutoClockedOut,
+        Text::OnBreak,
+        Text::InMeeting,
+        Text::OnHold,
+        Text::GraceCountdown,
+        Text::QuitConfirm,
+        Text::ProfileLocked,
+        Text::ShortcutUnavailable,
+        Text::BreakTooLong,
+        Text::BreakBudgetLeft,
+        Text::BreakBudgetOver,
+        Text::BreakBudgetExceeded,
+        Text::IdleBreakStarted,
+        Text::GoalReached,
+        Text::FocusBlockEnded,
+        Text::BreakReminder,
+        Text::FocusBreakPrompt,
+        Text::OvertimeStarted,
+        Text::SleepExcluded,
+        Text::SessionSummary,
+        Text::TodayTotal,
+        Text::HoursMinutes,
+        Text::Hours,
+        Text::Minutes,
+        Text::SendFailed,
+        Text::SendRejected,
+        Text::ClockAhead,
+        Text::ClockBehind,
+        Text::ClockFixed,
+        Text::RetryOnNextLaunch,
+        Text::StartedAt,
+        Text::Adjust,
+        Text::Undo,
+        Text::AdjustMinutes,
+        Text::AdjustNotWorking,
+        Text::AdjustTooLarge,
+        Text::LaunchAtLogin,
+        Text::LongStretch,
+        Text::MandatoryBreak,
+        Text::TodayEvents,
+        Text::PastMidnight,
+        Text::SplitAtMidnight,
+        Text::SenderUnavailable,
+        Text::AlreadyRunning,
+        Text::ConfigCorrupted,
+        Text::StateCorrupted,
+        Text::ServerSessionOpen,
+        Text::LocalSessionNotOnServer,
+        Text::AutoClockInPrompt,
+        Text::SendSucceeded,
+    ];
+
+    // ALL の中の位置 (文言を追加したときに ALL への追加漏れをコンパイルエラーにする)
+    fn position(text: Text) -> usize {
+        match text {
+            Text::ClockIn => 0,
+            Text::ClockOut => 1,
+            Text::Break => 2,
+            Text::EndBreak => 3,
+            Text::Meeting => 4,
+            Text::EndMeeting => 5,
+            Text::Hold => 6,
+            Text::EndHold => 7,
+            Text::Profiles => 8,
+            Text::Totals => 9,
+            Text::ExportCsv => 10,
+            Text::ExportMonth => 11,
+            Text::CopyWeeklyReport => 12,
+            Text::WeeklyReportCopied => 13,
+            Text::WeeklyReportFailed => 14,
+            Text::ImportCsv => 15,
+            Text::ImportWhileWorking => 16,
+            Text::ImportDone => 17,
+            Text::ExportJson => 18,
+            Text::ImportJson => 19,
+            Text::ImportJsonConfirm => 20,
+            Text::Settings => 21,
+            Text::OpenDataDir => 22,
+            Text::OpenDataDirFailed => 23,
+            Text::Quit => 24,
+            Text::ClockOutAndQuit => 25,
+            Text::SessionNote => 26,
+            Text::AutoClockedOut => 27,
+            Text::OnBreak => 28,
+            Text::InMeeting => 29,
+            Text::OnHold => 30,
+            Text::GraceCountdown => 31,
+            Text::QuitConfirm => 32,
+            Text::ProfileLocked => 33,
+            Text::ShortcutUnavailable => 34,
+            Text::BreakTooLong => 35,
+            Text::BreakBudgetLeft => 36,
+            Text::BreakBudgetOver => 37,
+            Text::BreakBudgetExceeded => 38,
+            Text::IdleBreakStarted => 39,
+            Text::GoalReached => 40,
+            Text::FocusBlockEnded => 41,
+            Text::BreakReminder => 42,
+            Text::FocusBreakPrompt => 43,
+            Text::OvertimeStarted => 44,
+            Text::SleepExcluded => 45,
+            Text::SessionSummary => 46,
+            Text::TodayTotal => 47,
+            Text::HoursMinutes => 48,
+            Text::Hours => 49,
+            Text::Minutes => 50,
+            Text::SendFailed => 51,
+            Text::SendRejected => 52,
+            Text::ClockAhead => 53,
+            Text::ClockBehind => 54,
+            Text::ClockFixed => 55,
+            Text::RetryOnNextLaunch => 56,
+            Text::StartedAt => 57,
+            Text::Adjust => 58,
+            Text::Undo => 59,
+            Text::AdjustMinutes => 60,
+            Text::AdjustNotWorking => 61,
+            Text::AdjustTooLarge => 62,
+            Text::LaunchAtLogin => 63,
+            Text::LongStretch => 64,
+            Text::MandatoryBreak => 65,
+            Text::TodayEvents => 66,
+            Text::PastMidnight => 67,
+            Text::SplitAtMidnight => 68,
+            Text::SenderUnavailable => 69,
+            Text::AlreadyRunning => 70,
+            Text::ConfigCorrupted => 71,
+            Text::StateCorrupted => 72,
+            Text::ServerSessionOpen => 73,
+            Text::LocalSessionNotOnServer => 74,
+            Text::AutoClockInPrompt => 75,
+            Text::SendSucceeded => 76,
+        }
+    }
+
+    #[test]
+    fn all_lists_every_text() {
+        for (i, text) in ALL.into_iter().enumerate() {
+            assert_eq!(position(text), i, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn every_text_resolves_in_each_language() {
+        for text in ALL {
+            let (ja, en) = (text.get(Lang::Ja), text.get(Lang::En));
+            assert!(!ja.trim().is_empty(), "{:?} の日本語がありません", text);
+            assert!(!en.trim().is_empty(), "{:?} の英語がありません", text);
+            // 埋める値の数はどの言語でも同じ
+            assert_eq!(
+                ja.matches("{}").count(),
+                en.matches("{}").count(),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn english_texts_are_not_left_in_japanese() {
+        for text in ALL {
+            let en = text.get(Lang::En);
+            assert!(
+                !en.chars().any(|c| ('\u{3040}'..='\u{30ff}').contains(&c)),
+                "{:?} の英語にかなが含まれています: {}",
+                text,
+                en
+            );
+        }
+    }
+}
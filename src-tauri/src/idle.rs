@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+// 最後のキーボード・マウスの入力からの経過時間 (取得できない OS では None)
+#[cfg(target_os = "macos")]
+pub fn system_idle_time() -> Option<Duration> {
+    // kCGEventSourceStateCombinedSessionState と kCGAnyInputEventType
+    let secs = unsafe { CGEventSourceSecondsSinceLastEventType(0, u32::MAX) };
+    (secs.is_finite() && secs >= 0.0).then(|| Duration::from_secs_f64(secs))
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn system_idle_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventSourceSecondsSinceLastEventType(state_id: i32, event_type: u32) -> f64;
+}
+
+// 離席とみなす時間を超えていれば離席していた時間を返す (threshold が None なら検出しない)
+pub fn away_time(idle: Option<Duration>, threshold: Option<Duration>) -> Option<Duration> {
+    let (idle, threshold) = (idle?, threshold?);
+    (idle >= threshold).then_some(idle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minutes(m: u64) -> Duration {
+        Duration::from_secs(m * 60)
+    }
+
+    #[test]
+    fn away_once_idle_reaches_threshold() {
+        let threshold = Some(minutes(10));
+        assert_eq!(away_time(Some(minutes(9)), threshold), None);
+        assert_eq!(away_time(Some(minutes(10)), threshold), Some(minutes(10)));
+        assert_eq!(away_time(Some(minutes(25)), threshold), Some(minutes(25)));
+    }
+
+    #[test]
+    fn not_away_without_threshold_or_idle_time() {
+        assert_eq!(away_time(Some(minutes(60)), None), None);
+        assert_eq!(away_time(None, Some(minutes(10))), None);
+    }
+}
@@ -0,0 +1,188 @@
+use std::io;
+use std::path::Path;
+
+use chrono::{DateTime, Local, NaiveDateTime};
+
+use crate::store::{EventAction, EventKind};
+
+// 時刻にタイムゾーンがない場合に試す書式 (ローカル時刻として扱う)
+const NAIVE_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y/%m/%d %H:%M",
+];
+
+// CSV から読み込んだイベント
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedEvent {
+    pub kind: EventKind,
+    pub action: EventAction,
+    pub timestamp: DateTime<Local>,
+}
+
+// CSV を読み込んだ結果
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ParsedCsv {
+    // 時刻の古い順
+    pub events: Vec<ImportedEvent>,
+    // 解釈できずに飛ばした行の数
+    pub skipped: usize,
+}
+
+// 「時刻, 種類, 開始/終了」の CSV ファイルを読み込む
+pub fn read_csv(path: &Path) -> Result<ParsedCsv, csv::Error> {
+    parse_csv(std::fs::File::open(path)?)
+}
+
+// 「時刻, 種類, 開始/終了」の CSV を読み込む
+// 1 行目が見出し (timestamp) なら飛ばし、解釈できない行は数えて飛ばす
+pub fn parse_csv(reader: impl io::Read) -> Result<ParsedCsv, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let mut parsed = ParsedCsv::default();
+    for (index, record) in reader.records().enumerate() {
+        let record = match record {
+            Ok(record) => record,
+            // 文字コードが違うなど行そのものが読めない場合も飛ばす
+            Err(e) if !e.is_io_error() => {
+                parsed.skipped += 1;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let fields: Vec<&str> = record.iter().collect();
+        if index == 0
+            && fields
+                .first()
+                .is_some_and(|f| f.eq_ignore_ascii_case("timestamp"))
+        {
+            continue;
+        }
+        // 空行は数えない
+        if fields.iter().all(|f| f.is_empty()) {
+            continue;
+        }
+        match parse_row(&fields) {
+            Some(event) => parsed.events.push(event),
+            None => parsed.skipped += 1,
+        }
+    }
+    parsed.events.sort_by_key(|event| event.timestamp);
+    Ok(parsed)
+}
+
+// 1 行分を解釈する (列が足りない・値が解釈できない場合は None)
+fn parse_row(fields: &[&str]) -> Option<ImportedEvent> {
+    let [timestamp, kind, action, ..] = fields else {
+        return None;
+    };
+    Some(ImportedEvent {
+        kind: EventKind::parse(&kind.to_lowercase())?,
+        action: EventAction::parse(&action.to_lowercase())?,
+        timestamp: parse_timestamp(timestamp)?,
+    })
+}
+
+// RFC 3339 か、タイムゾーンのない "2024-04-01 09:00" などの形式の時刻を解釈する
+fn parse_timestamp(s: &str) -> Option<DateTime<Local>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(s) {
+        return Some(timestamp.with_timezone(&Local));
+    }
+    NAIVE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .and_then(|naive| naive.and_local_timezone(Local).earliest())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    // 2024 年 4 月 1 日 h 時 m 分 (ローカル時刻)
+    fn local(h: u32, m: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 4, 1, h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn rows_are_parsed_and_sorted_by_time() {
+        let csv = "\
+timestamp,kind,action
+2024-04-01 12:00,break,start
+2024/04/01 09:00:00,Work,START
+2024-04-01 13:00:00,break,end
+2024/04/01 18:30,work,end,メモは無視する
+";
+        let parsed = parse_csv(csv.as_bytes()).unwrap();
+        assert_eq!(parsed.skipped, 0);
+        assert_eq!(
+            parsed.events,
+            [
+                ImportedEvent {
+                    kind: EventKind::Work,
+                    action: EventAction::Start,
+                    timestamp: local(9, 0),
+                },
+                ImportedEvent {
+                    kind: EventKind::Break,
+                    action: EventAction::Start,
+                    timestamp: local(12, 0),
+                },
+                ImportedEvent {
+                    kind: EventKind::Break,
+                    action: EventAction::End,
+                    timestamp: local(13, 0),
+                },
+                ImportedEvent {
+                    kind: EventKind::Work,
+                    action: EventAction::End,
+                    timestamp: local(18, 30),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rfc3339_timestamps_keep_their_offset() {
+        let parsed = parse_csv("2024-04-01T00:00:00Z,work,start\n".as_bytes()).unwrap();
+        assert_eq!(
+            parsed.events[0].timestamp,
+            chrono::Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn malformed_rows_are_counted_and_skipped() {
+        let csv = "\
+2024-04-01 09:00,work,start
+2024-04-01 10:00,work
+2024-04-01 11:00,lunch,start
+2024-04-01 12:00,break,pause
+昨日の朝,work,end
+
+2024-04-01 18:00,work,end
+timestamp,kind,action
+";
+        let parsed = parse_csv(csv.as_bytes()).unwrap();
+        assert_eq!(parsed.events.len(), 2);
+        // 空行は数えず、2 行目以降の見出しは解釈できない行として数える
+        assert_eq!(parsed.skipped, 5);
+    }
+
+    #[test]
+    fn rows_that_are_not_utf8_are_skipped() {
+        let mut csv = b"2024-04-01 09:00,work,start\n".to_vec();
+        // Shift_JIS の「業務」
+        csv.extend_from_slice(b"2024-04-01 12:00,\x8b\xc6\x96\xb1,start\n");
+        csv.extend_from_slice(b"2024-04-01 18:00,work,end\n");
+        let parsed = parse_csv(csv.as_slice()).unwrap();
+        assert_eq!(parsed.events.len(), 2);
+        assert_eq!(parsed.skipped, 1);
+    }
+}
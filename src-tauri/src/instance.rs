@@ -0,0 +1,87 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use tauri::AppHandle;
+
+// 起動中であることを示すロックファイルの名前 (アプリのデータディレクトリに置く)
+const LOCK_FILE_NAME: &str = "app.lock";
+
+// アプリが 1 つしか起動していないことを保つロック (アプリが終了したら OS が解放する)
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    // ロックを取る (既に起動しているアプリが取っていれば None)
+    // ファイルがあるかではなく OS のロックで判断するので、落ちたアプリのロックファイルが残っていても起動できる
+    pub fn acquire(path: &Path) -> io::Result<Option<Self>> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+
+        // どのプロセスが起動しているか調べられるようにプロセス ID を書いておく
+        file.set_len(0)?;
+        writeln!(file, "{}", process::id())?;
+        Ok(Some(Self { _file: file }))
+    }
+}
+
+// ロックファイルのパスを取得
+pub fn lock_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path_resolver()
+        .app_data_dir()
+        .map(|dir| dir.join(LOCK_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // テストごとに別のロックファイル
+    fn temp_lock(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("kintai-instance-{}", process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn second_acquire_is_refused_while_locked() {
+        let path = temp_lock("second");
+        let first = InstanceLock::acquire(&path).unwrap();
+        assert!(first.is_some());
+        assert!(InstanceLock::acquire(&path).unwrap().is_none());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap().trim(),
+            process::id().to_string()
+        );
+    }
+
+    #[test]
+    fn acquire_succeeds_after_lock_is_dropped() {
+        let path = temp_lock("dropped");
+        let first = InstanceLock::acquire(&path).unwrap();
+        assert!(first.is_some());
+        drop(first);
+        assert!(InstanceLock::acquire(&path).unwrap().is_some());
+    }
+
+    #[test]
+    fn stale_lock_file_does_not_block() {
+        let path = temp_lock("stale");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "99999\n").unwrap();
+        assert!(InstanceLock::acquire(&path).unwrap().is_some());
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, Registry};
+
+// ログファイルの名前 (kintai.2024-01-01.log のように日付が入る)
+const LOG_FILE_PREFIX: &str = "kintai";
+const LOG_FILE_SUFFIX: &str = "log";
+// 残しておくログファイルの数 (日数)
+const MAX_LOG_FILES: usize = 14;
+
+// 設定を読み込んだあとでログのレベルを変えるためのハンドル
+pub type LevelHandle = reload::Handle<LevelFilter, Registry>;
+
+// ログの出力を開始 (dir に日ごとのファイルを作る, dir がなければ標準エラー出力だけ)
+pub fn init(dir: Option<&Path>) -> Option<LevelHandle> {
+    let (level, handle) = reload::Layer::new(LevelFilter::INFO);
+
+    let file = dir.and_then(|dir| {
+        RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix(LOG_FILE_SUFFIX)
+            .max_log_files(MAX_LOG_FILES)
+            .build(dir)
+            .map_err(|e| eprintln!("ログファイルを作成できません: {}", e))
+            .ok()
+    });
+
+    let result = tracing_subscriber::registry()
+        .with(level)
+        .with(file.map(|file| fmt::layer().with_ansi(false).with_writer(file)))
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .try_init();
+    match result {
+        Ok(()) => Some(handle),
+        Err(e) => {
+            eprintln!("ログの出力を開始できません: {}", e);
+            None
+        }
+    }
+}
+
+// ログのレベルを変更 ("error", "warn", "info", "debug", "trace")
+pub fn set_level(handle: &LevelHandle, level: &str) {
+    let Ok(filter) = level.parse::<LevelFilter>() else {
+        tracing::warn!("ログのレベルが正しくありません: {}", level);
+        return;
+    };
+    if let Err(e) = handle.modify(|current| *current = filter) {
+        tracing::warn!("ログのレベルを変更できません: {}", e);
+    }
+}
@@ -1,184 +1,1627 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-#[cfg(target_os = "macos")]
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
-};
+mod autostart;
+mod backup;
+mod clock;
+mod config;
+mod event_socket;
+mod file;
+mod flusher;
+mod format;
+mod i18n;
+mod idle;
+mod import;
+mod instance;
+mod logging;
+mod notification;
+mod queue;
+mod report;
+mod screen_lock;
+mod secret;
+mod sender;
+mod state;
+mod status_server;
+mod store;
+mod timer;
+mod tray;
+mod webhook;
+mod work;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::sync::{atomic::Ordering, mpsc, Arc, Mutex, Once};
 use std::thread;
 use std::time::Duration;
 
+use chrono::{DateTime, Local, NaiveTime};
+use tauri::api::dialog::{self, FileDialogBuilder};
+use tauri::api::shell;
+#[cfg(target_os = "macos")]
+use tauri::ActivationPolicy;
 use tauri::{
-    ActivationPolicy, AppHandle, CustomMenuItem, SystemTray, SystemTrayEvent, SystemTrayMenu,
-    SystemTrayMenuItem,
+    AppHandle, ClipboardManager, CustomMenuItem, GlobalShortcutManager, Manager, RunEvent,
+    SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem, SystemTraySubmenu,
+    WindowBuilder, WindowUrl,
 };
 
-fn main() {
-    let quit = CustomMenuItem::new("quit".to_string(), "Quit");
-    let attendance = CustomMenuItem::new("attendance".to_string(), "業務開始");
-    let break_time = CustomMenuItem::new("break_time".to_string(), "休憩").disabled();
+use tracing::{debug, error, info, warn};
 
-    let tray_menu = SystemTrayMenu::new()
-        .add_item(attendance.clone()) // Clone attendance item for toggling its title
-        .add_item(break_time.clone()) // Clone break_time item for toggling its title
-        .add_native_item(SystemTrayMenuItem::Separator)
-        .add_item(quit);
+use clock::ClockGuard;
+use config::{Config, ConfigError, TrayAction, TrayClick};
+use flusher::{flush_queue, record_event, send_status, start_flusher};
+use format::{format_clock, format_duration, format_title};
+use i18n::{t, t_with, Text};
+use instance::InstanceLock;
+use notification::{notify, notify_as, notify_corrupted, quiet, send_failure_message, Urgency};
+use queue::EventQueue;
+use sender::Sender;
+use state::{PersistedState, Reconciliation};
+use store::{today_totals, EventAction, EventKind, EventStore, StoredEvent};
+use timer::{daily_goal, refresh_title, start_timer};
+use tray::{menu_label, Effect, TrayIcon, TrayState};
+use work::{
+    apply_transition, elapsed_since, save_state, set_started_at, set_undo, started_at_label,
+    sync_menu, transition, work_status, WorkState, WorkStatus,
+};
+
+// 業務開始を確認する時刻になったかを調べる間隔
+const AUTO_CLOCK_IN_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// 休憩を勧める時刻になったかを調べる間隔
+const BREAK_REMINDER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// 設定ウィンドウのラベル
+const SETTINGS_WINDOW_LABEL: &str = "settings";
+// 集計ウィンドウのラベル
+const TOTALS_WINDOW_LABEL: &str = "totals";
+// 今日の記録ウィンドウのラベル
+const EVENTS_WINDOW_LABEL: &str = "events";
+// 業務のメモを入力するウィンドウのラベル
+const NOTE_WINDOW_LABEL: &str = "note";
+// 経過時間の調整のメニューに並べる分数
+const ADJUST_MINUTES: [i64; 6] = [5, 15, 30, -5, -15, -30];
+
+fn main() {
+    // 設定ファイルの場所の指定は Tauri を起動する前に確認する (ログはまだ使えないので標準エラーに出す)
+    if let Err(e) = config::init_path_override() {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
 
-    let system_tray = SystemTray::new().with_menu(tray_menu);
+    // メニューはプロファイルの設定を読み込んでから作る
+    // 左クリックでメニューを開くかはトレイを作るときにしか決められないので、先に設定を読み込んでおく
+    let context = tauri::generate_context!();
+    let system_tray = SystemTray::new();
+    #[cfg(target_os = "macos")]
+    let system_tray = system_tray
+        .with_menu_on_left_click(config::load_before_launch(context.config()).menu_on_left_click());
 
-    let is_working = Arc::new(AtomicBool::new(false)); // 業務開始状態のフラグ
-    let is_on_break = Arc::new(AtomicBool::new(false)); // 休憩状態のフラグ
+    let work = WorkState::default();
+    let setup_work = work.clone();
+    let exit_work = work.clone();
 
     tauri::Builder::default()
-        .setup(|app| {
+        .setup(move |app| {
+            // Dock にアイコンを出さない (macOS のみ)
+            #[cfg(target_os = "macos")]
             app.set_activation_policy(ActivationPolicy::Accessory);
+            // 2 つ目に起動したアプリは何もせずに終了する (両方のトレイから記録して二重にならないように)
+            // ログや設定・状態のファイルに触れる前に確かめる (ログはまだ使えないので標準エラーに出す)
+            let lock =
+                match instance::lock_path(&app.handle()).map(|path| InstanceLock::acquire(&path)) {
+                    Some(Ok(Some(lock))) => Ok(lock),
+                    Some(Ok(None)) => {
+                        eprintln!("既に起動しているため終了します");
+                        i18n::init(config::load_before_launch(&app.config()).language);
+                        notify_as(&app.handle(), Urgency::Reply, t(Text::AlreadyRunning));
+                        process::exit(0);
+                    }
+                    Some(Err(e)) => Err(format!("起動中のアプリを確認できませんでした: {}", e)),
+                    None => Err("ロックファイルの場所がわかりません".to_string()),
+                };
+            let log_level = logging::init(app.path_resolver().app_log_dir().as_deref());
+            match lock {
+                Ok(lock) => {
+                    app.manage(lock);
+                }
+                Err(e) => warn!("{}", e),
+            }
+            let (config, has_endpoint) = load_config(&app.handle());
+            if let Some(handle) = &log_level {
+                logging::set_level(handle, &config.log_level);
+            }
+            i18n::init(config.language);
+            app.tray_handle().set_menu(tray_menu(&config))?;
+            tray::set_icon(&app.handle(), TrayIcon::Idle);
+            autostart::sync(config.launch_at_login);
+            refresh_launch_at_login(&app.handle());
+            app.manage(Mutex::new(config));
+            app.manage(open_store(&app.handle()));
+            // ウィンドウから呼ぶコマンドもトレイと同じ勤務状態を使う
+            app.manage(setup_work.clone());
+
+            // 前回送れなかったイベントも含めて送信を開始
+            let queue = Arc::new(EventQueue::new(queue::queue_path(&app.handle())));
+            app.manage(Arc::clone(&queue));
+            app.manage(ClockGuard::default());
+
+            // 送信用のランタイムは起動時に 1 つだけ作る
+            // (作れなければ送信せずにキューに貯めておき、次回の起動時に送る)
+            match Sender::new() {
+                Ok(sender) => {
+                    app.manage(sender);
+                    start_flusher(&app.handle(), queue);
+                }
+                Err(e) => {
+                    error!("送信の準備ができませんでした: {}", e);
+                    notify_as(&app.handle(), Urgency::Critical, t(Text::SenderUnavailable));
+                }
+            }
+
+            start_event_socket(&app.handle());
+            restore_state(&app.handle(), &setup_work);
+            start_status_server(&app.handle());
+            reconcile_with_server(&app.handle(), &setup_work);
+            register_shortcuts(&app.handle(), &setup_work);
+            observe_screen_lock(&app.handle(), &setup_work);
+            start_auto_clock_in(&app.handle(), &setup_work);
+            start_break_reminders(&app.handle(), &setup_work);
+            #[cfg(unix)]
+            handle_signals(&app.handle(), &setup_work)?;
+
+            // 初回起動時は送信先をキーチェーンに登録してもらうために設定ウィンドウを開く
+            if !has_endpoint {
+                handle_settings(&app.handle());
+            }
             Ok(())
         })
+        .invoke_handler(tauri::generate_handler![
+            get_config,
+            save_config,
+            get_totals,
+            get_today_events,
+            update_event_time,
+            delete_event,
+            get_status,
+            toggle_work,
+            clock_in_with_note,
+            toggle_break,
+            get_today_summary
+        ])
         .system_tray(system_tray)
         .enable_macos_default_menu(false)
-        .on_system_tray_event(move |app, event| {
-            // フラグをクロージャ内で共有
-            let is_working = Arc::clone(&is_working);
-            let is_on_break = Arc::clone(&is_on_break);
-
-            match event {
-                SystemTrayEvent::LeftClick { .. } => {
-                    handle_tray_left_click(app, &is_working, &is_on_break);
-                }
-                SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
-                    "quit" => {
-                        std::process::exit(0);
-                    }
-                    "attendance" => {
-                        handle_attendance(app, &is_working, &is_on_break);
+        .on_system_tray_event(move |app, event| match event {
+            SystemTrayEvent::LeftClick { .. } => {
+                refresh_launch_at_login(app);
+                handle_tray_click(app, &work, TrayClick::Left);
+            }
+            SystemTrayEvent::DoubleClick { .. } => {
+                handle_tray_click(app, &work, TrayClick::Double);
+            }
+            SystemTrayEvent::RightClick { .. } => {
+                refresh_launch_at_login(app);
+                handle_tray_click(app, &work, TrayClick::Right);
+            }
+            SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
+                "quit" => {
+                    handle_quit(app, &work);
+                }
+                "clock_out_quit" => {
+                    handle_clock_out_and_quit(app, &work);
+                }
+                "attendance" => {
+                    handle_attendance(app, &work);
+                }
+                "break_time" => {
+                    handle_break_time(app, &work);
+                }
+                "meeting" => {
+                    handle_meeting(app, &work);
+                }
+                "hold" => {
+                    handle_hold(app, &work);
+                }
+                "undo" => {
+                    handle_undo(app, &work);
+                }
+                "export_month" => {
+                    handle_export_month(app);
+                }
+                "weekly_report" => {
+                    handle_weekly_report(app);
+                }
+                "export_csv" => {
+                    handle_export_csv(app);
+                }
+                "import_csv" => {
+                    handle_import_csv(app, &work);
+                }
+                "export_json" => {
+                    handle_export_json(app);
+                }
+                "import_json" => {
+                    handle_import_json(app, &work);
+                }
+                "totals" => {
+                    handle_totals(app);
+                }
+                "today_events" => {
+                    handle_today_events(app);
+                }
+                "settings" => {
+                    handle_settings(app);
+                }
+                "open_data_dir" => {
+                    handle_open_data_dir(app);
+                }
+                "launch_at_login" => {
+                    handle_launch_at_login(app);
+                }
+                id => {
+                    if let Some(index) = id.strip_prefix("profile:") {
+                        handle_profile(app, &work, index.parse().unwrap_or_default());
+                    } else if let Some(minutes) = id.strip_prefix("adjust:") {
+                        handle_adjust(app, &work, minutes.parse().unwrap_or_default());
+                    } else if let Some(index) = id.strip_prefix("break_reason:") {
+                        handle_break_reason(app, &work, index.parse().unwrap_or_default());
                     }
-                    "break_time" => {
-                        handle_break_time(app, &is_on_break);
+                }
+            },
+            _ => {}
+        })
+        .build(context)
+        .expect("error while building tauri application")
+        .run(move |app, event| {
+            // ログアウトやシャットダウンでアプリが終了するとき
+            if let RunEvent::Exit = event {
+                shutdown(app, &exit_work);
+            }
+        });
+}
+
+// 終了のシグナルを受けたら業務を終了して送信してから終了する
+#[cfg(unix)]
+fn handle_signals(app: &AppHandle, work: &WorkState) -> std::io::Result<()> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+
+    let mut signals = signal_hook::iterator::Signals::new([SIGTERM, SIGINT, SIGHUP])?;
+    let (app_clone, work_clone) = (app.clone(), work.clone());
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            info!("シグナル {} を受け取ったので終了します", signal);
+            shutdown(&app_clone, &work_clone);
+            std::process::exit(0);
+        }
+    });
+    Ok(())
+}
+
+// 所定労働時間のある曜日に設定した時刻になったら、業務を開始するか確認する
+// (起動した時点で今日の時刻を過ぎていれば、今日は確認しない)
+fn start_auto_clock_in(app: &AppHandle, work: &WorkState) {
+    let (app, work) = (app.clone(), work.clone());
+    thread::spawn(move || {
+        let mut last_checked = Local::now().naive_local();
+        loop {
+            thread::sleep(AUTO_CLOCK_IN_CHECK_INTERVAL);
+            let now = Local::now().naive_local();
+            let (start, schedule) = {
+                let config = app.state::<Mutex<Config>>();
+                let config = config.lock().unwrap();
+                (config.auto_clock_in_time(), config.schedule)
+            };
+            match start {
+                Ok(Some(start))
+                    if schedule.start_passed(start, last_checked, now)
+                        && !work.is_working.load(Ordering::Relaxed) =>
+                {
+                    ask_auto_clock_in(&app, &work, start);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("{}", e),
+            }
+            last_checked = now;
+        }
+    });
+}
+
+// 業務を開始するか確認し、「はい」なら業務を開始する (その間に業務を開始していれば何もしない)
+fn ask_auto_clock_in(app: &AppHandle, work: &WorkState, start: NaiveTime) {
+    let clock_24h = app.state::<Mutex<Config>>().lock().unwrap().clock_24h;
+    let time = Local::now()
+        .date_naive()
+        .and_time(start)
+        .and_local_timezone(Local)
+        .earliest()
+        .map_or_else(
+            || start.format("%H:%M").to_string(),
+            |at| format_clock(&at, clock_24h),
+        );
+    let (app, work) = (app.clone(), work.clone());
+    dialog::ask(
+        None::<&tauri::Window>,
+        "72時間",
+        t_with(Text::AutoClockInPrompt, &[&time]),
+        move |clock_in| {
+            if !clock_in {
+                info!("業務の自動開始を取り消しました");
+                return;
+            }
+            transition(&app, &work, |state| {
+                if state.is_working {
+                    Vec::new()
+                } else {
+                    state.toggle_work()
+                }
+            });
+        },
+    );
+}
+
+// 設定した時刻になったら、業務中で休憩中・会議中でなければ休憩を勧めるスレッドを始める
+// (スリープ中に過ぎた時刻は、スリープから戻ったときに勧める)
+fn start_break_reminders(app: &AppHandle, work: &WorkState) {
+    let (app, work) = (app.clone(), work.clone());
+    thread::spawn(move || {
+        let mut last_checked = Local::now().naive_local();
+        loop {
+            thread::sleep(BREAK_REMINDER_CHECK_INTERVAL);
+            let now = Local::now().naive_local();
+            let reminders = app
+                .state::<Mutex<Config>>()
+                .lock()
+                .unwrap()
+                .break_reminders
+                .clone();
+            for reminder in reminders {
+                match reminder.is_due(last_checked, now) {
+                    Ok(true) => {
+                        if work.tray_state().accepts_break_reminder() {
+                            suggest_scheduled_break(&app, &work, &reminder.label);
+                        } else {
+                            debug!(
+                                "業務中でないか休憩中・会議中なので休憩を勧めません: {}",
+                                reminder.label
+                            );
+                        }
                     }
-                    _ => {}
-                },
-                _ => {}
+                    Ok(false) => {}
+                    Err(e) => warn!("{}", e),
+                }
             }
-        })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+            last_checked = now;
+        }
+    });
 }
 
-// タスクトレイ右クリックの処理
-fn handle_tray_left_click(
-    app: &AppHandle,
-    is_working: &Arc<AtomicBool>,
-    is_on_break: &Arc<AtomicBool>,
-) {
-    if is_on_break.load(Ordering::Relaxed) {
-        handle_break_time(app, is_on_break);
+// 休憩の時間になったことを通知し、休憩を始めるか確認する (その間に休憩を始めていれば何もしない)
+fn suggest_scheduled_break(app: &AppHandle, work: &WorkState, label: &str) {
+    let message = t_with(Text::BreakReminder, &[label]);
+    notify(app, &message);
+    // 通知を表示しない時間帯には確認もしない
+    if quiet(app, Urgency::Normal) {
+        return;
+    }
+
+    let (app, work) = (app.clone(), work.clone());
+    dialog::ask(
+        None::<&tauri::Window>,
+        "72時間",
+        format!("{}\n{}", message, t(Text::FocusBreakPrompt)),
+        move |start_break| {
+            if start_break {
+                transition(&app, &work, |state| state.start_break(Duration::ZERO));
+            }
+        },
+    );
+}
+
+// 画面をロックしたら休憩を始め、ロックを解除したらその休憩を終える (設定で有効にした場合)
+// 手動や離席の検出で始めた休憩は、ロックを解除しても終えない
+fn observe_screen_lock(app: &AppHandle, work: &WorkState) {
+    let (app, work) = (app.clone(), work.clone());
+    screen_lock::observe(move |locked| {
+        if !app
+            .state::<Mutex<Config>>()
+            .lock()
+            .unwrap()
+            .break_on_screen_lock
+        {
+            return;
+        }
+
+        let mut changed = false;
+        transition(&app, &work, |state| {
+            let effects = screen_lock::on_change(state, &work.lock_break, locked);
+            changed = !effects.is_empty();
+            effects
+        });
+        match (changed, locked) {
+            (true, true) => info!("画面をロックしたので休憩を開始しました"),
+            (true, false) => info!("画面のロックを解除したので休憩を終了しました"),
+            _ => {}
+        }
+    });
+}
+
+// 終了前の後始末 (業務中なら業務を終了し、未送信イベントの送信を一定時間だけ待つ)
+fn shutdown(app: &AppHandle, work: &WorkState) {
+    // シグナルとアプリの終了イベントの両方から呼ばれても 1 回だけ行う
+    static SHUTDOWN: Once = Once::new();
+    SHUTDOWN.call_once(|| {
+        // 休憩中・会議中ならそれを終えてから業務を終了する
+        if work.is_on_break.load(Ordering::Relaxed) {
+            handle_break_time(app, work);
+        }
+        if work.is_in_meeting.load(Ordering::Relaxed) {
+            handle_meeting(app, work);
+        }
+        if work.is_working.load(Ordering::Relaxed) {
+            handle_attendance(app, work);
+        }
+
+        let timeout = Duration::from_secs(
+            app.state::<Mutex<Config>>()
+                .lock()
+                .unwrap()
+                .shutdown_timeout_secs,
+        );
+        let (tx, rx) = mpsc::channel();
+        let app_clone = app.clone();
+        thread::spawn(move || {
+            let queue = app_clone.state::<Arc<EventQueue>>();
+            let _ = tx.send(flush_queue(&app_clone, &queue, true));
+        });
+        match rx.recv_timeout(timeout) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => notify_as(
+                app,
+                Urgency::Critical,
+                &t_with(Text::RetryOnNextLaunch, &[&send_failure_message(&e)]),
+            ),
+            Err(_) => warn!("送信が終わらないまま終了します (次回の起動時に再送します)"),
+        }
+    });
+}
+
+// トレイのメニューを作成
+fn tray_menu(config: &Config) -> SystemTrayMenu {
+    let quit = CustomMenuItem::new("quit".to_string(), t(Text::Quit));
+    let clock_out_quit =
+        CustomMenuItem::new("clock_out_quit".to_string(), t(Text::ClockOutAndQuit));
+    let attendance =
+        CustomMenuItem::new("attendance".to_string(), menu_label(config, Text::ClockIn));
+    let break_time =
+        CustomMenuItem::new("break_time".to_string(), menu_label(config, Text::Break)).disabled();
+    let meeting = CustomMenuItem::new("meeting".to_string(), t(Text::Meeting)).disabled();
+    let hold = CustomMenuItem::new("hold".to_string(), t(Text::Hold)).disabled();
+    let export_csv = CustomMenuItem::new("export_csv".to_string(), t(Text::ExportCsv));
+    let import_csv = CustomMenuItem::new("import_csv".to_string(), t(Text::ImportCsv));
+    let export_json = CustomMenuItem::new("export_json".to_string(), t(Text::ExportJson));
+    let import_json = CustomMenuItem::new("import_json".to_string(), t(Text::ImportJson));
+    let export_month = CustomMenuItem::new("export_month".to_string(), t(Text::ExportMonth));
+    let weekly_report = CustomMenuItem::new("weekly_report".to_string(), t(Text::CopyWeeklyReport));
+    let totals = CustomMenuItem::new("totals".to_string(), t(Text::Totals));
+    let today_events = CustomMenuItem::new("today_events".to_string(), t(Text::TodayEvents));
+    let settings = CustomMenuItem::new("settings".to_string(), t(Text::Settings));
+    let open_data_dir = CustomMenuItem::new("open_data_dir".to_string(), t(Text::OpenDataDir));
+    let launch_at_login =
+        CustomMenuItem::new("launch_at_login".to_string(), t(Text::LaunchAtLogin));
+
+    let started_at = CustomMenuItem::new(
+        "started_at".to_string(),
+        started_at_label(None, config.clock_24h),
+    )
+    .disabled();
+
+    // 打刻し忘れたときに経過時間を調整する
+    let mut adjust = SystemTrayMenu::new();
+    for minutes in ADJUST_MINUTES {
+        let label = t_with(Text::AdjustMinutes, &[&format!("{:+}", minutes)]);
+        adjust = adjust.add_item(CustomMenuItem::new(format!("adjust:{}", minutes), label));
+    }
+
+    let undo = CustomMenuItem::new("undo".to_string(), t(Text::Undo)).disabled();
+
+    let mut tray_menu = SystemTrayMenu::new()
+        .add_item(started_at)
+        .add_item(attendance);
+    // 休憩の理由があれば、理由を選んで休憩を始めるサブメニューにする (休憩の終了は 1 つの項目で行う)
+    if config.break_reasons.is_empty() {
+        tray_menu = tray_menu.add_item(break_time);
     } else {
-        handle_attendance(app, is_working, is_on_break);
+        let mut reasons = SystemTrayMenu::new();
+        for (index, reason) in config.break_reasons.iter().enumerate() {
+            reasons = reasons.add_item(
+                CustomMenuItem::new(format!("break_reason:{}", index), reason).disabled(),
+            );
+        }
+        reasons = reasons
+            .add_native_item(SystemTrayMenuItem::Separator)
+            .add_item(break_time);
+        tray_menu = tray_menu.add_submenu(SystemTraySubmenu::new(
+            menu_label(config, Text::Break),
+            reasons,
+        ));
+    }
+    tray_menu = tray_menu
+        .add_item(meeting)
+        .add_item(hold)
+        .add_item(undo)
+        .add_submenu(SystemTraySubmenu::new(t(Text::Adjust), adjust))
+        .add_native_item(SystemTrayMenuItem::Separator);
+
+    // プロファイルがあれば選べるようにする
+    if !config.profiles.is_empty() {
+        let active = config.active();
+        let mut profiles = SystemTrayMenu::new();
+        for (index, profile) in config.profiles.iter().enumerate() {
+            let mut item = CustomMenuItem::new(format!("profile:{}", index), &profile.label);
+            if active == Some(profile) {
+                item = item.selected();
+            }
+            profiles = profiles.add_item(item);
+        }
+        tray_menu = tray_menu
+            .add_submenu(SystemTraySubmenu::new(t(Text::Profiles), profiles))
+            .add_native_item(SystemTrayMenuItem::Separator);
+    }
+
+    tray_menu
+        .add_item(totals)
+        .add_item(today_events)
+        .add_item(export_csv)
+        .add_item(export_month)
+        .add_item(weekly_report)
+        .add_item(import_csv)
+        .add_item(export_json)
+        .add_item(import_json)
+        .add_item(settings)
+        .add_item(open_data_dir)
+        .add_item(launch_at_login)
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(clock_out_quit)
+        .add_item(quit)
+}
+
+// プロファイルのメニュー項目の処理 (業務中は切り替えない)
+fn handle_profile(app: &AppHandle, work: &WorkState, index: usize) {
+    let state = app.state::<Mutex<Config>>();
+
+    // 通知は設定をロックする前に表示する (通知も設定を読むため)
+    let working = work.is_working.load(Ordering::Relaxed);
+    if working {
+        notify_as(app, Urgency::Reply, t(Text::ProfileLocked));
+    }
+
+    let mut config = state.lock().unwrap();
+    if let Some(profile) = config.profiles.get(index).filter(|_| !working) {
+        config.active_profile = profile.label.clone();
+        if let Some(path) = config::config_path(app) {
+            if let Err(e) = secret::store(&config).save(&path) {
+                warn!("プロファイルの選択を保存できませんでした: {}", e);
+            }
+        }
+    }
+
+    // クリックでチェックが外れることがあるので選択中のものに付け直す
+    let active = config.active().cloned();
+    for (index, profile) in config.profiles.iter().enumerate() {
+        let item = app.tray_handle().get_item(&format!("profile:{}", index));
+        let _ = item.set_selected(active.as_ref() == Some(profile));
+    }
+}
+
+// 経過時間の調整のメニュー項目の処理 (業務中だけ調整できる)
+// 調整している間に業務を終了すると消したタイトルを書き戻してしまうので、状態遷移と排他にする
+fn handle_adjust(app: &AppHandle, work: &WorkState, minutes: i64) {
+    let _guard = work.transition_lock.lock().unwrap();
+    if !work.is_working.load(Ordering::Relaxed) {
+        notify_as(app, Urgency::Reply, t(Text::AdjustNotWorking));
+        return;
+    }
+    if !work.adjust(minutes * 60) {
+        notify_as(app, Urgency::Reply, t(Text::AdjustTooLarge));
+        return;
+    }
+    info!("経過時間を {:+} 分調整しました", minutes);
+    // 取り消すと調整も戻ってしまうので、調整より前の操作は取り消せないようにする
+    set_undo(app, work, None);
+
+    // イベントログの業務開始もずらして、調整したことを残しておく
+    let store = app.state::<EventStore>();
+    if let Err(e) = store.adjust_start(EventKind::Work, minutes * 60, &Local::now()) {
+        warn!("経過時間の調整を記録できませんでした: {}", e);
+    }
+
+    // 時計の表示をすぐに反映する
+    let started_at = *work.started_at.lock().unwrap();
+    set_started_at(app, work, started_at);
+    refresh_title(app, work);
+}
+
+// トレイのアイコンをクリックしたときの処理 (設定した操作を行う)
+// メニューを開くのは OS が行うので、ここでは何もしない
+fn handle_tray_click(app: &AppHandle, work: &WorkState, click: TrayClick) {
+    let action = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .tray_action(click);
+    match action {
+        TrayAction::Toggle => transition(app, work, TrayState::left_click),
+        TrayAction::ToggleWork => handle_attendance(app, work),
+        TrayAction::ToggleBreak => handle_break_time(app, work),
+        TrayAction::Totals => handle_totals(app),
+        TrayAction::Menu | TrayAction::None => {}
     }
 }
 
 // "attendance" メニュー項目の処理
-fn handle_attendance(app: &AppHandle, is_working: &Arc<AtomicBool>, is_on_break: &Arc<AtomicBool>) {
-    // 業務開始/業務終了を切り替える
-    let new_value = !is_working.load(Ordering::Relaxed);
-    is_working.store(new_value, Ordering::Relaxed);
-
-    // メニューアイテムのタイトルを更新
-    let item_handle = app.tray_handle().get_item("attendance");
-    let new_title = if new_value {
-        "業務終了"
-    } else {
-        "業務開始"
+// (業務開始のときにメモを入力する設定なら、先にメモを入力するウィンドウを開く)
+fn handle_attendance(app: &AppHandle, work: &WorkState) {
+    let ask_note = app.state::<Mutex<Config>>().lock().unwrap().ask_note;
+    if ask_note && !work.is_working.load(Ordering::Relaxed) {
+        open_window(
+            app,
+            NOTE_WINDOW_LABEL,
+            "note.html",
+            t(Text::SessionNote),
+            (320.0, 140.0),
+        );
+        return;
+    }
+    transition(app, work, TrayState::toggle_work);
+}
+
+// メモを付けて業務を開始する (メモを入力するウィンドウから呼ぶ, 空ならメモなし)
+#[tauri::command]
+fn clock_in_with_note(app: AppHandle, note: String) -> WorkStatus {
+    let work = app.state::<WorkState>();
+    *work.note.lock().unwrap() = Some(note);
+    transition(&app, &work, |state| {
+        if state.is_working {
+            return Vec::new();
+        }
+        state.toggle_work()
+    });
+    // 確認している間に業務を開始していた場合は使わなかったメモを捨てる
+    work.note.lock().unwrap().take();
+
+    if let Some(window) = app.get_window(NOTE_WINDOW_LABEL) {
+        let _ = window.close();
+    }
+    work_status(&app, &work)
+}
+
+// "break_time" メニュー項目の処理
+fn handle_break_time(app: &AppHandle, work: &WorkState) {
+    transition(app, work, TrayState::toggle_break);
+}
+
+// 休憩の理由のメニュー項目の処理 (理由を覚えて休憩を始める, 休憩中・会議中は何もしない)
+fn handle_break_reason(app: &AppHandle, work: &WorkState, index: usize) {
+    let reason = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .break_reasons
+        .get(index)
+        .cloned();
+    let Some(reason) = reason else {
+        return;
     };
-    let _ = item_handle.set_title(new_title);
+    transition(app, work, |state| {
+        let effects = state.start_break(Duration::ZERO);
+        if !effects.is_empty() {
+            *work.break_reason.lock().unwrap() = Some(reason);
+        }
+        effects
+    });
+}
 
-    // タイマーを開始または停止
-    if new_value {
-        start_timer(app, is_working.clone(), is_on_break.clone());
+// "meeting" メニュー項目の処理
+fn handle_meeting(app: &AppHandle, work: &WorkState) {
+    transition(app, work, TrayState::toggle_meeting);
+}
 
-        // "break_time" メニューアイテムを有効化
-        let item_handle = app.tray_handle().get_item("break_time");
-        let _ = item_handle.set_enabled(true);
-        let _ = send_req("業務 開始");
-    } else {
-        // "break_time" メニューアイテムを無効化
-        let item_handle = app.tray_handle().get_item("break_time");
-        let _ = item_handle.set_enabled(false);
+// "hold" メニュー項目の処理
+fn handle_hold(app: &AppHandle, work: &WorkState) {
+    transition(app, work, TrayState::toggle_hold);
+}
 
-        let app_clone = app.clone();
-        let _ = app_clone.tray_handle().set_title("");
-        let _ = send_req("業務 終了");
+// "undo" メニュー項目の処理 (直前の操作だけを取り消す, 続けて選んでも何もしない)
+fn handle_undo(app: &AppHandle, work: &WorkState) {
+    let _guard = work.transition_lock.lock().unwrap();
+    let Some(snapshot) = work.take_undo() else {
+        return;
+    };
+
+    // 日付をまたいで業務を分けたときなど、1 つの操作でなければ取り消せない
+    if !apply_transition(app, work, |state| state.undo(snapshot.state)) {
+        set_undo(app, work, None);
+        return;
     }
+    // 業務の開始・休憩の開始などで初期化された時間を操作する前のものに戻す
+    work.restore(&snapshot);
+    set_started_at(app, work, snapshot.started_at);
+    refresh_title(app, work);
+    save_state(app, work);
+    let _ = app.emit_all("status-changed", work_status(app, work));
+    // 取り消しそのものは取り消せない
+    set_undo(app, work, None);
+    info!("直前の操作を取り消しました: {:?}", snapshot.state);
 }
 
-// "break_time" メニュー項目の処理
-fn handle_break_time(app: &AppHandle, is_on_break: &Arc<AtomicBool>) {
-    let new_value = !is_on_break.load(Ordering::Relaxed);
-    is_on_break.store(new_value, Ordering::Relaxed);
+// 設定を変えたときにメニューの文言を今の状態に合わせて付け直す
+fn refresh_menu_labels(app: &AppHandle) {
+    let work = app.state::<WorkState>();
+    sync_menu(app, &work.tray_state());
+}
 
-    // メニューアイテムのタイトルを更新
-    let item_handle = app.tray_handle().get_item("break_time");
-    let new_title = if new_value { "休憩解除" } else { "休憩" };
-    let _ = item_handle.set_title(new_title);
+// 設定されたグローバルショートカットを登録
+fn register_shortcuts(app: &AppHandle, work: &WorkState) {
+    let config = app.state::<Mutex<Config>>().lock().unwrap().clone();
 
-    if new_value {
-        let app_clone = app.clone();
-        let _ = app_clone.tray_handle().set_title("休憩中");
+    let (app_clone, work_clone) = (app.clone(), work.clone());
+    register_shortcut(app, &config.attendance_shortcut, move || {
+        handle_attendance(&app_clone, &work_clone);
+    });
 
-        // "attendance" メニューアイテムを無効化
-        let item_handle = app.tray_handle().get_item("attendance");
-        let _ = item_handle.set_enabled(false);
-        let _ = send_req("休憩 開始");
-    } else {
-        // "attendance" メニューアイテムを有効化
-        let item_handle = app.tray_handle().get_item("attendance");
-        let _ = item_handle.set_enabled(true);
-        let _ = send_req("休憩 終了");
+    let (app_clone, work_clone) = (app.clone(), work.clone());
+    register_shortcut(app, &config.break_shortcut, move || {
+        handle_break_time(&app_clone, &work_clone);
+    });
+}
+
+// グローバルショートカットを 1 つ登録 (空なら登録しない)
+fn register_shortcut<F: Fn() + Send + 'static>(app: &AppHandle, accelerator: &str, handler: F) {
+    if accelerator.trim().is_empty() {
+        return;
+    }
+
+    // OS や他のアプリが既に使っている場合は登録できないので、設定の変更を促す
+    if let Err(e) = app.global_shortcut_manager().register(accelerator, handler) {
+        warn!(
+            "ショートカット {} を登録できませんでした: {}",
+            accelerator, e
+        );
+        notify(app, &t_with(Text::ShortcutUnavailable, &[accelerator]));
     }
 }
 
-// タイマーを開始
-fn start_timer(app: &AppHandle, is_working: Arc<AtomicBool>, is_on_break: Arc<AtomicBool>) {
+// "quit" メニュー項目の処理 (業務中なら確認して業務終了を送ってから終了する)
+fn handle_quit(app: &AppHandle, work: &WorkState) {
+    if !work.is_working.load(Ordering::Relaxed) {
+        std::process::exit(0);
+    }
+
     let app_clone = app.clone();
-    thread::spawn(move || {
-        let mut time = Duration::from_secs(0);
-        loop {
-            if !is_working.load(Ordering::Relaxed) {
-                break;
-            }
-            if is_on_break.load(Ordering::Relaxed) {
-                continue;
+    let work = work.clone();
+    dialog::ask(
+        None::<&tauri::Window>,
+        "72時間",
+        t(Text::QuitConfirm),
+        move |confirmed| {
+            if !confirmed {
+                return;
             }
-            time += Duration::from_secs(1);
-            let formatted_duration = format_duration(time);
 
-            // アプリケーションのトレイハンドルを使ってタイトルを設定
-            let _ = app_clone.tray_handle().set_title(&formatted_duration);
+            // 業務を終了して送信を待ってから終了
+            shutdown(&app_clone, &work);
+            std::process::exit(0);
+        },
+    );
+}
+
+// "clock_out_quit" メニュー項目の処理 (確認せずに業務を終了し、送信を待ってから終了する)
+// 送信できなかった業務終了はキューに残るので、次回の起動時に送信する
+fn handle_clock_out_and_quit(app: &AppHandle, work: &WorkState) {
+    shutdown(app, work);
+    std::process::exit(0);
+}
+
+// "launch_at_login" メニュー項目の処理 (ログイン時の起動を切り替えて設定に保存する)
+fn handle_launch_at_login(app: &AppHandle) {
+    let state = app.state::<Mutex<Config>>();
+    let mut config = state.lock().unwrap();
+    config.launch_at_login = !config.launch_at_login;
+    if let Some(path) = config::config_path(app) {
+        if let Err(e) = secret::store(&config).save(&path) {
+            warn!("ログイン時の起動の設定を保存できませんでした: {}", e);
+        }
+    }
+    autostart::sync(config.launch_at_login);
+    drop(config);
+    refresh_launch_at_login(app);
+}
+
+// ログイン時の起動のチェックを実際の登録状態に合わせる
+fn refresh_launch_at_login(app: &AppHandle) {
+    let item = app.tray_handle().get_item("launch_at_login");
+    let _ = item.set_selected(autostart::is_enabled());
+}
+
+// "settings" メニュー項目の処理 (開いていれば前面に出す)
+fn handle_settings(app: &AppHandle) {
+    open_window(
+        app,
+        SETTINGS_WINDOW_LABEL,
+        "settings.html",
+        t(Text::Settings),
+        (360.0, 320.0),
+    );
+}
+
+// "totals" メニュー項目の処理 (開いていれば前面に出す)
+fn handle_totals(app: &AppHandle) {
+    open_window(
+        app,
+        TOTALS_WINDOW_LABEL,
+        "totals.html",
+        t(Text::Totals),
+        (420.0, 480.0),
+    );
+}
+
+// "today_events" メニュー項目の処理 (開いていれば前面に出す)
+fn handle_today_events(app: &AppHandle) {
+    open_window(
+        app,
+        EVENTS_WINDOW_LABEL,
+        "events.html",
+        t(Text::TodayEvents),
+        (420.0, 480.0),
+    );
+}
+
+// ウィンドウを開く (既に開いていれば前面に出す)
+fn open_window(app: &AppHandle, label: &str, url: &str, title: &str, size: (f64, f64)) {
+    if let Some(window) = app.get_window(label) {
+        let _ = window.show();
+        let _ = window.set_focus();
+        return;
+    }
+
+    let result = WindowBuilder::new(app, label, WindowUrl::App(url.into()))
+        .title(title)
+        .inner_size(size.0, size.1)
+        .resizable(false)
+        .focused(true)
+        .build();
+    if let Err(e) = result {
+        warn!("{}ウィンドウを開けませんでした: {}", title, e);
+    }
+}
+
+// 今週と今月の集計を集計ウィンドウに渡す
+#[tauri::command]
+fn get_totals(app: AppHandle) -> Result<report::Totals, String> {
+    let events = app
+        .state::<EventStore>()
+        .events()
+        .map_err(|e| format!("イベントログを読み込めません: {}", e))?;
+    let config = app.state::<Mutex<Config>>().lock().unwrap().clone();
+    let days = day_summaries(&app, &events);
+    let today = Local::now().date_naive();
+    let mut totals = report::totals(&days, today, config.week_start.weekday());
+    totals.streak =
+        daily_goal(&app).map(|goal| report::goal_streak(&days, goal, &config.schedule, today));
+    Ok(totals)
+}
+
+// 集計・書き出しに使う日ごとの集計 (設定に従って実働時間を丸める)
+fn day_summaries(app: &AppHandle, events: &[StoredEvent]) -> Vec<report::DaySummary> {
+    let mut days = report::daily_summaries(events);
+    let config = app.state::<Mutex<Config>>().lock().unwrap().clone();
+    if config.round_daily_total {
+        report::round_daily_totals(&mut days);
+    }
+    report::set_overtime(&mut days, &config.schedule);
+    days
+}
+
+// 設定されたパスでイベントのソケットを開く (空なら開かない)
+fn start_event_socket(app: &AppHandle) {
+    let path = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .event_socket
+        .clone();
+    if path.is_empty() {
+        return;
+    }
 
-            thread::sleep(Duration::from_secs(1));
+    match event_socket::Publisher::start(Path::new(&path)) {
+        Ok(publisher) => {
+            info!("勤務状態の変化を {} に送ります", path);
+            app.manage(publisher);
+        }
+        Err(e) => error!("イベントのソケットを開けませんでした ({}): {}", path, e),
+    }
+}
+
+// 設定されていれば、ダッシュボードなどから勤務状態を取得できるローカルの HTTP サーバーを始める
+fn start_status_server(app: &AppHandle) {
+    let port = app.state::<Mutex<Config>>().lock().unwrap().status_port;
+    if port == 0 {
+        return;
+    }
+
+    let app_clone = app.clone();
+    let result = status_server::start(port, move || {
+        let work = app_clone.state::<WorkState>();
+        let started_at = *work.started_at.lock().unwrap();
+        status_server::Status {
+            working: work.is_working.load(Ordering::Relaxed),
+            on_break: work.is_on_break.load(Ordering::Relaxed),
+            elapsed_secs: work.work_duration().as_secs(),
+            started_at,
         }
     });
+    if let Err(e) = result {
+        error!("勤務状態を公開できませんでした (ポート {}): {}", port, e);
+    }
+}
+
+// 現在の勤務状態をウィンドウに渡す
+#[tauri::command]
+fn get_status(app: AppHandle) -> WorkStatus {
+    work_status(&app, &app.state::<WorkState>())
+}
+
+// 業務の開始/終了を切り替える (トレイの "attendance" と同じ)
+#[tauri::command]
+fn toggle_work(app: AppHandle) -> WorkStatus {
+    let work = app.state::<WorkState>();
+    handle_attendance(&app, &work);
+    work_status(&app, &work)
+}
+
+// 休憩の開始/終了を切り替える (トレイの "break_time" と同じ)
+#[tauri::command]
+fn toggle_break(app: AppHandle) -> WorkStatus {
+    let work = app.state::<WorkState>();
+    handle_break_time(&app, &work);
+    work_status(&app, &work)
+}
+
+// 今日の実働・休憩・会議の合計をウィンドウに渡す
+#[tauri::command]
+fn get_today_summary(app: AppHandle) -> Result<report::TodaySummary, String> {
+    let (worked, break_time, meeting_time) =
+        today_totals(&app).map_err(|e| format!("実働時間を集計できません: {}", e))?;
+    Ok(report::TodaySummary {
+        date: Local::now().date_naive(),
+        worked: format_duration(worked),
+        break_time: format_duration(break_time),
+        meeting_time: format_duration(meeting_time),
+    })
+}
+
+// 今日のイベントを今日の記録ウィンドウに渡す
+#[tauri::command]
+fn get_today_events(app: AppHandle) -> Result<Vec<report::EventEntry>, String> {
+    Ok(report::event_entries(&today_events(&app)?))
 }
 
-// 経過時間を hh:mm:ss のフォーマットに整形
-fn format_duration(duration: Duration) -> String {
-    let hours = duration.as_secs() / 3600;
-    let minutes = (duration.as_secs() % 3600) / 60;
-    let seconds = duration.as_secs() % 60;
-    format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+// 今日の記録ウィンドウで修正したイベントの時刻を保存 (time は "HH:MM")
+#[tauri::command]
+fn update_event_time(app: AppHandle, id: i64, time: String) -> Result<(), String> {
+    let time = NaiveTime::parse_from_str(&time, "%H:%M")
+        .map_err(|_| format!("時刻の形式が正しくありません: {}", time))?;
+    let event = today_events(&app)?
+        .into_iter()
+        .find(|event| event.id == id)
+        .ok_or("イベントが見つかりません")?;
+    let timestamp = event
+        .timestamp
+        .date_naive()
+        .and_time(time)
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or("存在しない時刻です")?;
+
+    app.state::<EventStore>()
+        .update_timestamp(id, &timestamp, &Local::now())
+        .map_err(|e| format!("イベントを修正できません: {}", e))?;
+    info!(
+        "イベントの時刻を修正しました ({} -> {})",
+        event.timestamp, timestamp
+    );
+    Ok(())
 }
 
-#[tokio::main]
-async fn send_req(statu: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let data = [("name", "多田"), ("status", statu)];
-    let url = "https://script.google.com/macros/s/AKfycbz2UC1m0PPe_HVHDq0ieQc62AtVUdNSG7-10x4jEKP1iio_yo0Q3mJuSfUS3wXLwX2l0g/exec";
-    let response = reqwest::Client::new().post(url).form(&data).send().await?;
+// 今日の記録ウィンドウで選んだイベントを削除
+#[tauri::command]
+fn delete_event(app: AppHandle, id: i64) -> Result<(), String> {
+    let deleted = app
+        .state::<EventStore>()
+        .delete(id, &Local::now())
+        .map_err(|e| format!("イベントを削除できません: {}", e))?;
+    if !deleted {
+        return Err("イベントが見つかりません".to_string());
+    }
+    info!("イベントを削除しました ({})", id);
+    Ok(())
+}
 
-    // サーバーからのレスポンスを取得
-    let body = response.text().await?;
-    println!("Response: {}", body);
+// 今日のイベント
+fn today_events(app: &AppHandle) -> Result<Vec<store::StoredEvent>, String> {
+    app.state::<EventStore>()
+        .events_on(Local::now().date_naive())
+        .map_err(|e| format!("イベントログを読み込めません: {}", e))
+}
+
+// 現在の設定を設定ウィンドウに渡す (送信先 URL などの秘密情報は渡さない)
+#[tauri::command]
+fn get_config(app: AppHandle) -> secret::Redacted {
+    secret::redact(&app.state::<Mutex<Config>>().lock().unwrap())
+}
 
-    eprintln!("*** 終了 ***");
+// 設定ウィンドウで編集した設定を検証して保存し、そのまま反映する
+#[tauri::command]
+fn save_config(app: AppHandle, mut config: Config) -> Result<(), String> {
+    // 秘密情報は設定ウィンドウに渡していないので、空のままなら今の値を使う
+    secret::keep_unchanged(&mut config, &app.state::<Mutex<Config>>().lock().unwrap());
+    config.validate().map_err(|e| e.to_string())?;
+    config.endpoint().map_err(|e| e.to_string())?;
+    config.labels.validate().map_err(|e| e.to_string())?;
+    config.field_names.validate().map_err(|e| e.to_string())?;
+    sender::pinned_tls(&config)
+        .map_err(|e| format!("ピン留めする証明書を読み込めません: {}", e))?;
+    config.auto_clock_in_time().map_err(|e| e.to_string())?;
+    for hours in &config.quiet_hours {
+        hours.times().map_err(|e| e.to_string())?;
+    }
+    if !config.secondary_endpoint_url.is_empty() {
+        config
+            .parse_endpoint(&config.secondary_endpoint_url)
+            .map_err(|e| format!("予備の{}", e))?;
+    }
+    config
+        .batch_endpoint()
+        .map_err(|e| format!("まとめて送る{}", e))?;
+
+    // 送信先 URL などの秘密情報はキーチェーンに保存し、設定ファイルには書かない
+    let path = config::config_path(&app).ok_or("設定ファイルの場所がわかりません")?;
+    secret::store(&config)
+        .save(&path)
+        .map_err(|e| format!("設定ファイルを保存できません: {}", e))?;
+
+    // タイマーや送信処理は毎回この値を読むので再起動しなくても反映される
+    *app.state::<Mutex<Config>>().lock().unwrap() = config;
+    refresh_menu_labels(&app);
+    // 表示の設定を変えたときに、次の更新を待たずに反映する
+    tray::set_tooltip(&app, "");
+    refresh_title(&app, &app.state::<WorkState>());
+
+    if let Some(window) = app.get_window(SETTINGS_WINDOW_LABEL) {
+        let _ = window.close();
+    }
     Ok(())
 }
+
+// "export_csv" メニュー項目の処理
+fn handle_export_csv(app: &AppHandle) {
+    let app_clone = app.clone();
+    let file_name = format!("kintai-{}.csv", Local::now().format("%Y%m%d"));
+    FileDialogBuilder::new()
+        .set_file_name(&file_name)
+        .add_filter("CSV", &["csv"])
+        .save_file(move |path| {
+            // キャンセルされた場合は何もしない
+            let Some(path) = path else {
+                return;
+            };
+
+            let events = match app_clone.state::<EventStore>().events() {
+                Ok(events) => events,
+                Err(e) => {
+                    warn!("イベントログを読み込めませんでした: {}", e);
+                    return;
+                }
+            };
+            let days = day_summaries(&app_clone, &events);
+            match report::write_csv(&path, &days) {
+                Ok(()) => info!("CSV を書き出しました: {}", path.display()),
+                Err(e) => error!("CSV を書き出せませんでした: {}", e),
+            }
+        });
+}
+
+// "import_csv" メニュー項目の処理 (表計算ソフトなどで付けていた過去の記録を取り込む)
+// 取り込んだ記録は後から記録したことになるので、業務中は取り込まない
+fn handle_import_csv(app: &AppHandle, work: &WorkState) {
+    if work.is_working.load(Ordering::Relaxed) {
+        notify_as(app, Urgency::Reply, t(Text::ImportWhileWorking));
+        return;
+    }
+
+    let app_clone = app.clone();
+    FileDialogBuilder::new()
+        .add_filter("CSV", &["csv"])
+        .pick_file(move |path| {
+            // キャンセルされた場合は何もしない
+            let Some(path) = path else {
+                return;
+            };
+
+            let work = app_clone.state::<WorkState>();
+            let _guard = work.transition_lock.lock().unwrap();
+            if work.is_working.load(Ordering::Relaxed) {
+                notify_as(&app_clone, Urgency::Reply, t(Text::ImportWhileWorking));
+                return;
+            }
+
+            let parsed = match import::read_csv(&path) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    error!("CSV を読み込めませんでした: {}", e);
+                    return;
+                }
+            };
+            let imported = match app_clone.state::<EventStore>().import(&parsed.events) {
+                Ok(imported) => imported,
+                Err(e) => {
+                    error!("記録を取り込めませんでした: {}", e);
+                    return;
+                }
+            };
+            info!(
+                "{} から {} 件の記録を取り込みました (重複 {} 件, 読み取れない行 {} 件)",
+                path.display(),
+                imported,
+                parsed.events.len() - imported,
+                parsed.skipped
+            );
+            // 取り込んだ記録の方が後に記録されたことになるので、それより前の操作は取り消せないようにする
+            set_undo(&app_clone, &work, None);
+
+            let message = t_with(
+                Text::ImportDone,
+                &[
+                    &imported.to_string(),
+                    &(parsed.events.len() - imported).to_string(),
+                    &parsed.skipped.to_string(),
+                ],
+            );
+            dialog::message(None::<&tauri::Window>, "72時間", message);
+        });
+}
+
+// "export_json" メニュー項目の処理 (イベントログ全体をバックアップとして書き出す)
+fn handle_export_json(app: &AppHandle) {
+    let app_clone = app.clone();
+    let file_name = format!("kintai-{}.json", Local::now().format("%Y%m%d"));
+    FileDialogBuilder::new()
+        .set_file_name(&file_name)
+        .add_filter("JSON", &["json"])
+        .save_file(move |path| {
+            // キャンセルされた場合は何もしない
+            let Some(path) = path else {
+                return;
+            };
+
+            let backup = match app_clone.state::<EventStore>().backup() {
+                Ok(backup) => backup,
+                Err(e) => {
+                    warn!("イベントログを読み込めませんでした: {}", e);
+                    return;
+                }
+            };
+            match backup::write_json(&path, &backup) {
+                Ok(()) => info!(
+                    "{} 件のイベントを書き出しました: {}",
+                    backup.events.len(),
+                    path.display()
+                ),
+                Err(e) => error!("{}", e),
+            }
+        });
+}
+
+// "import_json" メニュー項目の処理 (書き出したバックアップで記録をすべて置き換える)
+// 業務中の記録も置き換わってしまうので、業務中は復元しない
+fn handle_import_json(app: &AppHandle, work: &WorkState) {
+    if work.is_working.load(Ordering::Relaxed) {
+        notify_as(app, Urgency::Reply, t(Text::ImportWhileWorking));
+        return;
+    }
+
+    let app_clone = app.clone();
+    FileDialogBuilder::new()
+        .add_filter("JSON", &["json"])
+        .pick_file(move |path| {
+            // キャンセルされた場合は何もしない
+            let Some(path) = path else {
+                return;
+            };
+
+            let backup = match backup::read_json(&path) {
+                Ok(backup) => backup,
+                Err(e) => {
+                    error!("{}", e);
+                    dialog::message(None::<&tauri::Window>, "72時間", e.to_string());
+                    return;
+                }
+            };
+
+            let message = t_with(Text::ImportJsonConfirm, &[&backup.events.len().to_string()]);
+            let app = app_clone.clone();
+            dialog::ask(None::<&tauri::Window>, "72時間", message, move |yes| {
+                if !yes {
+                    return;
+                }
+
+                let work = app.state::<WorkState>();
+                let _guard = work.transition_lock.lock().unwrap();
+                if work.is_working.load(Ordering::Relaxed) {
+                    notify_as(&app, Urgency::Reply, t(Text::ImportWhileWorking));
+                    return;
+                }
+                if let Err(e) = app.state::<EventStore>().restore(&backup) {
+                    error!("記録を復元できませんでした: {}", e);
+                    return;
+                }
+                info!(
+                    "{} から {} 件のイベントを復元しました",
+                    path.display(),
+                    backup.events.len()
+                );
+                // 取り消す操作の記録も置き換わったので、取り消せないようにする
+                set_undo(&app, &work, None);
+            });
+        });
+}
+
+// "export_month" メニュー項目の処理 (今月の勤務表を書き出す, 拡張子が xlsx なら Excel 形式)
+fn handle_export_month(app: &AppHandle) {
+    let app_clone = app.clone();
+    let today = Local::now().date_naive();
+    let file_name = format!("kintai-{}.csv", today.format("%Y%m"));
+    let dialog = FileDialogBuilder::new()
+        .set_file_name(&file_name)
+        .add_filter("CSV", &["csv"]);
+    #[cfg(feature = "xlsx")]
+    let dialog = dialog.add_filter("Excel", &["xlsx"]);
+    dialog.save_file(move |path| {
+        // キャンセルされた場合は何もしない
+        let Some(path) = path else {
+            return;
+        };
+
+        let events = match app_clone.state::<EventStore>().events() {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("イベントログを読み込めませんでした: {}", e);
+                return;
+            }
+        };
+        let rows = report::month_rows(&day_summaries(&app_clone, &events), today);
+
+        #[cfg(feature = "xlsx")]
+        if path.extension().is_some_and(|ext| ext == "xlsx") {
+            match report::write_month_xlsx(&path, &rows) {
+                Ok(()) => info!("勤務表を書き出しました: {}", path.display()),
+                Err(e) => error!("勤務表を書き出せませんでした: {}", e),
+            }
+            return;
+        }
+        match report::write_month_csv(&path, &rows) {
+            Ok(()) => info!("勤務表を書き出しました: {}", path.display()),
+            Err(e) => error!("勤務表を書き出せませんでした: {}", e),
+        }
+    });
+}
+
+// "weekly_report" メニュー項目の処理 (今週の週報をクリップボードにコピーする)
+fn handle_weekly_report(app: &AppHandle) {
+    let events = match app.state::<EventStore>().events() {
+        Ok(events) => events,
+        Err(e) => {
+            warn!("イベントログを読み込めませんでした: {}", e);
+            notify_as(
+                app,
+                Urgency::Reply,
+                &t_with(Text::WeeklyReportFailed, &[&e.to_string()]),
+            );
+            return;
+        }
+    };
+    let week_start = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .week_start
+        .weekday();
+    let today = Local::now().date_naive();
+    let text = report::weekly_report(&day_summaries(app, &events), today, week_start, today);
+
+    match app.clipboard_manager().write_text(text) {
+        Ok(()) => notify_as(app, Urgency::Reply, t(Text::WeeklyReportCopied)),
+        Err(e) => {
+            warn!("週報をクリップボードにコピーできませんでした: {}", e);
+            notify_as(
+                app,
+                Urgency::Reply,
+                &t_with(Text::WeeklyReportFailed, &[&e.to_string()]),
+            );
+        }
+    }
+}
+
+// 起動時にサーバーの業務の状態を取得し、ローカルの状態と食い違っていれば確認する
+// (未送信のイベントが残っている間はサーバーの状態が古いので突き合わせない)
+fn reconcile_with_server(app: &AppHandle, work: &WorkState) {
+    let config = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .with_active_profile();
+    if !config.reconcile_on_startup || config.dry_run || config.validate().is_err() {
+        return;
+    }
+    if !app.state::<Arc<EventQueue>>().pending().is_empty() {
+        info!("未送信のイベントがあるため、サーバーの状態との突き合わせをスキップしました");
+        return;
+    }
+
+    let (app, work) = (app.clone(), work.clone());
+    thread::spawn(move || {
+        let Some(sender) = app.try_state::<Sender>() else {
+            return;
+        };
+        let (url, proxy) = match (config.endpoint(), config.proxy()) {
+            (Ok(url), Ok(proxy)) => (url, proxy),
+            _ => return,
+        };
+        let proxy = proxy.and_then(|url| sender::proxy(&config, &url).ok());
+        let tls = match sender::pinned_tls(&config) {
+            Ok(tls) => tls,
+            Err(e) => {
+                warn!("ピン留めした証明書を読み込めません: {}", e);
+                return;
+            }
+        };
+        let server = match sender.fetch_status(
+            url.as_str(),
+            &config.name,
+            Duration::from_secs(config.timeout_secs),
+            proxy,
+            tls,
+        ) {
+            Ok(server) => server,
+            Err(e) => {
+                warn!("サーバーの業務の状態を取得できませんでした: {}", e);
+                return;
+            }
+        };
+
+        let local_open = work.is_working.load(Ordering::Relaxed);
+        match Reconciliation::new(local_open, server.open) {
+            Reconciliation::Agree => debug!("サーバーの業務の状態と一致しています"),
+            Reconciliation::LocalOpen => ask_resend_start(&app, &work),
+            Reconciliation::ServerOpen => {
+                let started_at = server.started_at.map(|time| time.with_timezone(&Local));
+                ask_resume_server_session(&app, &work, started_at);
+            }
+        }
+    });
+}
+
+// サーバーでは業務中のとき、その業務を再開するか今の時刻で業務終了を送るかを確認する
+fn ask_resume_server_session(
+    app: &AppHandle,
+    work: &WorkState,
+    started_at: Option<DateTime<Local>>,
+) {
+    let clock_24h = app.state::<Mutex<Config>>().lock().unwrap().clock_24h;
+    let time = started_at.map_or("--:--".to_string(), |time| format_clock(&time, clock_24h));
+    warn!("サーバーでは業務中です (業務開始: {})", time);
+
+    let (app, work) = (app.clone(), work.clone());
+    dialog::ask(
+        None::<&tauri::Window>,
+        "72時間",
+        t_with(Text::ServerSessionOpen, &[&time]),
+        move |resume| {
+            if !resume {
+                send_status(&app, EventKind::Work, EventAction::End, Local::now(), None);
+                return;
+            }
+
+            // 業務開始はサーバーに記録済みなので送らず、ローカルにだけ記録する
+            // (確認している間に業務を開始していたら何もしない)
+            let started_at = started_at.unwrap_or_else(Local::now);
+            let mut resumed = false;
+            transition(&app, &work, |state| {
+                if state.is_working {
+                    return Vec::new();
+                }
+                resumed = true;
+                state
+                    .toggle_work()
+                    .into_iter()
+                    .filter(|effect| !matches!(effect, Effect::Report(..) | Effect::Record(..)))
+                    .collect()
+            });
+            if !resumed {
+                return;
+            }
+            record_event(&app, EventKind::Work, EventAction::Start, started_at, None);
+            work.resume_from(elapsed_since(Some(started_at)));
+            set_started_at(&app, &work, Some(started_at));
+            save_state(&app, &work);
+        },
+    );
+}
+
+// ローカルでは業務中なのにサーバーに記録がないとき、業務開始を送り直すか確認する
+fn ask_resend_start(app: &AppHandle, work: &WorkState) {
+    let Some(started_at) = *work.started_at.lock().unwrap() else {
+        return;
+    };
+    let clock_24h = app.state::<Mutex<Config>>().lock().unwrap().clock_24h;
+    let time = format_clock(&started_at, clock_24h);
+    warn!(
+        "サーバーに業務開始が記録されていません (業務開始: {})",
+        time
+    );
+
+    let app = app.clone();
+    dialog::ask(
+        None::<&tauri::Window>,
+        "72時間",
+        t_with(Text::LocalSessionNotOnServer, &[&time]),
+        move |resend| {
+            if resend {
+                send_status(&app, EventKind::Work, EventAction::Start, started_at, None);
+            }
+        },
+    );
+}
+
+// 起動時に前回の勤務状態を復元
+fn restore_state(app: &AppHandle, work: &WorkState) {
+    let Some(path) = state::state_path(app) else {
+        return;
+    };
+    let state = match PersistedState::load(&path) {
+        Ok(Some(state)) => state,
+        Ok(None) => return,
+        // 書き込み中に落ちたなどで壊れていれば、元のファイルを残して業務中でない状態で起動する
+        Err(e) => {
+            warn!("勤務状態のファイルが壊れています: {}", e);
+            notify_corrupted(app, &path, Text::StateCorrupted);
+            return;
+        }
+    };
+    let Some(restored) = work.restore_persisted(&state) else {
+        return;
+    };
+    // 再起動した時刻ではなく最初に業務を開始した時刻を表示する
+    set_started_at(app, work, state.started_at);
+
+    // メニューとタイトルを業務中の表示に合わせる
+    sync_menu(app, &restored);
+    tray::set_icon(app, restored.icon());
+    refresh_title(app, work);
+
+    start_timer(app, work.clone());
+}
+
+// 設定ファイルを読み込む (読み込めない場合はデフォルト設定)
+fn load_config(app: &AppHandle) -> (Config, bool) {
+    let mut config = match config::config_path(app) {
+        Some(path) => Config::load(&path).unwrap_or_else(|e| {
+            warn!("{}", e);
+            // 壊れた設定ファイルは保存し直すときに上書きされるので、元のファイルを残しておく
+            if matches!(e, ConfigError::Parse(_)) {
+                // 言語の設定も読めていないので、デフォルトの設定と同じく OS の言語で通知する
+                i18n::init(None);
+                notify_corrupted(app, &path, Text::ConfigCorrupted);
+            }
+            Config::default()
+        }),
+        None => Config::default(),
+    };
+    secret::apply(&mut config);
+    // 送信先はデフォルトでは空なので、キーチェーンか設定ファイルで登録されるまで送信しない
+    let has_endpoint = !config.endpoint_url.trim().is_empty();
+    if config.dry_run {
+        warn!("dry_run が有効なので、送信する内容をログに出すだけで送信しません");
+    }
+    let active = config.with_active_profile();
+    if let Err(e) = active.validate() {
+        warn!("{}", e);
+    }
+    if !has_endpoint {
+        warn!("送信先 URL が設定されていません (設定するまで送信しません)");
+    } else if let Err(e) = active.endpoint() {
+        warn!("{} (送信を無効にします)", e);
+    }
+    if let Err(e) = config.proxy() {
+        warn!("{} (送信を保留します)", e);
+    }
+    if let Err(e) = config.labels.validate() {
+        warn!("{}", e);
+    }
+    if let Err(e) = config.field_names.validate() {
+        warn!("{} (デフォルトの項目名で送信します)", e);
+    }
+    if let Err(e) = sender::pinned_tls(&config) {
+        warn!(
+            "ピン留めする証明書を読み込めません: {} (送信を保留します)",
+            e
+        );
+    }
+    if !config.title_format.is_empty()
+        && format_title(&config.title_format, Duration::ZERO).is_none()
+    {
+        warn!(
+            "表示形式が不正です: {} (デフォルトの形式で表示します)",
+            config.title_format
+        );
+    }
+    (config, has_endpoint)
+}
+
+// イベントログを開く (開けない場合はメモリ上に記録する)
+fn open_store(app: &AppHandle) -> EventStore {
+    let opened = match store::store_path(app) {
+        Some(path) => EventStore::open(&path),
+        None => EventStore::open_in_memory(),
+    };
+    opened.unwrap_or_else(|e| {
+        error!("イベントログを開けません: {} (メモリ上に記録します)", e);
+        EventStore::open_in_memory().expect("error while opening in-memory event store")
+    })
+}
+
+// "open_data_dir" メニュー項目の処理
+// (状態・送信待ち・記録のファイルがあるフォルダと、ログのフォルダをファイルマネージャーで開く, なければ作る)
+fn handle_open_data_dir(app: &AppHandle) {
+    let resolver = app.path_resolver();
+    let mut dirs = Vec::new();
+    for dir in [resolver.app_data_dir(), resolver.app_log_dir()] {
+        let dir = dir
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "フォルダの場所がわかりません"))
+            .and_then(|dir| fs::create_dir_all(&dir).map(|()| dir));
+        match dir {
+            Ok(dir) if !dirs.contains(&dir) => dirs.push(dir),
+            Ok(_) => {}
+            Err(e) => {
+                warn!("データフォルダを作れませんでした: {}", e);
+                notify_as(
+                    app,
+                    Urgency::Reply,
+                    &t_with(Text::OpenDataDirFailed, &[&e.to_string()]),
+                );
+                return;
+            }
+        }
+    }
+
+    // 開き終わるまで待つので、メニューのスレッドでは開かない
+    let app = app.clone();
+    thread::spawn(move || {
+        for dir in dirs {
+            let opened = shell::open(&app.shell_scope(), dir.to_string_lossy(), None);
+            if let Err(e) = opened {
+                warn!(
+                    "データフォルダを開けませんでした ({}): {}",
+                    dir.display(),
+                    e
+                );
+                notify_as(
+                    &app,
+                    Urgency::Reply,
+                    &t_with(Text::OpenDataDirFailed, &[&e.to_string()]),
+                );
+            }
+        }
+    });
+}
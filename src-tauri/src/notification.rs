@@ -0,0 +1,385 @@
+use std::path::Path;
+use std::process;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveTime};
+use tauri::api::notification::Notification;
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::file;
+use crate::format::{format_duration, format_duration_text};
+use crate::i18n::{t_with, Text};
+use crate::queue::QueuedEvent;
+use crate::sender::SendError;
+use crate::store::today_totals;
+use crate::work::{SessionTimes, WorkState};
+
+// 送信に成功したことを続けて通知しない間隔 (秒)
+const SUCCESS_NOTIFY_INTERVAL_SECS: u64 = 10;
+
+// 通知の種類 (通知を表示しない時間帯に表示するかを決める)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    // 休憩の警告や目標の達成などのお知らせ
+    Normal,
+    // 送信の失敗など、気づかないと記録が失われるもの (設定すれば時間帯の間も表示する)
+    Critical,
+    // メニューの操作に対する応答 (操作した直後なので時間帯の間も表示する)
+    Reply,
+}
+
+// デスクトップ通知を表示 (通知を表示しない時間帯なら表示しない)
+pub fn notify(app: &AppHandle, body: &str) {
+    notify_as(app, Urgency::Normal, body);
+}
+
+// 種類を指定してデスクトップ通知を表示 (通知はすべてここを通す)
+// 通知を表示しない時間帯ならログに残すだけにする
+pub fn notify_as(app: &AppHandle, urgency: Urgency, body: &str) {
+    if quiet(app, urgency) {
+        info!("通知を表示しない時間帯のため通知しませんでした: {}", body);
+        return;
+    }
+    let identifier = app.config().tauri.bundle.identifier.clone();
+    if let Err(e) = Notification::new(identifier)
+        .title("72時間")
+        .body(body)
+        .show()
+    {
+        warn!("通知を表示できませんでした: {}", e);
+    }
+}
+
+// 今が通知を表示しない時間帯で、urgency の通知を表示しないか
+// 操作への返事は設定を読まずに表示する (設定をロックしたまま呼ばれることがある)
+pub fn quiet(app: &AppHandle, urgency: Urgency) -> bool {
+    if urgency == Urgency::Reply {
+        return false;
+    }
+    let config = app.state::<Mutex<Config>>();
+    let config = config.lock().unwrap();
+    quiet_at(&config, urgency, Local::now().time())
+}
+
+// 時刻 now が config の通知を表示しない時間帯で、urgency の通知を表示しないか
+fn quiet_at(config: &Config, urgency: Urgency, now: NaiveTime) -> bool {
+    let in_quiet_hours = || config.quiet_hours.iter().any(|hours| hours.contains(now));
+    match urgency {
+        Urgency::Normal => in_quiet_hours(),
+        Urgency::Critical => !config.critical_in_quiet_hours && in_quiet_hours(),
+        Urgency::Reply => false,
+    }
+}
+
+// 壊れていたファイルを複製して残し、デフォルトの内容で起動したことを通知する
+pub fn notify_corrupted(app: &AppHandle, path: &Path, text: Text) {
+    match file::back_up(path) {
+        Ok(backup) => {
+            info!("壊れていたファイルを {} に残しました", backup.display());
+            notify_as(
+                app,
+                Urgency::Critical,
+                &t_with(text, &[&backup.display().to_string()]),
+            );
+        }
+        Err(e) => warn!("壊れていたファイルを残せませんでした: {}", e),
+    }
+}
+
+// 業務終了時に今回の業務の集計を通知
+pub fn notify_session_summary(app: &AppHandle, work: &WorkState) {
+    let mut message = session_summary(&work.session_times());
+    // 今回の業務はもう記録しているので、今日の合計には今日の他の業務も含めて集計する
+    match today_totals(app) {
+        Ok((worked_today, break_today, _)) => {
+            message.push('\n');
+            message.push_str(&today_total(worked_today, break_today));
+        }
+        Err(e) => warn!("今日の実働時間を集計できませんでした: {}", e),
+    }
+    notify(app, &message);
+}
+
+// 今日の業務すべての実働時間と休憩時間 ("本日の勤務: 7時間45分 (休憩 1時間)")
+fn today_total(worked: Duration, break_time: Duration) -> String {
+    t_with(
+        Text::TodayTotal,
+        &[
+            &format_duration_text(worked),
+            &format_duration_text(break_time),
+        ],
+    )
+}
+
+// 今回の業務の内訳 (業務 - 休憩 - 会議 - 一時停止 = 実働)
+fn session_summary(times: &SessionTimes) -> String {
+    t_with(
+        Text::SessionSummary,
+        &[
+            &format_duration(times.total()),
+            &format_duration(times.break_time),
+            &format_duration(times.meeting_time),
+            &format_duration(times.hold_time),
+            &format_duration(times.worked),
+        ],
+    )
+}
+
+// 送信に成功したことを通知 (設定で有効にした種類の開始・終了だけ)
+// 再送がまとめて成功したときに通知が重ならないよう、短い間隔では通知しない
+pub fn notify_sent(app: &AppHandle, config: &Config, event: &QueuedEvent) {
+    static LAST_NOTIFIED: Mutex<Option<Instant>> = Mutex::new(None);
+
+    if !sent_notice_due(
+        config,
+        event,
+        &mut LAST_NOTIFIED.lock().unwrap(),
+        Instant::now(),
+    ) {
+        return;
+    }
+    notify(app, &t_with(Text::SendSucceeded, &[&event.status]));
+    if !quiet(app, Urgency::Normal) {
+        play_sound(&config.success_notification.sound);
+    }
+}
+
+// 送信に成功したことを今 (now) 通知するか (通知するなら last を now にする)
+// last は前回通知した時刻
+fn sent_notice_due(
+    config: &Config,
+    event: &QueuedEvent,
+    last: &mut Option<Instant>,
+    now: Instant,
+) -> bool {
+    let is_transition = matches!(event.action.as_deref(), Some("start" | "end"));
+    let enabled = event
+        .event_type
+        .as_deref()
+        .is_some_and(|event_type| config.success_notification.enabled(event_type));
+    if !is_transition || !enabled {
+        return false;
+    }
+
+    let interval = Duration::from_secs(SUCCESS_NOTIFY_INTERVAL_SECS);
+    if last.is_some_and(|at| now.saturating_duration_since(at) < interval) {
+        return false;
+    }
+    *last = Some(now);
+    true
+}
+
+// システムサウンドを鳴らす (鳴り終わるのは待たない, 空なら何もしない)
+#[cfg(target_os = "macos")]
+fn play_sound(name: &str) {
+    if name.is_empty() {
+        return;
+    }
+    let path = format!("/System/Library/Sounds/{}.aiff", name);
+    thread::spawn(move || {
+        if let Err(e) = process::Command::new("afplay").arg(&path).status() {
+            warn!("サウンドを再生できませんでした ({}): {}", path, e);
+        }
+    });
+}
+
+#[cfg(not(target_os = "macos"))]
+fn play_sound(_name: &str) {}
+
+// 送信に失敗したときの通知の文言
+pub fn send_failure_message(e: &SendError) -> String {
+    if e.is_retryable() {
+        t_with(Text::SendFailed, &[&e.to_string()])
+    } else {
+        // 再送しても受け付けられない見込みが高い (URL の設定を確認してもらう)
+        t_with(Text::SendRejected, &[&e.to_string()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{Local, TimeZone, Utc};
+
+    use crate::config::QuietHours;
+    use crate::store::{EventAction, EventKind, EventStore};
+
+    fn event(kind: EventKind, action: EventAction) -> QueuedEvent {
+        QueuedEvent::at(
+            "佐藤",
+            "業務 開始",
+            kind,
+            action,
+            "https://example.com/exec",
+            Utc.with_ymd_and_hms(2024, 4, 1, 9, 0, 0).unwrap(),
+            true,
+        )
+    }
+
+    fn notifying_work() -> Config {
+        let mut config = Config::default();
+        config.success_notification.work = true;
+        config
+    }
+
+    #[test]
+    fn success_is_notified_only_for_enabled_transitions() {
+        let config = notifying_work();
+        let now = Instant::now();
+        let start = event(EventKind::Work, EventAction::Start);
+        assert!(sent_notice_due(&config, &start, &mut None, now));
+        let breaks = event(EventKind::Break, EventAction::Start);
+        assert!(!sent_notice_due(&config, &breaks, &mut None, now));
+
+        // 業務中であることを知らせただけなら通知しない
+        let heartbeat = QueuedEvent::heartbeat(
+            "佐藤",
+            "https://example.com/exec",
+            Duration::from_secs(900),
+            Utc.with_ymd_and_hms(2024, 4, 1, 9, 15, 0).unwrap(),
+            true,
+        );
+        assert!(!sent_notice_due(&config, &heartbeat, &mut None, now));
+    }
+
+    #[test]
+    fn success_notifications_are_throttled() {
+        let config = notifying_work();
+        let start = event(EventKind::Work, EventAction::Start);
+        let end = event(EventKind::Work, EventAction::End);
+        let now = Instant::now();
+        let mut last = None;
+        assert!(sent_notice_due(&config, &start, &mut last, now));
+        // 再送がまとめて成功したときは続けて通知しない
+        assert!(!sent_notice_due(
+            &config,
+            &end,
+            &mut last,
+            now + Duration::from_secs(1)
+        ));
+        assert!(sent_notice_due(
+            &config,
+            &end,
+            &mut last,
+            now + Duration::from_secs(SUCCESS_NOTIFY_INTERVAL_SECS)
+        ));
+    }
+
+    #[test]
+    fn session_summary_subtracts_hold_as_well() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let times = SessionTimes {
+            worked: minutes(6 * 60),
+            break_time: minutes(60),
+            meeting_time: minutes(30),
+            hold_time: minutes(15),
+        };
+        assert_eq!(
+            session_summary(&times),
+            "お疲れさまでした\n業務 07:45:00 - 休憩 01:00:00 - 会議 00:30:00 - 一時停止 00:15:00 = 実働 06:00:00"
+        );
+    }
+
+    #[test]
+    fn today_total_sums_every_session_of_the_day() {
+        let store = EventStore::open_in_memory().unwrap();
+        let at = |h: u32, m: u32| Local.with_ymd_and_hms(2024, 4, 1, h, m, 0).unwrap();
+        for (kind, action, time) in [
+            // 午前: 3 時間 30 分のうち休憩 30 分
+            (EventKind::Work, EventAction::Start, at(9, 0)),
+            (EventKind::Break, EventAction::Start, at(11, 0)),
+            (EventKind::Break, EventAction::End, at(11, 30)),
+            (EventKind::Work, EventAction::End, at(12, 30)),
+            // 午後: 5 時間 15 分のうち休憩 30 分
+            (EventKind::Work, EventAction::Start, at(13, 30)),
+            (EventKind::Break, EventAction::Start, at(15, 0)),
+            (EventKind::Break, EventAction::End, at(15, 30)),
+            (EventKind::Work, EventAction::End, at(18, 45)),
+        ] {
+            store.record(kind, action, &time, None).unwrap();
+        }
+
+        let (worked, break_time, _) = store.totals_on(at(9, 0).date_naive()).unwrap();
+        assert_eq!(
+            today_total(worked, break_time),
+            "本日の勤務: 7時間45分 (休憩 1時間)"
+        );
+    }
+
+    #[test]
+    fn today_total_omits_zero_parts() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        assert_eq!(
+            today_total(minutes(8 * 60), minutes(45)),
+            "本日の勤務: 8時間 (休憩 45分)"
+        );
+        assert_eq!(
+            today_total(Duration::from_secs(30), Duration::ZERO),
+            "本日の勤務: 0分 (休憩 0分)"
+        );
+    }
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    // start から end まで通知を表示しない設定
+    fn quiet_config(hours: &[(&str, &str)]) -> Config {
+        Config {
+            quiet_hours: hours
+                .iter()
+                .map(|&(start, end)| QuietHours {
+                    start: start.to_string(),
+                    end: end.to_string(),
+                })
+                .collect(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn quiet_hours_wrap_past_midnight() {
+        let config = quiet_config(&[("22:00", "07:00")]);
+        for (now, quiet) in [
+            (time(21, 59), false),
+            (time(22, 0), true),
+            (time(23, 30), true),
+            (time(0, 0), true),
+            (time(6, 59), true),
+            (time(7, 0), false),
+            (time(12, 0), false),
+        ] {
+            assert_eq!(quiet_at(&config, Urgency::Normal, now), quiet, "{}", now);
+        }
+    }
+
+    #[test]
+    fn any_of_the_quiet_hours_suppresses() {
+        let config = quiet_config(&[("12:00", "13:00"), ("oops", "13:00"), ("18:00", "09:00")]);
+        assert!(quiet_at(&config, Urgency::Normal, time(12, 30)));
+        assert!(!quiet_at(&config, Urgency::Normal, time(13, 0)));
+        assert!(quiet_at(&config, Urgency::Normal, time(8, 0)));
+        assert!(!quiet_at(&config, Urgency::Normal, time(10, 0)));
+        // 時間帯がなければいつでも表示する
+        assert!(!quiet_at(&Config::default(), Urgency::Normal, time(23, 0)));
+    }
+
+    #[test]
+    fn critical_and_replies_can_bypass_quiet_hours() {
+        let mut config = quiet_config(&[("22:00", "07:00")]);
+        let night = time(23, 0);
+        assert!(quiet_at(&config, Urgency::Critical, night));
+        // メニューの操作への応答はいつでも表示する
+        assert!(!quiet_at(&config, Urgency::Reply, night));
+
+        // 設定すれば送信の失敗などは時間帯の間も表示する
+        config.critical_in_quiet_hours = true;
+        assert!(!quiet_at(&config, Urgency::Critical, night));
+        assert!(quiet_at(&config, Urgency::Normal, night));
+    }
+}
@@ -0,0 +1,651 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tokio::sync::oneshot;
+
+use crate::file;
+use crate::store::{EventAction, EventKind};
+
+// 未送信イベントを保存するファイルの名前 (アプリのデータディレクトリに置く)
+const QUEUE_FILE_NAME: &str = "queue.jsonl";
+
+// 業務中であることを知らせるときの status
+const HEARTBEAT_STATUS: &str = "業務 継続";
+
+// 送信待ちの勤怠イベント
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedEvent {
+    pub name: String,
+    pub status: String,
+    // 操作した時刻 (タイムゾーン付き)
+    pub timestamp: DateTime<FixedOffset>,
+    // 送信先 URL (操作したときのプロファイルのもの, 古いキューにはないので省略可)
+    // 送信先を設定する前に操作したものは None にして、設定した後の送信先に送る
+    #[serde(default)]
+    pub endpoint_url: Option<String>,
+    // イベントの種類 ("work" など) と開始か終了か ("start" または "end")
+    // (status は表示用の文言なので、サーバーではこちらで判別する)
+    #[serde(default)]
+    pub event_type: Option<String>,
+    #[serde(default)]
+    pub action: Option<String>,
+    // 業務の経過時間 (業務中であることを知らせるときだけ)
+    #[serde(default)]
+    pub elapsed_secs: Option<u64>,
+    // 予備の送信先に送る控えか (失敗しても送信の失敗として通知しない)
+    #[serde(default)]
+    pub secondary: bool,
+    // 業務開始のときに入力したメモ (空なら送らない)
+    #[serde(default)]
+    pub note: Option<String>,
+    // 操作したマシンのホスト名 (複数のマシンから記録する人のため, 設定で送らないこともできる)
+    #[serde(default)]
+    pub device: Option<String>,
+}
+
+impl QueuedEvent {
+    // 指定した時刻でイベントを作成 (utc が false ならローカルタイムゾーン)
+    pub fn at(
+        name: &str,
+        status: &str,
+        kind: EventKind,
+        action: EventAction,
+        endpoint_url: &str,
+        at: DateTime<Utc>,
+        utc: bool,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            status: status.to_string(),
+            timestamp: fixed_timestamp(at, utc),
+            endpoint_url: endpoint(endpoint_url),
+            event_type: Some(kind.as_str().to_string()),
+            action: Some(action.as_str().to_string()),
+            elapsed_secs: None,
+            secondary: false,
+            note: None,
+            device: None,
+        }
+    }
+
+    // メモを付ける (空白だけなら付けない)
+    pub fn with_note(self, note: Option<&str>) -> Self {
+        Self {
+            note: note
+                .map(str::trim)
+                .filter(|note| !note.is_empty())
+                .map(str::to_string),
+            ..self
+        }
+    }
+
+    // 業務中であることを知らせるイベントを作成 (elapsed は業務の経過時間)
+    pub fn heartbeat(
+        name: &str,
+        endpoint_url: &str,
+        elapsed: Duration,
+        at: DateTime<Utc>,
+        utc: bool,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            status: HEARTBEAT_STATUS.to_string(),
+            timestamp: fixed_timestamp(at, utc),
+            endpoint_url: endpoint(endpoint_url),
+            event_type: Some(EventKind::Work.as_str().to_string()),
+            action: Some("heartbeat".to_string()),
+            elapsed_secs: Some(elapsed.as_secs()),
+            secondary: false,
+            note: None,
+            device: None,
+        }
+    }
+
+    // 操作したマシンのホスト名を付ける (取得できなければ付けない)
+    pub fn with_device(self) -> Self {
+        Self {
+            device: device_name(),
+            ..self
+        }
+    }
+
+    // 予備の送信先に送る控え
+    pub fn to_secondary(&self, endpoint_url: &str) -> Self {
+        Self {
+            endpoint_url: Some(endpoint_url.to_string()),
+            secondary: true,
+            ..self.clone()
+        }
+    }
+
+    // このイベントと、予備の送信先が設定されていればその控え
+    pub fn with_secondary(self, secondary_endpoint_url: &str) -> Vec<Self> {
+        let secondary =
+            (!secondary_endpoint_url.is_empty()).then(|| self.to_secondary(secondary_endpoint_url));
+        std::iter::once(self).chain(secondary).collect()
+    }
+}
+
+// このマシンのホスト名 (取得できないか空なら None)
+fn device_name() -> Option<String> {
+    hostname::get()
+        .ok()
+        .map(|name| name.to_string_lossy().trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+// 記録する送信先 URL (設定されていなければ None)
+fn endpoint(endpoint_url: &str) -> Option<String> {
+    Some(endpoint_url.trim())
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+}
+
+// 送信する時刻 (utc が false ならローカルタイムゾーン)
+fn fixed_timestamp(at: DateTime<Utc>, utc: bool) -> DateTime<FixedOffset> {
+    if utc {
+        at.fixed_offset()
+    } else {
+        at.with_timezone(&Local).fixed_offset()
+    }
+}
+
+// ファイルに追記していく送信待ちキュー
+pub struct EventQueue {
+    path: PathBuf,
+    // ファイル操作の排他用
+    file_lock: Mutex<()>,
+    // 送信処理の排他用 (同じイベントを二重に送らないため)
+    flush_lock: Mutex<()>,
+    // 送信スレッドを起こすためのフラグ
+    wake_flag: Mutex<bool>,
+    wake: Condvar,
+    // 送信中のイベントと、その送信を取り消すためのチャンネル
+    sending: Mutex<Option<(QueuedEvent, oneshot::Sender<()>)>>,
+}
+
+impl EventQueue {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            file_lock: Mutex::new(()),
+            flush_lock: Mutex::new(()),
+            wake_flag: Mutex::new(false),
+            wake: Condvar::new(),
+            sending: Mutex::new(None),
+        }
+    }
+
+    // イベントを末尾に追加
+    // 送信中のイベントを打ち消すイベント (開始に対する終了など) なら、送信中のほうの送信を取り消す
+    pub fn push(&self, event: &QueuedEvent) -> io::Result<()> {
+        {
+            let _guard = self.file_lock.lock().unwrap();
+            if let Some(dir) = self.path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut file = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(&self.path)?;
+            // 追記の途中で落ちて最後の行が途切れていたら、その行とつながらないよう改行してから書く
+            if !ends_with_newline(&mut file)? {
+                writeln!(file)?;
+            }
+            writeln!(file, "{}", serde_json::to_string(event)?)?;
+        }
+
+        let mut sending = self.sending.lock().unwrap();
+        if sending
+            .as_ref()
+            .is_some_and(|(sending, _)| contradicts(sending, event))
+        {
+            if let Some((_, cancel)) = sending.take() {
+                let _ = cancel.send(());
+            }
+        }
+        Ok(())
+    }
+
+    // イベントの送信を始める (返り値は新しいイベントで送信が取り消されたときに受け取れる)
+    pub fn begin_send(&self, event: &QueuedEvent) -> oneshot::Receiver<()> {
+        let (cancel, cancelled) = oneshot::channel();
+        *self.sending.lock().unwrap() = Some((event.clone(), cancel));
+        cancelled
+    }
+
+    // イベントの送信が終わった
+    pub fn end_send(&self) {
+        self.sending.lock().unwrap().take();
+    }
+
+    // 送信待ちのイベントを古い順に取得
+    pub fn pending(&self) -> Vec<QueuedEvent> {
+        let _guard = self.file_lock.lock().unwrap();
+        read_events(&self.path)
+    }
+
+    // 指定したイベントを 1 つ取り除く (送信が確認できたときだけ呼ぶ)
+    pub fn remove(&self, event: &QueuedEvent) -> io::Result<()> {
+        let _guard = self.file_lock.lock().unwrap();
+        let mut events = read_events(&self.path);
+        if let Some(index) = events.iter().position(|queued| queued == event) {
+            events.remove(index);
+        }
+        write_events(&self.path, events.iter())
+    }
+
+    // window 以内に開始と終了を続けて操作したイベントを取り除く (取り除いた数を返す)
+    // (すぐに取り消された操作はサーバーに送らない)
+    pub fn coalesce(&self, window: Duration) -> io::Result<usize> {
+        let _guard = self.file_lock.lock().unwrap();
+        let events = read_events(&self.path);
+        let count = events.len();
+        let mut kept: Vec<QueuedEvent> = Vec::with_capacity(count);
+        for event in events {
+            // 送信先ごとに直前のイベントと比べる (予備の送信先の控えが間にはさまるため)
+            let previous = kept
+                .iter()
+                .rposition(|kept| kept.endpoint_url == event.endpoint_url);
+            match previous {
+                Some(index) if cancels_out(&kept[index], &event, window) => {
+                    kept.remove(index);
+                }
+                _ => kept.push(event),
+            }
+        }
+
+        let removed = count - kept.len();
+        if removed > 0 {
+            write_events(&self.path, kept.iter())?;
+        }
+        Ok(removed)
+    }
+
+    // 送信処理を始める (返り値を保持している間は他のスレッドは送信できない)
+    pub fn begin_flush(&self) -> MutexGuard<'_, ()> {
+        self.flush_lock.lock().unwrap()
+    }
+
+    // 送信スレッドを起こす
+    pub fn notify(&self) {
+        *self.wake_flag.lock().unwrap() = true;
+        self.wake.notify_one();
+    }
+
+    // 起こされるか timeout が経過するまで待つ (起こされたら true)
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let flag = self.wake_flag.lock().unwrap();
+        let (mut flag, _) = self
+            .wake
+            .wait_timeout_while(flag, timeout, |woken| !*woken)
+            .unwrap();
+        std::mem::replace(&mut *flag, false)
+    }
+}
+
+// キューファイルのパスを取得 (データディレクトリがなければ一時ディレクトリ)
+pub fn queue_path(app: &AppHandle) -> PathBuf {
+    app.path_resolver()
+        .app_data_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join(QUEUE_FILE_NAME)
+}
+
+// later が earlier を取り消す操作か (同じ種類・送信先で、開始と終了が window 以内に続いたもの)
+fn cancels_out(earlier: &QueuedEvent, later: &QueuedEvent, window: Duration) -> bool {
+    let within = (later.timestamp - earlier.timestamp)
+        .to_std()
+        .is_ok_and(|gap| gap <= window);
+    contradicts(earlier, later) && within
+}
+
+// later が同じ送信先への同じ種類の逆の操作 (開始に対する終了など) か
+fn contradicts(earlier: &QueuedEvent, later: &QueuedEvent) -> bool {
+    let opposite = matches!(
+        (earlier.action.as_deref(), later.action.as_deref()),
+        (Some("start"), Some("end")) | (Some("end"), Some("start"))
+    );
+    opposite
+        && earlier.event_type.is_some()
+        && earlier.event_type == later.event_type
+        && earlier.endpoint_url == later.endpoint_url
+}
+
+// イベントをファイルに書き込む (書き込み途中で落ちても壊れないよう一時ファイルから置き換える)
+fn write_events<'a>(path: &Path, events: impl Iterator<Item = &'a QueuedEvent>) -> io::Result<()> {
+    let mut content = String::new();
+    for event in events {
+        content.push_str(&serde_json::to_string(event)?);
+        content.push('\n');
+    }
+    file::write_atomic(path, &content)
+}
+
+// ファイルが空か改行で終わっているか
+fn ends_with_newline(file: &mut File) -> io::Result<bool> {
+    if file.metadata()?.len() == 0 {
+        return Ok(true);
+    }
+    let mut last = [0; 1];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+// ファイルからイベントを読み込む (読めない行は飛ばす)
+fn read_events(path: &Path) -> Vec<QueuedEvent> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    use chrono::TimeZone;
+
+    // テストごとに別のキューファイル
+    fn temp_queue(name: &str) -> (EventQueue, PathBuf) {
+        let path = env::temp_dir()
+            .join(format!("kintai-queue-{}-{}", name, std::process::id()))
+            .join(QUEUE_FILE_NAME);
+        let _ = fs::remove_file(&path);
+        (EventQueue::new(path.clone()), path)
+    }
+
+    // 2024-04-01 9:00 (UTC) から minute 分後のイベント
+    fn event(kind: EventKind, action: EventAction, minute: i64) -> QueuedEvent {
+        let at =
+            Utc.with_ymd_and_hms(2024, 4, 1, 9, 0, 0).unwrap() + chrono::Duration::minutes(minute);
+        QueuedEvent::at(
+            "佐藤",
+            "業務 開始",
+            kind,
+            action,
+            "https://example.com/exec",
+            at,
+            true,
+        )
+    }
+
+    #[test]
+    fn concurrent_push_and_remove_lose_nothing() {
+        let (queue, path) = temp_queue("stress");
+        let queue = Arc::new(queue);
+
+        let pushers: Vec<_> = (0..4)
+            .map(|i| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    for j in 0..50 {
+                        queue
+                            .push(&event(EventKind::Work, EventAction::Start, i * 100 + j))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        // 追加されている間に送信済みとして取り除いていく
+        let mut removed = Vec::new();
+        while removed.len() < 100 {
+            if let Some(first) = queue.pending().into_iter().next() {
+                queue.remove(&first).unwrap();
+                removed.push(first);
+            }
+        }
+        for pusher in pushers {
+            pusher.join().unwrap();
+        }
+
+        let mut all = queue.pending();
+        assert_eq!(all.len(), 100);
+        all.extend(removed);
+        all.sort_by_key(|event| event.timestamp);
+        all.dedup();
+        assert_eq!(all.len(), 200);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn torn_last_line_does_not_swallow_next_event() {
+        let (queue, path) = temp_queue("torn");
+        let first = event(EventKind::Work, EventAction::Start, 0);
+        queue.push(&first).unwrap();
+
+        // 追記の途中で落ちた状態を再現する
+        let line = serde_json::to_string(&event(EventKind::Break, EventAction::Start, 5)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{}", &line[..line.len() / 2]).unwrap();
+        drop(file);
+
+        // 起動し直したキューでも残っているイベントを読み込み、その後のイベントも失わない
+        let queue = EventQueue::new(path.clone());
+        let next = event(EventKind::Work, EventAction::End, 10);
+        queue.push(&next).unwrap();
+        assert_eq!(queue.pending(), vec![first, next]);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn leftover_temp_file_does_not_replace_queue() {
+        let (queue, path) = temp_queue("leftover");
+        let first = event(EventKind::Work, EventAction::Start, 0);
+        let second = event(EventKind::Work, EventAction::End, 60);
+        queue.push(&first).unwrap();
+        queue.push(&second).unwrap();
+
+        // 書き換えの途中で落ちて一時ファイルだけが残った状態を再現する
+        let tmp_path = path.with_file_name(format!("{}.tmp", QUEUE_FILE_NAME));
+        fs::write(&tmp_path, "{\"name\":").unwrap();
+
+        let queue = EventQueue::new(path.clone());
+        assert_eq!(queue.pending(), vec![first.clone(), second.clone()]);
+        queue.remove(&first).unwrap();
+        assert_eq!(queue.pending(), vec![second]);
+        assert!(!tmp_path.exists());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn events_wait_in_the_file_until_next_launch() {
+        // 送信の準備ができなかった起動ではキューに貯めるだけにする
+        let (queue, path) = temp_queue("unsent");
+        let events = [
+            event(EventKind::Work, EventAction::Start, 0),
+            event(EventKind::Work, EventAction::End, 480),
+        ];
+        for event in &events {
+            queue.push(event).unwrap();
+        }
+        drop(queue);
+
+        let next_launch = EventQueue::new(path.clone());
+        assert_eq!(next_launch.pending(), events);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn rapid_toggles_coalesce_to_the_minimal_sends() {
+        let (queue, path) = temp_queue("coalesce");
+        // 開始して、100 ミリ秒ごとに終了・開始・終了・開始と切り替えた
+        let flips: Vec<_> = [
+            EventAction::Start,
+            EventAction::End,
+            EventAction::Start,
+            EventAction::End,
+            EventAction::Start,
+        ]
+        .into_iter()
+        .zip(0..)
+        .map(|(action, i)| {
+            let mut flip = event(EventKind::Work, action, 0);
+            flip.timestamp += chrono::Duration::milliseconds(100 * i);
+            flip
+        })
+        .collect();
+        for flip in &flips {
+            queue.push(flip).unwrap();
+        }
+
+        // 最後の開始だけを送る
+        assert_eq!(queue.coalesce(Duration::from_millis(500)).unwrap(), 4);
+        assert_eq!(queue.pending(), vec![flips[4].clone()]);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn stable_transitions_are_not_coalesced() {
+        let (queue, path) = temp_queue("stable");
+        let events = vec![
+            event(EventKind::Work, EventAction::Start, 0),
+            event(EventKind::Work, EventAction::End, 1),
+            // 種類が違えば打ち消し合わない
+            event(EventKind::Break, EventAction::Start, 1),
+            event(EventKind::Work, EventAction::Start, 1),
+        ];
+        for event in &events {
+            queue.push(event).unwrap();
+        }
+        assert_eq!(queue.coalesce(Duration::from_millis(500)).unwrap(), 0);
+        assert_eq!(queue.pending(), events);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn coalesce_compares_events_per_endpoint() {
+        let (queue, path) = temp_queue("endpoints");
+        let start = event(EventKind::Work, EventAction::Start, 0);
+        let end = event(EventKind::Work, EventAction::End, 0);
+        // 予備の送信先の控えが間にはさまっても、同じ送信先どうしで打ち消す
+        for event in [
+            start.clone(),
+            start.to_secondary("https://example.com/backup"),
+            end.clone(),
+            end.to_secondary("https://example.com/backup"),
+        ] {
+            queue.push(&event).unwrap();
+        }
+        assert_eq!(queue.coalesce(Duration::from_millis(500)).unwrap(), 4);
+        assert!(queue.pending().is_empty());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn device_is_the_host_name_and_kept_in_the_queue() {
+        let (queue, path) = temp_queue("device");
+        let event = event(EventKind::Work, EventAction::Start, 0).with_device();
+        let host = hostname::get()
+            .unwrap()
+            .to_string_lossy()
+            .trim()
+            .to_string();
+        assert_eq!(event.device.as_deref(), Some(host.as_str()));
+
+        // 予備の送信先に送る控えにも付ける
+        let copies = event
+            .clone()
+            .with_secondary("https://backup.example.com/exec");
+        assert!(copies.iter().all(|copy| copy.device == event.device));
+
+        queue.push(&event).unwrap();
+        assert_eq!(queue.pending(), [event]);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn events_queued_without_a_device_still_load() {
+        let (queue, path) = temp_queue("no-device");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        // device を付ける前のバージョンで保存したイベント
+        fs::write(
+            &path,
+            "{\"name\":\"佐藤\",\"status\":\"業務 開始\",\"timestamp\":\"2024-04-01T09:00:00+00:00\"}\n",
+        )
+        .unwrap();
+        let pending = queue.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].device, None);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn only_the_opposite_action_contradicts() {
+        let start = event(EventKind::Break, EventAction::Start, 0);
+        let end = event(EventKind::Break, EventAction::End, 1);
+        assert!(contradicts(&start, &end));
+        assert!(contradicts(&end, &start));
+
+        // 同じ操作・別の種類・別の送信先は打ち消さない
+        assert!(!contradicts(
+            &start,
+            &event(EventKind::Break, EventAction::Start, 1)
+        ));
+        assert!(!contradicts(
+            &start,
+            &event(EventKind::Meeting, EventAction::End, 1)
+        ));
+        let elsewhere = QueuedEvent {
+            endpoint_url: Some("https://backup.example.com/exec".to_string()),
+            ..end.clone()
+        };
+        assert!(!contradicts(&start, &elsewhere));
+        // 業務中であることを知らせるイベントや、種類のない古いイベントも打ち消さない
+        let heartbeat = QueuedEvent::heartbeat(
+            "佐藤",
+            "https://example.com/exec",
+            Duration::from_secs(60),
+            Utc::now(),
+            true,
+        );
+        assert!(!contradicts(&start, &heartbeat));
+        let untyped = |event: &QueuedEvent| QueuedEvent {
+            event_type: None,
+            ..event.clone()
+        };
+        assert!(!contradicts(&untyped(&start), &untyped(&end)));
+    }
+
+    #[test]
+    fn push_cancels_only_a_contradicted_send() {
+        let (queue, path) = temp_queue("cancel");
+        let start = event(EventKind::Work, EventAction::Start, 0);
+
+        let mut cancelled = queue.begin_send(&start);
+        queue
+            .push(&event(EventKind::Break, EventAction::Start, 1))
+            .unwrap();
+        assert!(cancelled.try_recv().is_err());
+
+        queue
+            .push(&event(EventKind::Work, EventAction::End, 2))
+            .unwrap();
+        assert!(cancelled.try_recv().is_ok());
+
+        // 送信が終わった後のイベントは何も取り消さない
+        let mut cancelled = queue.begin_send(&start);
+        queue.end_send();
+        queue
+            .push(&event(EventKind::Work, EventAction::End, 3))
+            .unwrap();
+        assert!(cancelled.try_recv().is_err());
+        let _ = fs::remove_file(path);
+    }
+}
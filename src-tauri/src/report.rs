@@ -0,0 +1,1096 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveTime, Weekday};
+use serde::Serialize;
+
+use crate::config::Schedule;
+use crate::format::format_duration;
+use crate::store::{status_text, EventAction, EventKind, StoredEvent};
+
+// 1 日分の勤務の集計
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaySummary {
+    pub date: NaiveDate,
+    // その日の最初の業務開始
+    pub start: DateTime<Local>,
+    // その日の最後の業務終了 (日をまたぐ場合は翌日 0:00)
+    pub end: DateTime<Local>,
+    // 休憩の合計
+    pub break_time: Duration,
+    // 会議の合計
+    pub meeting_time: Duration,
+    // 休憩と会議を除いた実働時間
+    pub worked: Duration,
+    // 休憩をはさまずに続けて働いた最長の時間
+    pub longest_stretch: Duration,
+    // 所定労働時間を超えて働いた時間 (set_overtime で計算する)
+    pub overtime: Duration,
+}
+
+// 期間内の日ごとの実働時間と合計 (集計ウィンドウに渡す)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PeriodSummary {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    // 業務の記録がある日だけ
+    pub days: Vec<DayWorked>,
+    pub worked: String,
+}
+
+// 1 日分の実働時間
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DayWorked {
+    pub date: NaiveDate,
+    pub worked: String,
+}
+
+// 今週と今月の集計
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Totals {
+    pub week: PeriodSummary,
+    pub month: PeriodSummary,
+    // 目標勤務時間を続けて達成している日数 (目標がなければ None, goal_streak で計算する)
+    pub streak: Option<u32>,
+}
+
+// 今日の合計 (ウィンドウに渡す)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TodaySummary {
+    pub date: NaiveDate,
+    pub worked: String,
+    pub break_time: String,
+    pub meeting_time: String,
+}
+
+// 1 件のイベント (今日の記録ウィンドウに渡す)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EventEntry {
+    pub id: i64,
+    pub label: String,
+    // "09:12" の形式
+    pub time: String,
+    // 終了イベントなら対応する開始からの長さ
+    pub duration: Option<String>,
+}
+
+// 1 日の実働時間を丸める単位 (秒)
+const QUARTER_HOUR_SECS: u64 = 15 * 60;
+
+// 月次レポートの 1 行 (日付, 出勤, 退勤, 休憩, 実働, 残業)
+pub type MonthRow = [String; 6];
+
+// 月次レポートの見出し
+const MONTH_HEADER: [&str; 6] = ["日付", "出勤", "退勤", "休憩", "実働", "残業"];
+
+// 週報に表示する曜日 (月曜日から)
+const WEEKDAY_NAMES: [&str; 7] = ["月", "火", "水", "木", "金", "土", "日"];
+
+// 日ごとの集計途中の値
+struct DayTotal {
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    work: Duration,
+    break_time: Duration,
+    meeting_time: Duration,
+    // 一時停止の合計 (実働時間から除くだけで表示はしない)
+    hold_time: Duration,
+    longest_stretch: Duration,
+}
+
+// イベントを日ごとに集計 (日をまたぐ業務は 0:00 で分割する)
+pub fn daily_summaries(events: &[StoredEvent]) -> Vec<DaySummary> {
+    let mut days: BTreeMap<NaiveDate, DayTotal> = BTreeMap::new();
+
+    let work = intervals(events, EventKind::Work);
+    for &(start, end) in &work {
+        for (date, start, end) in split_by_day(start, end) {
+            let length = (end - start).to_std().unwrap_or_default();
+            let day = days.entry(date).or_insert(DayTotal {
+                start,
+                end,
+                work: Duration::ZERO,
+                break_time: Duration::ZERO,
+                meeting_time: Duration::ZERO,
+                hold_time: Duration::ZERO,
+                longest_stretch: Duration::ZERO,
+            });
+            day.start = day.start.min(start);
+            day.end = day.end.max(end);
+            day.work += length;
+        }
+    }
+
+    for kind in [EventKind::Break, EventKind::Meeting, EventKind::Hold] {
+        for (start, end) in intervals(events, kind) {
+            for (date, start, end) in split_by_day(start, end) {
+                // 業務の記録がない日の休憩・会議・一時停止は数えない
+                let Some(day) = days.get_mut(&date) else {
+                    continue;
+                };
+                let length = (end - start).to_std().unwrap_or_default();
+                match kind {
+                    EventKind::Meeting => day.meeting_time += length,
+                    EventKind::Hold => day.hold_time += length,
+                    _ => day.break_time += length,
+                }
+            }
+        }
+    }
+
+    for (start, end) in stretches(&work, &intervals(events, EventKind::Break)) {
+        for (date, start, end) in split_by_day(start, end) {
+            if let Some(day) = days.get_mut(&date) {
+                let length = (end - start).to_std().unwrap_or_default();
+                day.longest_stretch = day.longest_stretch.max(length);
+            }
+        }
+    }
+
+    days.into_iter()
+        .map(|(date, day)| DaySummary {
+            date,
+            start: day.start,
+            end: day.end,
+            break_time: day.break_time,
+            meeting_time: day.meeting_time,
+            worked: day
+                .work
+                .saturating_sub(day.break_time)
+                .saturating_sub(day.meeting_time)
+                .saturating_sub(day.hold_time),
+            longest_stretch: day.longest_stretch,
+            overtime: Duration::ZERO,
+        })
+        .collect()
+}
+
+// 日ごとの残業 (実働時間のうち曜日の所定労働時間を超えた分) を計算する
+// 実働時間を丸める場合は丸めた後に呼ぶ
+pub fn set_overtime(days: &mut [DaySummary], schedule: &Schedule) {
+    for day in days {
+        day.overtime = day
+            .worked
+            .saturating_sub(schedule.expected(day.date.weekday()));
+    }
+}
+
+// 実働時間を近いほうの 15 分単位に丸める (ちょうど 7 分 30 秒は切り上げ)
+pub fn round_quarter_hour(duration: Duration) -> Duration {
+    let quarters = (duration.as_secs() + QUARTER_HOUR_SECS / 2) / QUARTER_HOUR_SECS;
+    Duration::from_secs(quarters * QUARTER_HOUR_SECS)
+}
+
+// 日ごとの実働時間を 15 分単位に丸める (集計・書き出し用, 期間の合計は丸めた値の合計になる)
+pub fn round_daily_totals(days: &mut [DaySummary]) {
+    for day in days {
+        day.worked = round_quarter_hour(day.worked);
+    }
+}
+
+// 日ごとの集計を CSV に書き出す
+pub fn write_csv(path: &Path, days: &[DaySummary]) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record([
+        "日付",
+        "開始",
+        "終了",
+        "休憩",
+        "会議",
+        "実働",
+        "最長連続",
+        "残業",
+    ])?;
+    for day in days {
+        writer.write_record([
+            day.date.format("%Y/%m/%d").to_string(),
+            day.start.format("%H:%M").to_string(),
+            format_end_time(day),
+            format_duration(day.break_time),
+            format_duration(day.meeting_time),
+            format_duration(day.worked),
+            format_duration(day.longest_stretch),
+            format_duration(day.overtime),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// month を含む月の月次レポートの行 (業務の記録がない日は日付だけ, 最後に合計の行)
+// 1 日に複数回業務した場合は最初の業務開始と最後の業務終了を出勤・退勤とする
+pub fn month_rows(days: &[DaySummary], month: NaiveDate) -> Vec<MonthRow> {
+    let (from, to) = month_range(month);
+    let mut rows = Vec::new();
+    let mut break_total = Duration::ZERO;
+    let mut worked_total = Duration::ZERO;
+    let mut overtime_total = Duration::ZERO;
+    for date in from.iter_days().take_while(|date| *date <= to) {
+        let date_text = date.format("%Y/%m/%d").to_string();
+        let Some(day) = days.iter().find(|day| day.date == date) else {
+            rows.push([
+                date_text,
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+            ]);
+            continue;
+        };
+        break_total += day.break_time;
+        worked_total += day.worked;
+        overtime_total += day.overtime;
+        rows.push([
+            date_text,
+            day.start.format("%H:%M").to_string(),
+            format_end_time(day),
+            format_duration(day.break_time),
+            format_duration(day.worked),
+            format_duration(day.overtime),
+        ]);
+    }
+    rows.push([
+        "合計".to_string(),
+        String::new(),
+        String::new(),
+        format_duration(break_total),
+        format_duration(worked_total),
+        format_duration(overtime_total),
+    ]);
+    rows
+}
+
+// week_of を含む週 (week_start の曜日から始まる) の週報 (メールに貼り付ける文面)
+// 週の途中なら today までの日だけを並べ、合計もそこまでの分にする
+pub fn weekly_report(
+    days: &[DaySummary],
+    week_of: NaiveDate,
+    week_start: Weekday,
+    today: NaiveDate,
+) -> String {
+    let (from, to) = week_range(week_of, week_start);
+    let last = to.min(today);
+    let mut title = format!(
+        "週報 {} 〜 {}",
+        format_report_date(from),
+        format_report_date(to)
+    );
+    if last < to {
+        title.push_str(&format!(" ({} まで)", format_report_date(last)));
+    }
+
+    let mut lines = vec![title, String::new()];
+    let mut worked_total = Duration::ZERO;
+    let mut break_total = Duration::ZERO;
+    let mut worked_days = 0;
+    for date in from.iter_days().take_while(|date| *date <= last) {
+        let Some(day) = days.iter().find(|day| day.date == date) else {
+            lines.push(format!("{}  -", format_report_date(date)));
+            continue;
+        };
+        worked_total += day.worked;
+        break_total += day.break_time;
+        worked_days += 1;
+        lines.push(format!(
+            "{}  {}〜{}  実働 {}  休憩 {}",
+            format_report_date(date),
+            day.start.format("%H:%M"),
+            format_end_time(day),
+            format_duration(day.worked),
+            format_duration(day.break_time),
+        ));
+    }
+    lines.push(String::new());
+    lines.push(format!(
+        "合計 {} ({} 日, 休憩 {})",
+        format_duration(worked_total),
+        worked_days,
+        format_duration(break_total),
+    ));
+    lines.join("\n")
+}
+
+// 週報の日付 ("04/01 (月)" の形式)
+fn format_report_date(date: NaiveDate) -> String {
+    format!(
+        "{} ({})",
+        date.format("%m/%d"),
+        WEEKDAY_NAMES[date.weekday().num_days_from_monday() as usize]
+    )
+}
+
+// 月次レポートを CSV に書き出す
+pub fn write_month_csv(path: &Path, rows: &[MonthRow]) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(MONTH_HEADER)?;
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// 月次レポートを Excel (xlsx) に書き出す
+#[cfg(feature = "xlsx")]
+pub fn write_month_xlsx(path: &Path, rows: &[MonthRow]) -> Result<(), rust_xlsxwriter::XlsxError> {
+    let mut workbook = rust_xlsxwriter::Workbook::new();
+    let sheet = workbook.add_worksheet();
+    for (col, title) in MONTH_HEADER.iter().enumerate() {
+        sheet.write_string(0, col as u16, *title)?;
+    }
+    for (row, values) in rows.iter().enumerate() {
+        for (col, value) in values.iter().enumerate() {
+            sheet.write_string(row as u32 + 1, col as u16, value)?;
+        }
+    }
+    workbook.save(path)
+}
+
+// イベントを今日の記録ウィンドウに表示する形に変換
+pub fn event_entries(events: &[StoredEvent]) -> Vec<EventEntry> {
+    events
+        .iter()
+        .map(|event| EventEntry {
+            id: event.id,
+            label: match &event.note {
+                Some(note) => format!("{} ({})", status_text(event.kind, event.action), note),
+                None => status_text(event.kind, event.action).to_string(),
+            },
+            time: event.timestamp.format("%H:%M").to_string(),
+            duration: event.duration.map(format_duration),
+        })
+        .collect()
+}
+
+// today を含む週と月の集計 (週は week_start の曜日から始まる)
+pub fn totals(days: &[DaySummary], today: NaiveDate, week_start: Weekday) -> Totals {
+    let (week_from, week_to) = week_range(today, week_start);
+    let (month_from, month_to) = month_range(today);
+    Totals {
+        week: period_summary(days, week_from, week_to),
+        month: period_summary(days, month_from, month_to),
+        streak: None,
+    }
+}
+
+// today までに目標勤務時間 goal を続けて達成している日数 (今日がまだ未達なら昨日までで数える)
+// 所定労働時間のない曜日 (休日) は、達成していれば数え、達成していなくても途切れたことにしない
+pub fn goal_streak(
+    days: &[DaySummary],
+    goal: Duration,
+    schedule: &Schedule,
+    today: NaiveDate,
+) -> u32 {
+    let Some(first) = days.iter().map(|day| day.date).min() else {
+        return 0;
+    };
+    let met: BTreeMap<NaiveDate, bool> = days
+        .iter()
+        .map(|day| (day.date, day.worked >= goal))
+        .collect();
+
+    let mut streak = 0;
+    let mut date = today;
+    // 記録のある最初の日より前は数えない (休日しかない設定でも終わるように)
+    while date >= first {
+        if met.get(&date).copied().unwrap_or(false) {
+            streak += 1;
+        } else if date != today && !schedule.expected(date.weekday()).is_zero() {
+            break;
+        }
+        let Some(previous) = date.pred_opt() else {
+            break;
+        };
+        date = previous;
+    }
+    streak
+}
+
+// from から to まで (両端を含む) の集計
+fn period_summary(days: &[DaySummary], from: NaiveDate, to: NaiveDate) -> PeriodSummary {
+    let days: Vec<&DaySummary> = days
+        .iter()
+        .filter(|day| from <= day.date && day.date <= to)
+        .collect();
+    let worked = days.iter().map(|day| day.worked).sum();
+    PeriodSummary {
+        from,
+        to,
+        days: days
+            .iter()
+            .map(|day| DayWorked {
+                date: day.date,
+                worked: format_duration(day.worked),
+            })
+            .collect(),
+        worked: format_duration(worked),
+    }
+}
+
+// today を含む週の最初と最後の日
+fn week_range(today: NaiveDate, week_start: Weekday) -> (NaiveDate, NaiveDate) {
+    let offset =
+        (7 + today.weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+    let from = today - Days::new(offset as u64);
+    (from, from + Days::new(6))
+}
+
+// today を含む月の最初と最後の日
+fn month_range(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let from = today.with_day(1).unwrap_or(today);
+    let to = from
+        .checked_add_months(chrono::Months::new(1))
+        .and_then(|next_month| next_month.pred_opt())
+        .unwrap_or(today);
+    (from, to)
+}
+
+// その日の 0:00
+pub fn start_of_day(date: NaiveDate) -> Option<DateTime<Local>> {
+    date.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+}
+
+// 終了時刻を整形 (日をまたいだ場合は 24:00 と表示する)
+fn format_end_time(day: &DaySummary) -> String {
+    if day.end.date_naive() > day.date {
+        "24:00".to_string()
+    } else {
+        day.end.format("%H:%M").to_string()
+    }
+}
+
+// 開始と終了の組を取り出す (終わっていないものは含めない)
+fn intervals(events: &[StoredEvent], kind: EventKind) -> Vec<(DateTime<Local>, DateTime<Local>)> {
+    let mut result = Vec::new();
+    let mut started = None;
+    for event in events.iter().filter(|e| e.kind == kind) {
+        match event.action {
+            EventAction::Start => started = Some(event.timestamp),
+            EventAction::End => {
+                if let Some(start) = started.take() {
+                    result.push((start, event.timestamp));
+                }
+            }
+        }
+    }
+    result
+}
+
+// 休憩をはさまずに続けて働いた区間 (業務の区間を休憩の区間で区切る, どちらも時刻順)
+fn stretches(
+    work: &[(DateTime<Local>, DateTime<Local>)],
+    breaks: &[(DateTime<Local>, DateTime<Local>)],
+) -> Vec<(DateTime<Local>, DateTime<Local>)> {
+    let mut result = Vec::new();
+    for &(start, end) in work {
+        let mut from = start;
+        for &(break_start, break_end) in breaks {
+            if break_end <= from || break_start >= end {
+                continue;
+            }
+            if break_start > from {
+                result.push((from, break_start));
+            }
+            from = break_end;
+        }
+        if end > from {
+            result.push((from, end));
+        }
+    }
+    result
+}
+
+// 期間を日付ごとに分割
+fn split_by_day(
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> Vec<(NaiveDate, DateTime<Local>, DateTime<Local>)> {
+    let mut result = Vec::new();
+    let mut current = start;
+    while current < end {
+        let date = current.date_naive();
+        let Some(next_day) = date
+            .succ_opt()
+            .and_then(start_of_day)
+            .filter(|next_day| *next_day > current)
+        else {
+            break;
+        };
+        let segment_end = end.min(next_day);
+        result.push((date, current, segment_end));
+        current = segment_end;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+    use EventAction::{End, Start};
+    use EventKind::{Break, Meeting, Work};
+
+    // 2024 年 4 月 day 日 h 時 m 分 (ローカル時刻)
+    fn local(day: u32, h: u32, m: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 4, day, h, m, 0).unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 4, day).unwrap()
+    }
+
+    fn minutes(m: u64) -> Duration {
+        Duration::from_secs(m * 60)
+    }
+
+    // 時刻順の (種類, 開始か終了か, 時刻) からイベントを作る
+    fn events(list: &[(EventKind, EventAction, DateTime<Local>)]) -> Vec<StoredEvent> {
+        list.iter()
+            .enumerate()
+            .map(|(i, &(kind, action, timestamp))| StoredEvent {
+                id: i as i64 + 1,
+                kind,
+                action,
+                timestamp,
+                duration: None,
+                note: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn multiple_sessions_are_aggregated_into_one_day() {
+        let days = daily_summaries(&events(&[
+            (Work, Start, local(1, 9, 0)),
+            (Break, Start, local(1, 12, 0)),
+            (Break, End, local(1, 13, 0)),
+            (Work, End, local(1, 14, 0)),
+            (Work, Start, local(1, 15, 0)),
+            (Work, End, local(1, 18, 30)),
+        ]));
+        assert_eq!(days.len(), 1);
+        let day = &days[0];
+        assert_eq!(day.date, date(1));
+        assert_eq!(day.start, local(1, 9, 0));
+        assert_eq!(day.end, local(1, 18, 30));
+        assert_eq!(day.break_time, minutes(60));
+        assert_eq!(day.worked, minutes(4 * 60 + 3 * 60 + 30));
+    }
+
+    #[test]
+    fn session_across_midnight_is_split_into_two_days() {
+        let days = daily_summaries(&events(&[
+            (Work, Start, local(1, 20, 0)),
+            (Break, Start, local(1, 23, 30)),
+            (Break, End, local(2, 0, 30)),
+            (Work, End, local(2, 2, 0)),
+        ]));
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].date, date(1));
+        assert_eq!(days[0].break_time, minutes(30));
+        assert_eq!(days[0].worked, minutes(3 * 60 + 30));
+        assert_eq!(format_end_time(&days[0]), "24:00");
+        assert_eq!(days[1].date, date(2));
+        assert_eq!(days[1].start, local(2, 0, 0));
+        assert_eq!(days[1].break_time, minutes(30));
+        assert_eq!(days[1].worked, minutes(90));
+    }
+
+    #[test]
+    fn unfinished_session_is_not_counted() {
+        let days = daily_summaries(&events(&[(Work, Start, local(1, 9, 0))]));
+        assert!(days.is_empty());
+    }
+
+    #[test]
+    fn csv_has_one_row_per_day() {
+        let days = daily_summaries(&events(&[
+            (Work, Start, local(1, 9, 0)),
+            (Break, Start, local(1, 12, 0)),
+            (Break, End, local(1, 12, 45)),
+            (Work, End, local(1, 18, 0)),
+            (Work, Start, local(2, 22, 0)),
+            (Work, End, local(3, 1, 0)),
+        ]));
+        let path = std::env::temp_dir().join(format!("kintai-report-{}.csv", std::process::id()));
+        write_csv(&path, &days).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "日付,開始,終了,休憩,会議,実働,最長連続,残業",
+                "2024/04/01,09:00,18:00,00:45:00,00:00:00,08:15:00,05:15:00,00:00:00",
+                "2024/04/02,22:00,24:00,00:00:00,00:00:00,02:00:00,02:00:00,00:00:00",
+                "2024/04/03,00:00,01:00,00:00:00,00:00:00,01:00:00,01:00:00,00:00:00",
+            ]
+        );
+    }
+
+    fn ymd(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn week_starts_on_configured_weekday() {
+        // 2024-04-03 は水曜日
+        assert_eq!(week_range(date(3), Weekday::Mon), (date(1), date(7)));
+        assert_eq!(
+            week_range(date(3), Weekday::Sun),
+            (ymd(2024, 3, 31), date(6))
+        );
+        // 週の始まりの曜日その日
+        assert_eq!(week_range(date(1), Weekday::Mon), (date(1), date(7)));
+        assert_eq!(week_range(date(7), Weekday::Sun), (date(7), date(13)));
+        // 日曜日は月曜始まりなら週の最後の日
+        assert_eq!(week_range(date(7), Weekday::Mon), (date(1), date(7)));
+    }
+
+    #[test]
+    fn month_range_rolls_over() {
+        assert_eq!(
+            month_range(ymd(2024, 1, 31)),
+            (ymd(2024, 1, 1), ymd(2024, 1, 31))
+        );
+        assert_eq!(
+            month_range(ymd(2024, 2, 10)),
+            (ymd(2024, 2, 1), ymd(2024, 2, 29))
+        );
+        assert_eq!(
+            month_range(ymd(2023, 2, 10)),
+            (ymd(2023, 2, 1), ymd(2023, 2, 28))
+        );
+        assert_eq!(
+            month_range(ymd(2024, 12, 1)),
+            (ymd(2024, 12, 1), ymd(2024, 12, 31))
+        );
+    }
+
+    #[test]
+    fn totals_split_week_and_month() {
+        let days = daily_summaries(&events(&[
+            // 3/31 (日) 〜 4/1 (月) にまたがる業務
+            (
+                Work,
+                Start,
+                Local.with_ymd_and_hms(2024, 3, 31, 22, 0, 0).unwrap(),
+            ),
+            (Work, End, local(1, 2, 0)),
+            (Work, Start, local(2, 9, 0)),
+            (Work, End, local(2, 10, 0)),
+        ]));
+
+        let monday = totals(&days, date(2), Weekday::Mon);
+        assert_eq!(monday.week.from, date(1));
+        assert_eq!(monday.week.worked, "03:00:00");
+        assert_eq!(monday.month.worked, "03:00:00");
+
+        // 日曜始まりなら 3/31 の 2 時間も今週に入るが、今月には入らない
+        let sunday = totals(&days, date(2), Weekday::Sun);
+        assert_eq!(sunday.week.from, ymd(2024, 3, 31));
+        assert_eq!(sunday.week.worked, "05:00:00");
+        assert_eq!(sunday.week.days.len(), 3);
+        assert_eq!(sunday.month.worked, "03:00:00");
+        assert_eq!(sunday.month.days.len(), 2);
+    }
+
+    #[test]
+    fn meetings_and_breaks_are_summed_separately() {
+        let days = daily_summaries(&events(&[
+            (Work, Start, local(1, 9, 0)),
+            (Meeting, Start, local(1, 10, 0)),
+            (Meeting, End, local(1, 11, 30)),
+            (Break, Start, local(1, 12, 0)),
+            (Break, End, local(1, 12, 45)),
+            (Work, End, local(1, 18, 0)),
+        ]));
+        assert_eq!(days[0].break_time, minutes(45));
+        assert_eq!(days[0].meeting_time, minutes(90));
+        assert_eq!(days[0].worked, minutes(9 * 60 - 45 - 90));
+        // 休憩をはさまない会議は続けて働いた時間を区切らない
+        assert_eq!(days[0].longest_stretch, minutes(5 * 60 + 15));
+    }
+
+    #[test]
+    fn stretch_without_breaks_is_the_whole_session() {
+        let work = [(local(1, 9, 0), local(1, 12, 0))];
+        assert_eq!(stretches(&work, &[]), work.to_vec());
+    }
+
+    #[test]
+    fn single_break_splits_the_session() {
+        let work = [(local(1, 9, 0), local(1, 18, 0))];
+        let breaks = [(local(1, 12, 0), local(1, 13, 0))];
+        assert_eq!(
+            stretches(&work, &breaks),
+            vec![
+                (local(1, 9, 0), local(1, 12, 0)),
+                (local(1, 13, 0), local(1, 18, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn longest_stretch_across_several_breaks() {
+        let days = daily_summaries(&events(&[
+            (Work, Start, local(1, 8, 0)),
+            (Break, Start, local(1, 10, 0)),
+            (Break, End, local(1, 10, 15)),
+            (Break, Start, local(1, 14, 45)),
+            (Break, End, local(1, 15, 0)),
+            (Break, Start, local(1, 16, 0)),
+            (Break, End, local(1, 16, 30)),
+            (Work, End, local(1, 18, 0)),
+        ]));
+        // 10:15 から 14:45 までが最長
+        assert_eq!(days[0].longest_stretch, minutes(4 * 60 + 30));
+    }
+
+    #[test]
+    fn breaks_outside_the_session_are_ignored() {
+        let work = [
+            (local(1, 9, 0), local(1, 12, 0)),
+            (local(2, 9, 0), local(2, 12, 0)),
+        ];
+        // 1 日目の業務の後の休憩と、業務の始まりをまたぐ休憩
+        let breaks = [
+            (local(1, 13, 0), local(1, 14, 0)),
+            (local(2, 8, 30), local(2, 9, 30)),
+        ];
+        assert_eq!(
+            stretches(&work, &breaks),
+            vec![
+                (local(1, 9, 0), local(1, 12, 0)),
+                (local(2, 9, 30), local(2, 12, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn stretch_crossing_midnight_is_split_by_day() {
+        let days = daily_summaries(&events(&[
+            (Work, Start, local(1, 21, 0)),
+            (Break, Start, local(1, 22, 0)),
+            (Break, End, local(1, 22, 30)),
+            (Work, End, local(2, 2, 0)),
+        ]));
+        assert_eq!(days.len(), 2);
+        // 22:30 から 2:00 までの区間を 0:00 で分けてそれぞれの日に数える
+        assert_eq!(days[0].longest_stretch, minutes(90));
+        assert_eq!(days[1].longest_stretch, minutes(120));
+    }
+
+    #[test]
+    fn month_rows_cover_every_day_and_total() {
+        // 4 月 1 日から 5 日まで 9:00-18:00 (昼休憩 1 時間)、8 日は午前と午後の 2 回
+        let mut list = Vec::new();
+        for day in 1..=5 {
+            list.extend([
+                (Work, Start, local(day, 9, 0)),
+                (Break, Start, local(day, 12, 0)),
+                (Break, End, local(day, 13, 0)),
+                (Work, End, local(day, 18, 0)),
+            ]);
+        }
+        list.extend([
+            (Work, Start, local(8, 9, 0)),
+            (Work, End, local(8, 12, 0)),
+            (Work, Start, local(8, 13, 30)),
+            (Work, End, local(8, 17, 0)),
+        ]);
+        let days = daily_summaries(&events(&list));
+        let rows = month_rows(&days, date(15));
+
+        // 30 日分と合計の行
+        assert_eq!(rows.len(), 31);
+        assert_eq!(
+            rows[0],
+            [
+                "2024/04/01",
+                "09:00",
+                "18:00",
+                "01:00:00",
+                "08:00:00",
+                "00:00:00"
+            ]
+            .map(String::from)
+        );
+        assert_eq!(
+            rows[7],
+            [
+                "2024/04/08",
+                "09:00",
+                "17:00",
+                "00:00:00",
+                "06:30:00",
+                "00:00:00"
+            ]
+            .map(String::from)
+        );
+        // 働いていない日は日付だけ
+        assert_eq!(
+            rows[5],
+            ["2024/04/06", "", "", "", "", ""].map(String::from)
+        );
+        assert_eq!(rows[29][0], "2024/04/30");
+        assert_eq!(
+            rows[30],
+            ["合計", "", "", "05:00:00", "46:30:00", "00:00:00"].map(String::from)
+        );
+    }
+
+    #[test]
+    fn quarter_hour_rounding_boundaries() {
+        let secs = Duration::from_secs;
+        assert_eq!(round_quarter_hour(Duration::ZERO), Duration::ZERO);
+        assert_eq!(round_quarter_hour(secs(7 * 60 + 29)), Duration::ZERO);
+        // ちょうど 7 分 30 秒は切り上げ
+        assert_eq!(round_quarter_hour(secs(7 * 60 + 30)), minutes(15));
+        assert_eq!(round_quarter_hour(minutes(8 * 60 + 7)), minutes(8 * 60));
+        assert_eq!(
+            round_quarter_hour(minutes(8 * 60 + 8)),
+            minutes(8 * 60 + 15)
+        );
+        assert_eq!(
+            round_quarter_hour(minutes(8 * 60 + 15)),
+            minutes(8 * 60 + 15)
+        );
+        assert_eq!(
+            round_quarter_hour(minutes(8 * 60 + 52) + secs(29)),
+            minutes(8 * 60 + 45)
+        );
+        assert_eq!(
+            round_quarter_hour(minutes(8 * 60 + 52) + secs(30)),
+            minutes(9 * 60)
+        );
+    }
+
+    #[test]
+    fn daily_totals_are_rounded_per_day() {
+        let mut days = daily_summaries(&events(&[
+            (Work, Start, local(1, 9, 0)),
+            (Work, End, local(1, 17, 8)),
+            (Work, Start, local(2, 9, 0)),
+            (Work, End, local(2, 17, 7)),
+        ]));
+        round_daily_totals(&mut days);
+        assert_eq!(days[0].worked, minutes(8 * 60 + 15));
+        assert_eq!(days[1].worked, minutes(8 * 60));
+        // 期間の合計は丸めた日ごとの値の合計
+        let month = month_rows(&days, date(1));
+        assert_eq!(month.last().unwrap()[4], "16:15:00");
+    }
+
+    #[test]
+    fn overtime_is_measured_against_net_time() {
+        // 4/1 (月): 9:00-19:00 で休憩 1 時間・会議 30 分 → 実働 8:30
+        let mut days = daily_summaries(&events(&[
+            (Work, Start, local(1, 9, 0)),
+            (Meeting, Start, local(1, 10, 0)),
+            (Meeting, End, local(1, 10, 30)),
+            (Break, Start, local(1, 12, 0)),
+            (Break, End, local(1, 13, 0)),
+            (Work, End, local(1, 19, 0)),
+        ]));
+        set_overtime(&mut days, &Schedule::default());
+        assert_eq!(days[0].worked, minutes(8 * 60 + 30));
+        // 在社時間 (10 時間) ではなく実働から所定の 8 時間を引く
+        assert_eq!(days[0].overtime, minutes(30));
+    }
+
+    #[test]
+    fn overtime_is_zero_below_the_schedule() {
+        let mut days = daily_summaries(&events(&[
+            (Work, Start, local(2, 9, 0)),
+            (Work, End, local(2, 16, 0)),
+        ]));
+        set_overtime(&mut days, &Schedule::default());
+        assert_eq!(days[0].overtime, Duration::ZERO);
+    }
+
+    #[test]
+    fn all_work_on_a_holiday_is_overtime() {
+        // 土日に加えて水曜も休日にした設定での 4/3 (水) と 4/6 (土)
+        let schedule = Schedule {
+            wednesday: 0,
+            ..Schedule::default()
+        };
+        let mut days = daily_summaries(&events(&[
+            (Work, Start, local(3, 10, 0)),
+            (Work, End, local(3, 13, 0)),
+            (Work, Start, local(6, 10, 0)),
+            (Break, Start, local(6, 12, 0)),
+            (Break, End, local(6, 12, 45)),
+            (Work, End, local(6, 14, 0)),
+        ]));
+        set_overtime(&mut days, &schedule);
+        assert_eq!(days[0].date.weekday(), Weekday::Wed);
+        assert_eq!(days[0].overtime, minutes(3 * 60));
+        assert_eq!(days[1].date.weekday(), Weekday::Sat);
+        assert_eq!(days[1].overtime, minutes(3 * 60 + 15));
+    }
+
+    #[test]
+    fn overtime_follows_the_rounded_total() {
+        // 8:07:30 は 8:15 に丸めてから残業を計算する
+        let mut days = daily_summaries(&events(&[
+            (Work, Start, local(4, 9, 0)),
+            (
+                Work,
+                End,
+                Local.with_ymd_and_hms(2024, 4, 4, 17, 7, 30).unwrap(),
+            ),
+        ]));
+        round_daily_totals(&mut days);
+        set_overtime(&mut days, &Schedule::default());
+        assert_eq!(days[0].overtime, minutes(15));
+    }
+
+    // 2024 年 4 月 day 日に hours 時間働いた日の集計
+    fn worked_day(day: u32, hours: u64) -> DaySummary {
+        DaySummary {
+            date: date(day),
+            start: local(day, 9, 0),
+            end: local(day, 9, 0) + chrono::Duration::hours(hours as i64),
+            break_time: Duration::ZERO,
+            meeting_time: Duration::ZERO,
+            worked: minutes(hours * 60),
+            longest_stretch: minutes(hours * 60),
+            overtime: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn goal_streak_skips_weekends_without_records() {
+        let goal = minutes(8 * 60);
+        let schedule = Schedule::default();
+        // 4/3 (水) から 4/5 (金) と 4/8 (月) に達成し、土日は休み
+        let days: Vec<DaySummary> = [3, 4, 5, 8].iter().map(|&day| worked_day(day, 8)).collect();
+        assert_eq!(goal_streak(&days, goal, &schedule, date(8)), 4);
+
+        // 休日に達成していれば数える
+        let mut with_saturday = days.clone();
+        with_saturday.push(worked_day(6, 9));
+        assert_eq!(goal_streak(&with_saturday, goal, &schedule, date(8)), 5);
+
+        // 休日に少しだけ働いても途切れない
+        let mut with_sunday = days;
+        with_sunday.push(worked_day(7, 1));
+        assert_eq!(goal_streak(&with_sunday, goal, &schedule, date(8)), 4);
+    }
+
+    #[test]
+    fn goal_streak_breaks_on_a_missed_working_day() {
+        let goal = minutes(8 * 60);
+        let schedule = Schedule::default();
+        // 4/2 (火) は目標に届かず、4/4 (木) は記録がない
+        let days: Vec<DaySummary> = [(1, 8), (2, 7), (3, 8), (5, 9), (8, 8)]
+            .iter()
+            .map(|&(day, hours)| worked_day(day, hours))
+            .collect();
+        assert_eq!(goal_streak(&days, goal, &schedule, date(8)), 2);
+        assert_eq!(goal_streak(&days, goal, &schedule, date(3)), 1);
+
+        // 木曜が休日の予定なら記録がなくても途切れない
+        let schedule = Schedule {
+            thursday: 0,
+            ..Schedule::default()
+        };
+        assert_eq!(goal_streak(&days, goal, &schedule, date(8)), 3);
+    }
+
+    #[test]
+    fn goal_streak_waits_for_today() {
+        let goal = minutes(8 * 60);
+        let schedule = Schedule::default();
+        let days = vec![worked_day(4, 8), worked_day(5, 8), worked_day(8, 3)];
+        // 今日 (月) がまだ未達なら金曜までで数え、達成したら今日も数える
+        assert_eq!(goal_streak(&days, goal, &schedule, date(8)), 2);
+        assert_eq!(goal_streak(&days, minutes(3 * 60), &schedule, date(8)), 3);
+        // 記録がなければ 0
+        assert_eq!(goal_streak(&[], goal, &schedule, date(8)), 0);
+    }
+
+    // 2024-04-01 (月) からの週の記録 (水曜は休み, 金曜は日をまたいで土曜の 1:00 まで)
+    fn sample_week() -> Vec<DaySummary> {
+        daily_summaries(&events(&[
+            (Work, Start, local(1, 9, 0)),
+            (Break, Start, local(1, 12, 0)),
+            (Break, End, local(1, 13, 0)),
+            (Work, End, local(1, 18, 0)),
+            (Work, Start, local(2, 9, 30)),
+            (Work, End, local(2, 17, 45)),
+            (Work, Start, local(4, 10, 0)),
+            (Meeting, Start, local(4, 11, 0)),
+            (Meeting, End, local(4, 12, 0)),
+            (Work, End, local(4, 15, 0)),
+            (Work, Start, local(5, 20, 0)),
+            (Work, End, local(6, 1, 0)),
+        ]))
+    }
+
+    #[test]
+    fn weekly_report_lists_each_day_and_the_total() {
+        let report = weekly_report(&sample_week(), date(3), Weekday::Mon, date(8));
+        assert_eq!(
+            report,
+            [
+                "週報 04/01 (月) 〜 04/07 (日)",
+                "",
+                "04/01 (月)  09:00〜18:00  実働 08:00:00  休憩 01:00:00",
+                "04/02 (火)  09:30〜17:45  実働 08:15:00  休憩 00:00:00",
+                "04/03 (水)  -",
+                "04/04 (木)  10:00〜15:00  実働 04:00:00  休憩 00:00:00",
+                "04/05 (金)  20:00〜24:00  実働 04:00:00  休憩 00:00:00",
+                "04/06 (土)  00:00〜01:00  実働 01:00:00  休憩 00:00:00",
+                "04/07 (日)  -",
+                "",
+                "合計 25:15:00 (5 日, 休憩 01:00:00)",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn weekly_report_stops_at_today_in_a_partial_week() {
+        let report = weekly_report(&sample_week(), date(3), Weekday::Mon, date(3));
+        assert_eq!(
+            report,
+            [
+                "週報 04/01 (月) 〜 04/07 (日) (04/03 (水) まで)",
+                "",
+                "04/01 (月)  09:00〜18:00  実働 08:00:00  休憩 01:00:00",
+                "04/02 (火)  09:30〜17:45  実働 08:15:00  休憩 00:00:00",
+                "04/03 (水)  -",
+                "",
+                "合計 16:15:00 (2 日, 休憩 01:00:00)",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn weekly_report_follows_the_week_start() {
+        // 日曜始まりなら 03/31 (日) から 04/06 (土) まで
+        let report = weekly_report(&sample_week(), date(3), Weekday::Sun, date(8));
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "週報 03/31 (日) 〜 04/06 (土)");
+        assert_eq!(lines[2], "03/31 (日)  -");
+        assert_eq!(
+            lines[8],
+            "04/06 (土)  00:00〜01:00  実働 01:00:00  休憩 00:00:00"
+        );
+        assert_eq!(lines[10], "合計 25:15:00 (5 日, 休憩 01:00:00)");
+
+        // 記録のない週は合計 0
+        let empty = weekly_report(&[], date(3), Weekday::Mon, date(8));
+        assert!(empty.ends_with("合計 00:00:00 (0 日, 休憩 00:00:00)"));
+    }
+}
@@ -0,0 +1,197 @@
+// 画面のロック/ロック解除を検出する
+// macOS は分散通知 (com.apple.screenIsLocked / com.apple.screenIsUnlocked) を受け取る
+// Windows (WTSRegisterSessionNotification の WTS_SESSION_LOCK) や
+// Linux (logind の Lock/Unlock シグナル) には対応しておらず、何もしない
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::tray::{Effect, TrayState};
+
+// 画面のロック/ロック解除を監視するスレッドを始める (locked は画面をロックしたら true)
+#[cfg(target_os = "macos")]
+pub fn observe<F>(on_change: F)
+where
+    F: Fn(bool) + Send + 'static,
+{
+    std::thread::spawn(move || macos::run(Box::new(on_change)));
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn observe<F>(_on_change: F)
+where
+    F: Fn(bool) + Send + 'static,
+{
+}
+
+// 画面のロック/ロック解除に合わせて休憩を始める/終える (lock_break はロックで始めた休憩か)
+// 手動や離席の検出で始めた休憩は、ロックを解除しても終えない
+pub fn on_change(state: &mut TrayState, lock_break: &AtomicBool, locked: bool) -> Vec<Effect> {
+    if locked {
+        let effects = state.start_break(Duration::ZERO);
+        if !effects.is_empty() {
+            lock_break.store(true, Ordering::Relaxed);
+        }
+        effects
+    } else if lock_break.swap(false, Ordering::Relaxed) && state.is_on_break {
+        // ロック中に業務を終了していれば何もしない
+        state.toggle_break()
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::{c_char, c_void, CStr};
+
+    type CFTypeRef = *const c_void;
+    type CFStringRef = *const c_void;
+    type CFNotificationCenterRef = *const c_void;
+    type CFNotificationCallback = extern "C" fn(
+        center: CFNotificationCenterRef,
+        observer: *mut c_void,
+        name: CFStringRef,
+        object: *const c_void,
+        user_info: *const c_void,
+    );
+
+    const K_CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+    // CFNotificationSuspensionBehaviorDeliverImmediately
+    const DELIVER_IMMEDIATELY: isize = 4;
+
+    const LOCKED: &CStr = c"com.apple.screenIsLocked";
+    const UNLOCKED: &CStr = c"com.apple.screenIsUnlocked";
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFNotificationCenterGetDistributedCenter() -> CFNotificationCenterRef;
+        fn CFNotificationCenterAddObserver(
+            center: CFNotificationCenterRef,
+            observer: *const c_void,
+            callback: CFNotificationCallback,
+            name: CFStringRef,
+            object: *const c_void,
+            suspension_behavior: isize,
+        );
+        fn CFStringCreateWithCString(
+            alloc: *const c_void,
+            c_str: *const c_char,
+            encoding: u32,
+        ) -> CFStringRef;
+        fn CFEqual(a: CFTypeRef, b: CFTypeRef) -> u8;
+        fn CFRunLoopRun();
+    }
+
+    // 通知を受け取ったときに呼ぶ処理と、比べるための通知の名前
+    struct Observer {
+        on_change: Box<dyn Fn(bool) + Send>,
+        locked: CFStringRef,
+    }
+
+    // 通知を登録して、このスレッドのランループで受け取り続ける (戻らない)
+    pub fn run(on_change: Box<dyn Fn(bool) + Send>) {
+        unsafe {
+            let locked = cf_string(LOCKED);
+            let unlocked = cf_string(UNLOCKED);
+            // アプリが終わるまで使うので解放しない
+            let observer = Box::into_raw(Box::new(Observer { on_change, locked }));
+            let center = CFNotificationCenterGetDistributedCenter();
+            for name in [locked, unlocked] {
+                CFNotificationCenterAddObserver(
+                    center,
+                    observer as *const c_void,
+                    callback,
+                    name,
+                    std::ptr::null(),
+                    DELIVER_IMMEDIATELY,
+                );
+            }
+            CFRunLoopRun();
+        }
+    }
+
+    unsafe fn cf_string(s: &CStr) -> CFStringRef {
+        CFStringCreateWithCString(std::ptr::null(), s.as_ptr(), K_CF_STRING_ENCODING_UTF8)
+    }
+
+    extern "C" fn callback(
+        _center: CFNotificationCenterRef,
+        observer: *mut c_void,
+        name: CFStringRef,
+        _object: *const c_void,
+        _user_info: *const c_void,
+    ) {
+        let observer = unsafe { &*(observer as *const Observer) };
+        let locked = unsafe { CFEqual(name, observer.locked) } != 0;
+        (observer.on_change)(locked);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::store::{EventAction, EventKind};
+
+    // 業務中の状態
+    fn working() -> TrayState {
+        let mut state = TrayState::default();
+        state.toggle_work();
+        state
+    }
+
+    // 記録する休憩のイベント
+    fn breaks(effects: &[Effect]) -> Vec<EventAction> {
+        effects
+            .iter()
+            .filter_map(|effect| match effect {
+                Effect::Report(EventKind::Break, action, _) => Some(*action),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lock_starts_and_unlock_ends_a_break() {
+        let (mut state, lock_break) = (working(), AtomicBool::new(false));
+        let effects = on_change(&mut state, &lock_break, true);
+        assert_eq!(breaks(&effects), [EventAction::Start]);
+        assert!(state.is_on_break);
+        assert!(lock_break.load(Ordering::Relaxed));
+
+        let effects = on_change(&mut state, &lock_break, false);
+        assert_eq!(breaks(&effects), [EventAction::End]);
+        assert!(!state.is_on_break);
+        assert!(!lock_break.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn unlock_keeps_a_manual_break() {
+        let (mut state, lock_break) = (working(), AtomicBool::new(false));
+        state.toggle_break();
+        // 休憩中にロックしても新しく休憩は始めない
+        assert!(on_change(&mut state, &lock_break, true).is_empty());
+        assert!(on_change(&mut state, &lock_break, false).is_empty());
+        assert!(state.is_on_break);
+    }
+
+    #[test]
+    fn lock_does_nothing_when_not_working() {
+        let (mut state, lock_break) = (TrayState::default(), AtomicBool::new(false));
+        assert!(on_change(&mut state, &lock_break, true).is_empty());
+        assert!(!lock_break.load(Ordering::Relaxed));
+        assert!(on_change(&mut state, &lock_break, false).is_empty());
+    }
+
+    #[test]
+    fn unlock_after_clocking_out_does_nothing() {
+        let (mut state, lock_break) = (working(), AtomicBool::new(false));
+        on_change(&mut state, &lock_break, true);
+        // ロック中に操作がなく、自動で業務を終了した
+        state.auto_clock_out(Duration::ZERO);
+        assert!(on_change(&mut state, &lock_break, false).is_empty());
+        assert!(!lock_break.load(Ordering::Relaxed));
+        assert_eq!(state, TrayState::default());
+    }
+}
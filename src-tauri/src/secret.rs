@@ -0,0 +1,241 @@
+use keyring::Entry;
+use serde::Serialize;
+use tracing::warn;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::config::Config;
+
+// キーチェーンに登録するサービス名 (アプリの identifier と揃える)
+const SERVICE: &str = "me.satooru.kintai-app";
+
+// キーチェーンに保存する秘密情報
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Secret {
+    // 送信先 URL (スクリプトのトークンを含む)
+    EndpointUrl,
+    // 予備の送信先 URL
+    SecondaryEndpointUrl,
+    // まとめて送る送信先 URL
+    BatchEndpointUrl,
+    // 署名に使う共有の秘密鍵
+    SigningKey,
+    // プロキシの認証に使うパスワード
+    ProxyPassword,
+}
+
+impl Secret {
+    pub const ALL: [Secret; 5] = [
+        Secret::EndpointUrl,
+        Secret::SecondaryEndpointUrl,
+        Secret::BatchEndpointUrl,
+        Secret::SigningKey,
+        Secret::ProxyPassword,
+    ];
+
+    fn account(self) -> &'static str {
+        match self {
+            Secret::EndpointUrl => "endpoint_url",
+            Secret::SecondaryEndpointUrl => "secondary_endpoint_url",
+            Secret::BatchEndpointUrl => "batch_endpoint_url",
+            Secret::SigningKey => "signing_secret",
+            Secret::ProxyPassword => "proxy_password",
+        }
+    }
+
+    // 設定の中で対応する値
+    fn field(self, config: &mut Config) -> &mut String {
+        match self {
+            Secret::EndpointUrl => &mut config.endpoint_url,
+            Secret::SecondaryEndpointUrl => &mut config.secondary_endpoint_url,
+            Secret::BatchEndpointUrl => &mut config.batch_endpoint_url,
+            Secret::SigningKey => &mut config.signing_secret,
+            Secret::ProxyPassword => &mut config.proxy_password,
+        }
+    }
+
+    // キーチェーンから読み込む (登録されていなければ None)
+    pub fn load(self) -> keyring::Result<Option<Zeroizing<String>>> {
+        match Entry::new(SERVICE, self.account())?.get_password() {
+            Ok(value) => Ok(Some(Zeroizing::new(value))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // キーチェーンに保存する (空なら削除する)
+    pub fn store(self, value: &str) -> keyring::Result<()> {
+        let entry = Entry::new(SERVICE, self.account())?;
+        if value.is_empty() {
+            match entry.delete_password() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(e),
+            }
+        } else {
+            entry.set_password(value)
+        }
+    }
+}
+
+// キーチェーンの値で設定の秘密情報を上書きする
+// (キーチェーンが使えない場合は設定ファイルの値のまま)
+pub fn apply(config: &mut Config) {
+    for secret in Secret::ALL {
+        match secret.load() {
+            Ok(Some(value)) => {
+                let field = secret.field(config);
+                field.zeroize();
+                field.push_str(&value);
+            }
+            Ok(None) => {}
+            Err(e) => warn!(
+                "キーチェーンから {} を読み込めません: {} (設定ファイルの値を使います)",
+                secret.account(),
+                e
+            ),
+        }
+    }
+}
+
+// 設定の秘密情報をキーチェーンに保存し、設定ファイルに書き出す設定を返す
+// (キーチェーンに保存できなかった値だけ設定ファイルに残す)
+pub fn store(config: &Config) -> Config {
+    let mut stripped = config.clone();
+    for secret in Secret::ALL {
+        let field = secret.field(&mut stripped);
+        match secret.store(field) {
+            Ok(()) => field.zeroize(),
+            Err(e) => warn!(
+                "キーチェーンに {} を保存できません: {} (設定ファイルに保存します)",
+                secret.account(),
+                e
+            ),
+        }
+    }
+    stripped
+}
+
+// 設定ウィンドウに渡す設定 (秘密情報は空にして、登録されているかだけを渡す)
+#[derive(Debug, Serialize)]
+pub struct Redacted {
+    #[serde(flatten)]
+    pub config: Config,
+    pub has_endpoint_url: bool,
+    pub has_secondary_endpoint_url: bool,
+    pub has_batch_endpoint_url: bool,
+    pub has_signing_secret: bool,
+    pub has_proxy_password: bool,
+}
+
+// 設定から秘密情報を取り除く (プロファイルごとの送信先 URL も渡さない)
+pub fn redact(config: &Config) -> Redacted {
+    let mut config = config.clone();
+    let mut has = [false; Secret::ALL.len()];
+    for (secret, has) in Secret::ALL.into_iter().zip(&mut has) {
+        let field = secret.field(&mut config);
+        *has = !field.is_empty();
+        field.zeroize();
+    }
+    for profile in &mut config.profiles {
+        profile.endpoint_url.zeroize();
+    }
+    let [has_endpoint_url, has_secondary_endpoint_url, has_batch_endpoint_url, has_signing_secret, has_proxy_password] =
+        has;
+    Redacted {
+        config,
+        has_endpoint_url,
+        has_secondary_endpoint_url,
+        has_batch_endpoint_url,
+        has_signing_secret,
+        has_proxy_password,
+    }
+}
+
+// 設定ウィンドウで空のまま保存された秘密情報を今の値に戻す
+// (設定ウィンドウには秘密情報を渡していないので、空なら変更していないものとみなす)
+pub fn keep_unchanged(config: &mut Config, current: &Config) {
+    let mut current = current.clone();
+    for secret in Secret::ALL {
+        let field = secret.field(config);
+        if field.is_empty() {
+            field.push_str(secret.field(&mut current));
+        }
+    }
+    for profile in &mut config.profiles {
+        if !profile.endpoint_url.is_empty() {
+            continue;
+        }
+        if let Some(kept) = current
+            .profiles
+            .iter()
+            .find(|kept| kept.label == profile.label)
+        {
+            profile.endpoint_url.clone_from(&kept.endpoint_url);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::Profile;
+
+    fn configured() -> Config {
+        let mut config = Config::default();
+        config
+            .endpoint_url
+            .push_str("https://example.com/exec?token=abc");
+        config.signing_secret.push_str("s3cret-key");
+        config.proxy_password.push_str("pr0xy-pass");
+        config.profiles.push(Profile {
+            label: "A".to_string(),
+            name: String::new(),
+            endpoint_url: "https://example.com/a".to_string(),
+        });
+        config
+    }
+
+    #[test]
+    fn redacted_config_has_no_secrets() {
+        let redacted = redact(&configured());
+        assert!(redacted.has_endpoint_url);
+        assert!(redacted.has_signing_secret);
+        assert!(redacted.has_proxy_password);
+        assert!(!redacted.has_secondary_endpoint_url);
+        assert!(!redacted.has_batch_endpoint_url);
+
+        let json = serde_json::to_string(&redacted).unwrap();
+        for secret in ["token=abc", "s3cret-key", "pr0xy-pass", "example.com/a"] {
+            assert!(
+                !json.contains(secret),
+                "{} が含まれています: {}",
+                secret,
+                json
+            );
+        }
+        assert!(json.contains("\"has_endpoint_url\":true"));
+    }
+
+    #[test]
+    fn blank_secrets_keep_current_values() {
+        let current = configured();
+        let mut saved = redact(&current).config;
+        saved.name = "佐藤".to_string();
+        keep_unchanged(&mut saved, &current);
+        assert_eq!(saved.endpoint_url, current.endpoint_url);
+        assert_eq!(saved.signing_secret, current.signing_secret);
+        assert_eq!(saved.proxy_password, current.proxy_password);
+        assert_eq!(saved.profiles, current.profiles);
+        assert_eq!(saved.name, "佐藤");
+    }
+
+    #[test]
+    fn entered_secrets_replace_current_values() {
+        let current = configured();
+        let mut saved = redact(&current).config;
+        saved.endpoint_url.push_str("https://example.com/new");
+        keep_unchanged(&mut saved, &current);
+        assert_eq!(saved.endpoint_url.as_str(), "https://example.com/new");
+        assert_eq!(saved.signing_secret, current.signing_secret);
+    }
+}
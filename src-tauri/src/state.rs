@@ -0,0 +1,179 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::file;
+
+// 状態ファイルの名前 (アプリのデータディレクトリに保存する)
+const STATE_FILE_NAME: &str = "state.json";
+
+// アプリの再起動をまたいで保持する勤務状態
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistedState {
+    pub elapsed_secs: u64,
+    pub is_working: bool,
+    pub is_on_break: bool,
+    // 業務中の休憩の合計 (古い状態ファイルにはないので省略可)
+    #[serde(default)]
+    pub total_break_secs: u64,
+    #[serde(default)]
+    pub is_in_meeting: bool,
+    // 業務中の会議の合計
+    #[serde(default)]
+    pub total_meeting_secs: u64,
+    // 業務を開始した時刻
+    #[serde(default)]
+    pub started_at: Option<DateTime<Local>>,
+    // 休憩・会議を始めた時刻 (再起動しても休憩・会議の経過時間を数え続ける)
+    #[serde(default)]
+    pub break_started_at: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub meeting_started_at: Option<DateTime<Local>>,
+    // 休憩の理由 (理由を選ばずに始めた休憩なら None)
+    #[serde(default)]
+    pub break_reason: Option<String>,
+    // 一時停止中か、業務中の一時停止の合計と一時停止した時刻
+    #[serde(default)]
+    pub is_on_hold: bool,
+    #[serde(default)]
+    pub total_hold_secs: u64,
+    #[serde(default)]
+    pub hold_started_at: Option<DateTime<Local>>,
+}
+
+impl PersistedState {
+    // ファイルから読み込む (読み込めない場合は None, 壊れている場合はエラー)
+    pub fn load(path: &Path) -> Result<Option<Self>, serde_json::Error> {
+        let Ok(content) = fs::read_to_string(path) else {
+            return Ok(None);
+        };
+        serde_json::from_str(&content).map(Some)
+    }
+
+    // ファイルに書き込む
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content = serde_json::to_string(self)?;
+        file::write_atomic(path, &content)
+    }
+}
+
+// 起動時にローカルとサーバーの業務の状態を突き合わせた結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reconciliation {
+    // どちらも業務中、またはどちらも業務中でない
+    Agree,
+    // ローカルでは業務中だがサーバーには業務開始が記録されていない
+    LocalOpen,
+    // サーバーでは業務中だがローカルでは業務中でない (業務中にアプリが落ちたなど)
+    ServerOpen,
+}
+
+impl Reconciliation {
+    pub fn new(local_open: bool, server_open: bool) -> Self {
+        match (local_open, server_open) {
+            (true, false) => Reconciliation::LocalOpen,
+            (false, true) => Reconciliation::ServerOpen,
+            _ => Reconciliation::Agree,
+        }
+    }
+}
+
+// 状態ファイルのパスを取得
+pub fn state_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path_resolver()
+        .app_data_dir()
+        .map(|dir| dir.join(STATE_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Duration;
+
+    // テストごとに別の状態ファイル
+    fn temp_state(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("kintai-state-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = temp_state("working.json");
+        let now = Local::now();
+        let state = PersistedState {
+            elapsed_secs: 3600,
+            is_working: true,
+            total_break_secs: 900,
+            total_meeting_secs: 1800,
+            started_at: Some(now - Duration::hours(2)),
+            total_hold_secs: 60,
+            ..Default::default()
+        };
+        state.save(&path).unwrap();
+        assert_eq!(PersistedState::load(&path).unwrap(), Some(state));
+    }
+
+    #[test]
+    fn restores_mid_break() {
+        let path = temp_state("break.json");
+        let now = Local::now();
+        let state = PersistedState {
+            elapsed_secs: 1200,
+            is_working: true,
+            is_on_break: true,
+            total_break_secs: 300,
+            started_at: Some(now - Duration::minutes(40)),
+            break_started_at: Some(now - Duration::minutes(10)),
+            break_reason: Some("lunch".to_string()),
+            ..Default::default()
+        };
+        state.save(&path).unwrap();
+
+        let restored = PersistedState::load(&path).unwrap().unwrap();
+        assert!(restored.is_on_break);
+        assert_eq!(restored.break_started_at, state.break_started_at);
+        assert_eq!(restored.break_reason.as_deref(), Some("lunch"));
+        assert_eq!(restored, state);
+    }
+
+    #[test]
+    fn missing_file_is_none() {
+        let path = temp_state("missing.json");
+        assert_eq!(PersistedState::load(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn corrupt_file_is_error() {
+        let path = temp_state("corrupt.json");
+        file::write_atomic(&path, "{ not json").unwrap();
+        assert!(PersistedState::load(&path).is_err());
+    }
+
+    #[test]
+    fn old_state_file_without_new_fields_loads() {
+        let path = temp_state("old.json");
+        file::write_atomic(
+            &path,
+            r#"{"elapsed_secs":10,"is_working":true,"is_on_break":false}"#,
+        )
+        .unwrap();
+        let restored = PersistedState::load(&path).unwrap().unwrap();
+        assert_eq!(restored.elapsed_secs, 10);
+        assert_eq!(restored.total_break_secs, 0);
+        assert_eq!(restored.break_started_at, None);
+    }
+
+    #[test]
+    fn reconciliation_cases() {
+        assert_eq!(Reconciliation::new(false, false), Reconciliation::Agree);
+        assert_eq!(Reconciliation::new(true, true), Reconciliation::Agree);
+        assert_eq!(Reconciliation::new(true, false), Reconciliation::LocalOpen);
+        assert_eq!(Reconciliation::new(false, true), Reconciliation::ServerOpen);
+    }
+}
@@ -0,0 +1,163 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+// 応答を待つ時間 (リクエストを送ってこない接続で止まらないようにする)
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// GET /status で返す勤務状態
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub working: bool,
+    pub on_break: bool,
+    // 休憩と会議を除いた業務の経過時間 (秒)
+    pub elapsed_secs: u64,
+    pub started_at: Option<DateTime<Local>>,
+}
+
+// 127.0.0.1 の port で勤務状態を返す HTTP サーバーを別のスレッドで始める
+// (ダッシュボードなどから取得する用, 他のマシンからは接続できない)
+pub fn start<F>(port: u16, status: F) -> io::Result<()>
+where
+    F: Fn() -> Status + Send + 'static,
+{
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    info!("勤務状態を http://127.0.0.1:{}/status で公開します", port);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = respond(stream, &status) {
+                        debug!("勤務状態の要求に応答できませんでした: {}", e);
+                    }
+                }
+                Err(e) => warn!("勤務状態の要求を受け付けられませんでした: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+// 1 回分のリクエストに応答する (GET /status 以外は 404)
+fn respond(mut stream: TcpStream, status: &impl Fn() -> Status) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next(), parts.next());
+    // クエリ文字列は無視する
+    let path = path.map(|path| path.split('?').next().unwrap_or(path));
+    let (code, body) = match (method, path) {
+        (Some("GET"), Some("/status")) => (
+            "200 OK",
+            serde_json::to_string(&status()).map_err(io::Error::from)?,
+        ),
+        (Some("GET"), _) => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            r#"{"error":"method not allowed"}"#.to_string(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        code,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+
+    use chrono::TimeZone;
+
+    // 業務中の勤務状態
+    fn working() -> Status {
+        Status {
+            working: true,
+            on_break: false,
+            elapsed_secs: 5400,
+            started_at: Some(Local.with_ymd_and_hms(2024, 4, 1, 9, 0, 0).unwrap()),
+        }
+    }
+
+    // request を送って応答 (ステータス行と本文) を受け取る
+    fn request(request: &str, status: Status) -> (String, String) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let request = request.to_string();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        let (stream, _) = listener.accept().unwrap();
+        respond(stream, &move || status.clone()).unwrap();
+
+        let response = client.join().unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("Content-Type: application/json"));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        let status_line = head.lines().next().unwrap().to_string();
+        (status_line, body.to_string())
+    }
+
+    #[test]
+    fn status_has_the_documented_shape() {
+        let (status_line, body) = request("GET /status HTTP/1.1\r\n\r\n", working());
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let keys: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        assert_eq!(keys, ["elapsed_secs", "on_break", "started_at", "working"]);
+        assert_eq!(json["working"], true);
+        assert_eq!(json["on_break"], false);
+        assert_eq!(json["elapsed_secs"], 5400);
+        let started_at = json["started_at"].as_str().unwrap();
+        assert_eq!(
+            DateTime::parse_from_rfc3339(started_at).unwrap(),
+            working().started_at.unwrap()
+        );
+    }
+
+    #[test]
+    fn started_at_is_null_when_not_working() {
+        let status = Status {
+            working: false,
+            on_break: false,
+            elapsed_secs: 0,
+            started_at: None,
+        };
+        // クエリ文字列は無視する
+        let (status_line, body) = request("GET /status?pretty=1 HTTP/1.1\r\n\r\n", status);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert_eq!(
+            body,
+            r#"{"working":false,"on_break":false,"elapsed_secs":0,"started_at":null}"#
+        );
+    }
+
+    #[test]
+    fn other_requests_are_rejected() {
+        let (status_line, body) = request("GET /config HTTP/1.1\r\n\r\n", working());
+        assert_eq!(status_line, "HTTP/1.1 404 Not Found");
+        assert_eq!(body, r#"{"error":"not found"}"#);
+
+        let (status_line, body) = request("POST /status HTTP/1.1\r\n\r\n", working());
+        assert_eq!(status_line, "HTTP/1.1 405 Method Not Allowed");
+        assert_eq!(body, r#"{"error":"method not allowed"}"#);
+    }
+}
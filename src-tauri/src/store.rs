@@ -0,0 +1,984 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use tauri::{AppHandle, Manager};
+
+use crate::backup::{Backup, BackupCorrection, BackupEvent, SCHEMA_VERSION};
+use crate::import::ImportedEvent;
+use crate::report;
+
+// イベントログの DB ファイルの名前 (アプリのデータディレクトリに置く)
+const STORE_FILE_NAME: &str = "events.db";
+
+// イベントの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Work,
+    Break,
+    Meeting,
+    // 一時停止 (ローカルにだけ記録する)
+    Hold,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Work => "work",
+            EventKind::Break => "break",
+            EventKind::Meeting => "meeting",
+            EventKind::Hold => "hold",
+        }
+    }
+
+    // サーバーには送らず、ローカルにだけ記録する種類か
+    pub fn is_local(&self) -> bool {
+        *self == EventKind::Hold
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "work" => Some(EventKind::Work),
+            "break" => Some(EventKind::Break),
+            "meeting" => Some(EventKind::Meeting),
+            "hold" => Some(EventKind::Hold),
+            _ => None,
+        }
+    }
+}
+
+// 開始か終了か
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventAction {
+    Start,
+    End,
+}
+
+impl EventAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventAction::Start => "start",
+            EventAction::End => "end",
+        }
+    }
+
+    // 開始なら終了、終了なら開始
+    pub fn opposite(&self) -> Self {
+        match self {
+            EventAction::Start => EventAction::End,
+            EventAction::End => EventAction::Start,
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "start" => Some(EventAction::Start),
+            "end" => Some(EventAction::End),
+            _ => None,
+        }
+    }
+}
+
+// DB に記録されたイベント
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredEvent {
+    pub id: i64,
+    pub kind: EventKind,
+    pub action: EventAction,
+    pub timestamp: DateTime<Local>,
+    // 終了イベントなら対応する開始からの長さ
+    pub duration: Option<Duration>,
+    // 業務開始のときに入力したメモ ("リモート" など)
+    pub note: Option<String>,
+}
+
+// 勤怠イベントを記録するローカルの DB
+pub struct EventStore {
+    conn: Mutex<Connection>,
+}
+
+impl EventStore {
+    // ファイルの DB を開く
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        Self::init(Connection::open(path)?)
+    }
+
+    // メモリ上の DB を開く
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS events (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind          TEXT    NOT NULL,
+                action        TEXT    NOT NULL,
+                timestamp     INTEGER NOT NULL,
+                duration_secs INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS events_timestamp ON events (timestamp);
+            CREATE TABLE IF NOT EXISTS corrections (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                event_id      INTEGER NOT NULL,
+                kind          TEXT    NOT NULL,
+                action        TEXT    NOT NULL,
+                old_timestamp INTEGER NOT NULL,
+                new_timestamp INTEGER,
+                corrected_at  INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS notes (
+                event_id      INTEGER PRIMARY KEY,
+                note          TEXT    NOT NULL
+            );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    // イベントを記録 (終了イベントなら対応する開始からの長さも記録して返す)
+    // note が空でなければイベントのメモとして一緒に記録する
+    pub fn record<Tz: TimeZone>(
+        &self,
+        kind: EventKind,
+        action: EventAction,
+        at: &DateTime<Tz>,
+        note: Option<&str>,
+    ) -> rusqlite::Result<Option<Duration>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let timestamp = at.timestamp();
+
+        let duration = match action {
+            EventAction::Start => None,
+            EventAction::End => {
+                let started: Option<i64> = tx
+                    .query_row(
+                        "SELECT timestamp FROM events
+                         WHERE kind = ?1 AND action = 'start'
+                         ORDER BY id DESC LIMIT 1",
+                        params![kind.as_str()],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                started.map(|started| Duration::from_secs((timestamp - started).max(0) as u64))
+            }
+        };
+
+        tx.execute(
+            "INSERT INTO events (kind, action, timestamp, duration_secs)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                kind.as_str(),
+                action.as_str(),
+                timestamp,
+                duration.map(|d| d.as_secs() as i64),
+            ],
+        )?;
+        if let Some(note) = note.map(str::trim).filter(|note| !note.is_empty()) {
+            tx.execute(
+                "INSERT INTO notes (event_id, note) VALUES (?1, ?2)",
+                params![tx.last_insert_rowid(), note],
+            )?;
+        }
+        tx.commit()?;
+        Ok(duration)
+    }
+
+    // 最後の開始イベントの時刻を delta_secs 秒だけ前 (負なら後) にずらし、調整したことを記録する
+    // (調整の記録は action が "adjust" の行で、duration_secs に調整した秒数を残す)
+    pub fn adjust_start<Tz: TimeZone>(
+        &self,
+        kind: EventKind,
+        delta_secs: i64,
+        at: &DateTime<Tz>,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE events SET timestamp = timestamp - ?2
+             WHERE id = (SELECT id FROM events
+                         WHERE kind = ?1 AND action = 'start'
+                         ORDER BY id DESC LIMIT 1)",
+            params![kind.as_str(), delta_secs],
+        )?;
+        tx.execute(
+            "INSERT INTO events (kind, action, timestamp, duration_secs)
+             VALUES (?1, 'adjust', ?2, ?3)",
+            params![kind.as_str(), at.timestamp(), delta_secs],
+        )?;
+        tx.commit()
+    }
+
+    // 最後に記録した kind と action のイベントの id (なければ None)
+    pub fn last_id(&self, kind: EventKind, action: EventAction) -> rusqlite::Result<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id FROM events WHERE kind = ?1 AND action = ?2 ORDER BY id DESC LIMIT 1",
+            params![kind.as_str(), action.as_str()],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    // 記録されたすべてのイベントを古い順に取得
+    pub fn events(&self) -> rusqlite::Result<Vec<StoredEvent>> {
+        self.query_events(i64::MIN, i64::MAX)
+    }
+
+    // 期間内に記録されたイベントを古い順に取得
+    pub fn events_between<Tz: TimeZone>(
+        &self,
+        from: &DateTime<Tz>,
+        to: &DateTime<Tz>,
+    ) -> rusqlite::Result<Vec<StoredEvent>> {
+        self.query_events(from.timestamp(), to.timestamp())
+    }
+
+    // その日 (ローカル時刻の 0:00 から翌日の 0:00 まで) に記録されたイベントを古い順に取得
+    pub fn events_on(&self, date: NaiveDate) -> rusqlite::Result<Vec<StoredEvent>> {
+        let Some((from, to)) = day_range(date) else {
+            return Ok(Vec::new());
+        };
+        self.events_between(&from, &to)
+    }
+
+    // その日の実働・休憩・会議の合計
+    pub fn totals_on(&self, date: NaiveDate) -> rusqlite::Result<(Duration, Duration, Duration)> {
+        let Some((from, to)) = day_range(date) else {
+            return Ok((Duration::ZERO, Duration::ZERO, Duration::ZERO));
+        };
+        Ok((
+            self.worked_time(&from, &to)?,
+            self.total_duration(EventKind::Break, &from, &to)?,
+            self.total_duration(EventKind::Meeting, &from, &to)?,
+        ))
+    }
+
+    // from 以上 to 未満の時刻のイベントを古い順に取得
+    fn query_events(&self, from: i64, to: i64) -> rusqlite::Result<Vec<StoredEvent>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, action, timestamp, duration_secs, note FROM events
+             LEFT JOIN notes ON notes.event_id = events.id
+             WHERE timestamp >= ?1 AND timestamp < ?2
+             ORDER BY timestamp, id",
+        )?;
+        let rows = stmt.query_map(params![from, to], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<i64>>(4)?,
+                row.get::<_, Option<String>>(5)?,
+            ))
+        })?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            let (id, kind, action, timestamp, duration_secs, note) = row?;
+            // 解釈できない行は飛ばす
+            let (Some(kind), Some(action), Some(timestamp)) = (
+                EventKind::parse(&kind),
+                EventAction::parse(&action),
+                Local.timestamp_opt(timestamp, 0).single(),
+            ) else {
+                continue;
+            };
+            events.push(StoredEvent {
+                id,
+                kind,
+                action,
+                timestamp,
+                duration: duration_secs.map(|secs| Duration::from_secs(secs.max(0) as u64)),
+                note,
+            });
+        }
+        Ok(events)
+    }
+
+    // イベントの時刻を修正する (修正したことは corrections に残す, イベントがなければ false)
+    pub fn update_timestamp<Tz: TimeZone>(
+        &self,
+        id: i64,
+        timestamp: &DateTime<Tz>,
+        at: &DateTime<Tz>,
+    ) -> rusqlite::Result<bool> {
+        self.correct(id, Some(timestamp.timestamp()), at.timestamp())
+    }
+
+    // イベントを削除する (削除したことは corrections に残す, イベントがなければ false)
+    pub fn delete<Tz: TimeZone>(&self, id: i64, at: &DateTime<Tz>) -> rusqlite::Result<bool> {
+        self.correct(id, None, at.timestamp())
+    }
+
+    // イベントの時刻を new_timestamp に変える (None なら削除する)
+    fn correct(&self, id: i64, new_timestamp: Option<i64>, at: i64) -> rusqlite::Result<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let event: Option<(String, String, i64)> = tx
+            .query_row(
+                "SELECT kind, action, timestamp FROM events WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        let Some((kind, action, old_timestamp)) = event else {
+            return Ok(false);
+        };
+
+        tx.execute(
+            "INSERT INTO corrections
+                 (event_id, kind, action, old_timestamp, new_timestamp, corrected_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, kind, action, old_timestamp, new_timestamp, at],
+        )?;
+        match new_timestamp {
+            Some(timestamp) => tx.execute(
+                "UPDATE events SET timestamp = ?2 WHERE id = ?1",
+                params![id, timestamp],
+            )?,
+            None => {
+                tx.execute("DELETE FROM notes WHERE event_id = ?1", params![id])?;
+                tx.execute("DELETE FROM events WHERE id = ?1", params![id])?
+            }
+        };
+
+        // 終了イベントの長さは記録したときの開始から計算しているので、修正に合わせて計算し直す
+        recalculate_durations(&tx, &kind)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    // 過去のイベントを取り込む (同じ種類・操作・時刻のイベントがすでにあれば飛ばす)
+    // events は時刻の古い順に渡す, 取り込んだ数を返す
+    pub fn import(&self, events: &[ImportedEvent]) -> rusqlite::Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut imported = 0;
+        for event in events {
+            imported += tx.execute(
+                "INSERT INTO events (kind, action, timestamp)
+                 SELECT ?1, ?2, ?3
+                 WHERE NOT EXISTS (SELECT 1 FROM events
+                                   WHERE kind = ?1 AND action = ?2 AND timestamp = ?3)",
+                params![
+                    event.kind.as_str(),
+                    event.action.as_str(),
+                    event.timestamp.timestamp(),
+                ],
+            )?;
+        }
+        for kind in [
+            EventKind::Work,
+            EventKind::Break,
+            EventKind::Meeting,
+            EventKind::Hold,
+        ] {
+            recalculate_durations(&tx, kind.as_str())?;
+        }
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    // 記録されたすべてのイベントと修正の履歴を、解釈できない行も含めてそのまま書き出す
+    pub fn backup(&self) -> rusqlite::Result<Backup> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, action, timestamp, duration_secs, note FROM events
+             LEFT JOIN notes ON notes.event_id = events.id
+             ORDER BY id",
+        )?;
+        let events = stmt
+            .query_map([], |row| {
+                Ok(BackupEvent {
+                    id: row.get(0)?,
+                    kind: row.get(1)?,
+                    action: row.get(2)?,
+                    timestamp: local_time(row.get(3)?)?,
+                    duration_secs: row.get(4)?,
+                    note: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, event_id, kind, action, old_timestamp, new_timestamp, corrected_at
+             FROM corrections ORDER BY id",
+        )?;
+        let corrections = stmt
+            .query_map([], |row| {
+                Ok(BackupCorrection {
+                    id: row.get(0)?,
+                    event_id: row.get(1)?,
+                    kind: row.get(2)?,
+                    action: row.get(3)?,
+                    old_timestamp: local_time(row.get(4)?)?,
+                    new_timestamp: row.get::<_, Option<i64>>(5)?.map(local_time).transpose()?,
+                    corrected_at: local_time(row.get(6)?)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Backup {
+            schema_version: SCHEMA_VERSION,
+            exported_at: Local::now(),
+            events,
+            corrections,
+        })
+    }
+
+    // 記録をすべてバックアップの内容に置き換える (id もそのまま戻す)
+    pub fn restore(&self, backup: &Backup) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute_batch(
+            "DELETE FROM notes;
+             DELETE FROM corrections;
+             DELETE FROM events;",
+        )?;
+        for event in &backup.events {
+            tx.execute(
+                "INSERT INTO events (id, kind, action, timestamp, duration_secs)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    event.id,
+                    event.kind,
+                    event.action,
+                    event.timestamp.timestamp(),
+                    event.duration_secs,
+                ],
+            )?;
+            if let Some(note) = &event.note {
+                tx.execute(
+                    "INSERT INTO notes (event_id, note) VALUES (?1, ?2)",
+                    params![event.id, note],
+                )?;
+            }
+        }
+        for correction in &backup.corrections {
+            tx.execute(
+                "INSERT INTO corrections
+                     (id, event_id, kind, action, old_timestamp, new_timestamp, corrected_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    correction.id,
+                    correction.event_id,
+                    correction.kind,
+                    correction.action,
+                    correction.old_timestamp.timestamp(),
+                    correction.new_timestamp.map(|t| t.timestamp()),
+                    correction.corrected_at.timestamp(),
+                ],
+            )?;
+        }
+        tx.commit()
+    }
+
+    // 期間内に終わった業務の合計から休憩・会議・一時停止の合計を引いた実働時間
+    pub fn worked_time<Tz: TimeZone>(
+        &self,
+        from: &DateTime<Tz>,
+        to: &DateTime<Tz>,
+    ) -> rusqlite::Result<Duration> {
+        let work = self.total_duration(EventKind::Work, from, to)?;
+        let breaks = self.total_duration(EventKind::Break, from, to)?;
+        let meetings = self.total_duration(EventKind::Meeting, from, to)?;
+        let holds = self.total_duration(EventKind::Hold, from, to)?;
+        Ok(work
+            .saturating_sub(breaks)
+            .saturating_sub(meetings)
+            .saturating_sub(holds))
+    }
+
+    // 期間内に終わった休憩の長さの理由ごとの合計 (理由を選ばなかった休憩は含めない)
+    pub fn break_time_by_reason<Tz: TimeZone>(
+        &self,
+        from: &DateTime<Tz>,
+        to: &DateTime<Tz>,
+    ) -> rusqlite::Result<Vec<(String, Duration)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT note, COALESCE(SUM(duration_secs), 0) FROM events
+             JOIN notes ON notes.event_id = events.id
+             WHERE kind = 'break' AND action = 'end' AND timestamp >= ?1 AND timestamp < ?2
+             GROUP BY note ORDER BY note",
+        )?;
+        let rows = stmt.query_map(params![from.timestamp(), to.timestamp()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                Duration::from_secs(row.get::<_, i64>(1)?.max(0) as u64),
+            ))
+        })?;
+        rows.collect()
+    }
+
+    // 期間内に終わったイベントの長さの合計
+    pub fn total_duration<Tz: TimeZone>(
+        &self,
+        kind: EventKind,
+        from: &DateTime<Tz>,
+        to: &DateTime<Tz>,
+    ) -> rusqlite::Result<Duration> {
+        let conn = self.conn.lock().unwrap();
+        let secs: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(duration_secs), 0) FROM events
+             WHERE kind = ?1 AND action = 'end' AND timestamp >= ?2 AND timestamp < ?3",
+            params![kind.as_str(), from.timestamp(), to.timestamp()],
+            |row| row.get(0),
+        )?;
+        Ok(Duration::from_secs(secs.max(0) as u64))
+    }
+}
+
+// kind の終了イベントの長さを、直前に記録した開始から計算し直す
+fn recalculate_durations(tx: &Transaction, kind: &str) -> rusqlite::Result<()> {
+    tx.execute(
+        "UPDATE events SET duration_secs = MAX(timestamp - (
+             SELECT s.timestamp FROM events s
+             WHERE s.kind = events.kind AND s.action = 'start' AND s.id < events.id
+             ORDER BY s.id DESC LIMIT 1
+         ), 0)
+         WHERE kind = ?1 AND action = 'end'",
+        params![kind],
+    )?;
+    Ok(())
+}
+
+// DB に記録した UNIX 時刻をローカル時刻にする
+fn local_time(timestamp: i64) -> rusqlite::Result<DateTime<Local>> {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .ok_or(rusqlite::Error::IntegralValueOutOfRange(0, timestamp))
+}
+
+// その日の 0:00 から翌日の 0:00 まで (ローカル時刻)
+fn day_range(date: NaiveDate) -> Option<(DateTime<Local>, DateTime<Local>)> {
+    let from = report::start_of_day(date)?;
+    let to = date.succ_opt().and_then(report::start_of_day)?;
+    Some((from, to))
+}
+
+// DB ファイルのパスを取得
+pub fn store_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path_resolver()
+        .app_data_dir()
+        .map(|dir| dir.join(STORE_FILE_NAME))
+}
+
+// 送信する勤怠の文言
+pub fn status_text(kind: EventKind, action: EventAction) -> &'static str {
+    match (kind, action) {
+        (EventKind::Work, EventAction::Start) => "業務 開始",
+        (EventKind::Work, EventAction::End) => "業務 終了",
+        (EventKind::Break, EventAction::Start) => "休憩 開始",
+        (EventKind::Break, EventAction::End) => "休憩 終了",
+        (EventKind::Meeting, EventAction::Start) => "会議 開始",
+        (EventKind::Meeting, EventAction::End) => "会議 終了",
+        (EventKind::Hold, EventAction::Start) => "一時停止 開始",
+        (EventKind::Hold, EventAction::End) => "一時停止 終了",
+    }
+}
+
+// 今日終わった業務の実働時間・休憩時間・会議の時間
+pub fn today_totals(app: &AppHandle) -> rusqlite::Result<(Duration, Duration, Duration)> {
+    app.state::<EventStore>()
+        .totals_on(Local::now().date_naive())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    use chrono::Utc;
+
+    use crate::backup;
+
+    // 2024-04-01 の h 時 m 分 (UTC)
+    fn at(h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 4, 1, h, m, 0).unwrap()
+    }
+
+    fn minutes(m: u64) -> Duration {
+        Duration::from_secs(m * 60)
+    }
+
+    fn record(store: &EventStore, kind: EventKind, action: EventAction, time: DateTime<Utc>) {
+        store.record(kind, action, &time, None).unwrap();
+    }
+
+    #[test]
+    fn end_records_duration_since_start() {
+        let store = EventStore::open_in_memory().unwrap();
+        let started = store
+            .record(EventKind::Work, EventAction::Start, &at(9, 0), None)
+            .unwrap();
+        assert_eq!(started, None);
+        let ended = store
+            .record(EventKind::Work, EventAction::End, &at(17, 30), None)
+            .unwrap();
+        assert_eq!(ended, Some(minutes(8 * 60 + 30)));
+
+        let events = store.events().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].duration, Some(minutes(8 * 60 + 30)));
+    }
+
+    #[test]
+    fn end_without_start_has_no_duration() {
+        let store = EventStore::open_in_memory().unwrap();
+        let ended = store
+            .record(EventKind::Break, EventAction::End, &at(12, 0), None)
+            .unwrap();
+        assert_eq!(ended, None);
+    }
+
+    #[test]
+    fn total_duration_sums_ended_events_in_range() {
+        let store = EventStore::open_in_memory().unwrap();
+        record(&store, EventKind::Break, EventAction::Start, at(10, 0));
+        record(&store, EventKind::Break, EventAction::End, at(10, 15));
+        record(&store, EventKind::Break, EventAction::Start, at(12, 0));
+        record(&store, EventKind::Break, EventAction::End, at(13, 0));
+        // 続いている休憩は数えない
+        record(&store, EventKind::Break, EventAction::Start, at(15, 0));
+
+        let total = store
+            .total_duration(EventKind::Break, &at(0, 0), &at(23, 59))
+            .unwrap();
+        assert_eq!(total, minutes(75));
+        // 範囲外に終わったものは数えない
+        let morning = store
+            .total_duration(EventKind::Break, &at(0, 0), &at(11, 0))
+            .unwrap();
+        assert_eq!(morning, minutes(15));
+        // 種類が違うものは数えない
+        let work = store
+            .total_duration(EventKind::Work, &at(0, 0), &at(23, 59))
+            .unwrap();
+        assert_eq!(work, Duration::ZERO);
+    }
+
+    #[test]
+    fn worked_time_subtracts_break_meeting_and_hold() {
+        let store = EventStore::open_in_memory().unwrap();
+        record(&store, EventKind::Work, EventAction::Start, at(9, 0));
+        record(&store, EventKind::Meeting, EventAction::Start, at(10, 0));
+        record(&store, EventKind::Meeting, EventAction::End, at(10, 30));
+        record(&store, EventKind::Break, EventAction::Start, at(12, 0));
+        record(&store, EventKind::Break, EventAction::End, at(13, 0));
+        record(&store, EventKind::Hold, EventAction::Start, at(15, 0));
+        record(&store, EventKind::Hold, EventAction::End, at(15, 10));
+        record(&store, EventKind::Work, EventAction::End, at(18, 0));
+
+        let worked = store.worked_time(&at(0, 0), &at(23, 59)).unwrap();
+        assert_eq!(worked, minutes(9 * 60 - 60 - 30 - 10));
+    }
+
+    #[test]
+    fn worked_time_does_not_go_negative() {
+        let store = EventStore::open_in_memory().unwrap();
+        record(&store, EventKind::Break, EventAction::Start, at(12, 0));
+        record(&store, EventKind::Break, EventAction::End, at(13, 0));
+        let worked = store.worked_time(&at(0, 0), &at(23, 59)).unwrap();
+        assert_eq!(worked, Duration::ZERO);
+    }
+
+    #[test]
+    fn update_timestamp_logs_correction_and_recomputes_duration() {
+        let store = EventStore::open_in_memory().unwrap();
+        record(&store, EventKind::Break, EventAction::Start, at(12, 0));
+        record(&store, EventKind::Break, EventAction::End, at(13, 0));
+        let start = store
+            .last_id(EventKind::Break, EventAction::Start)
+            .unwrap()
+            .unwrap();
+
+        assert!(store
+            .update_timestamp(start, &at(12, 30), &at(14, 0))
+            .unwrap());
+        let events = store.events().unwrap();
+        assert_eq!(events[0].timestamp, at(12, 30));
+        assert_eq!(events[1].duration, Some(minutes(30)));
+
+        let corrections = store.backup().unwrap().corrections;
+        assert_eq!(corrections.len(), 1);
+        assert_eq!(corrections[0].event_id, start);
+        assert_eq!(corrections[0].old_timestamp, at(12, 0));
+        assert_eq!(corrections[0].new_timestamp, Some(at(12, 30).into()));
+        assert_eq!(corrections[0].corrected_at, at(14, 0));
+    }
+
+    #[test]
+    fn delete_logs_correction_and_recomputes_duration() {
+        let store = EventStore::open_in_memory().unwrap();
+        record(&store, EventKind::Work, EventAction::Start, at(9, 0));
+        record(&store, EventKind::Work, EventAction::End, at(10, 0));
+        record(&store, EventKind::Work, EventAction::Start, at(11, 0));
+        record(&store, EventKind::Work, EventAction::End, at(12, 0));
+        // 2 回目の業務開始を消すと、2 回目の終了は 1 回目の開始から数え直す
+        let second_start = store
+            .last_id(EventKind::Work, EventAction::Start)
+            .unwrap()
+            .unwrap();
+
+        assert!(store.delete(second_start, &at(13, 0)).unwrap());
+        let events = store.events().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].duration, Some(minutes(3 * 60)));
+
+        let corrections = store.backup().unwrap().corrections;
+        assert_eq!(corrections.len(), 1);
+        assert_eq!(corrections[0].event_id, second_start);
+        assert_eq!(corrections[0].new_timestamp, None);
+    }
+
+    #[test]
+    fn correcting_a_missing_event_does_nothing() {
+        let store = EventStore::open_in_memory().unwrap();
+        assert!(!store.update_timestamp(42, &at(9, 0), &at(10, 0)).unwrap());
+        assert!(!store.delete(42, &at(10, 0)).unwrap());
+        assert!(store.backup().unwrap().corrections.is_empty());
+    }
+
+    // 2024 年 4 月 day 日 h 時 m 分 (ローカル時刻)
+    fn local(day: u32, h: u32, m: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 4, day, h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn events_on_returns_only_that_local_day() {
+        let store = EventStore::open_in_memory().unwrap();
+        for time in [
+            local(1, 23, 59),
+            local(2, 0, 0),
+            local(2, 9, 0),
+            local(2, 23, 59),
+            local(3, 0, 0),
+        ] {
+            store
+                .record(EventKind::Work, EventAction::Start, &time, None)
+                .unwrap();
+        }
+
+        let day = NaiveDate::from_ymd_opt(2024, 4, 2).unwrap();
+        let times: Vec<_> = store
+            .events_on(day)
+            .unwrap()
+            .into_iter()
+            .map(|event| event.timestamp)
+            .collect();
+        assert_eq!(times, [local(2, 0, 0), local(2, 9, 0), local(2, 23, 59)]);
+    }
+
+    #[test]
+    fn events_on_an_empty_day_is_empty() {
+        let store = EventStore::open_in_memory().unwrap();
+        store
+            .record(EventKind::Work, EventAction::Start, &local(1, 9, 0), None)
+            .unwrap();
+        let day = NaiveDate::from_ymd_opt(2024, 4, 5).unwrap();
+        assert!(store.events_on(day).unwrap().is_empty());
+    }
+
+    #[test]
+    fn events_on_reflects_corrections() {
+        let store = EventStore::open_in_memory().unwrap();
+        let day = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        store
+            .record(EventKind::Work, EventAction::Start, &local(1, 9, 0), None)
+            .unwrap();
+        store
+            .record(EventKind::Break, EventAction::Start, &local(1, 12, 0), None)
+            .unwrap();
+        let events = store.events_on(day).unwrap();
+
+        // 休憩の開始を削除し、業務の開始を翌日に直す
+        assert!(store.delete(events[1].id, &local(1, 18, 0)).unwrap());
+        assert!(store
+            .update_timestamp(events[0].id, &local(2, 9, 0), &local(1, 18, 0))
+            .unwrap());
+        assert!(store.events_on(day).unwrap().is_empty());
+        assert_eq!(store.events_on(day.succ_opt().unwrap()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn totals_on_sums_only_that_day() {
+        let store = EventStore::open_in_memory().unwrap();
+        for (kind, action, time) in [
+            (EventKind::Work, EventAction::Start, local(1, 9, 0)),
+            (EventKind::Work, EventAction::End, local(1, 17, 0)),
+            (EventKind::Work, EventAction::Start, local(2, 9, 0)),
+            (EventKind::Break, EventAction::Start, local(2, 12, 0)),
+            (EventKind::Break, EventAction::End, local(2, 13, 0)),
+            (EventKind::Meeting, EventAction::Start, local(2, 14, 0)),
+            (EventKind::Meeting, EventAction::End, local(2, 14, 30)),
+            (EventKind::Work, EventAction::End, local(2, 18, 0)),
+        ] {
+            store.record(kind, action, &time, None).unwrap();
+        }
+
+        let day = NaiveDate::from_ymd_opt(2024, 4, 2).unwrap();
+        assert_eq!(
+            store.totals_on(day).unwrap(),
+            (minutes(9 * 60 - 60 - 30), minutes(60), minutes(30))
+        );
+        let empty = NaiveDate::from_ymd_opt(2024, 4, 3).unwrap();
+        assert_eq!(
+            store.totals_on(empty).unwrap(),
+            (Duration::ZERO, Duration::ZERO, Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn notes_are_stored_trimmed_and_blank_ones_dropped() {
+        let store = EventStore::open_in_memory().unwrap();
+        store
+            .record(
+                EventKind::Work,
+                EventAction::Start,
+                &at(9, 0),
+                Some(" リモート "),
+            )
+            .unwrap();
+        store
+            .record(EventKind::Work, EventAction::End, &at(12, 0), Some("  "))
+            .unwrap();
+        store
+            .record(EventKind::Work, EventAction::Start, &at(13, 0), None)
+            .unwrap();
+
+        let notes: Vec<_> = store
+            .events()
+            .unwrap()
+            .into_iter()
+            .map(|event| event.note)
+            .collect();
+        assert_eq!(notes, [Some("リモート".to_string()), None, None]);
+    }
+
+    #[test]
+    fn import_skips_events_already_recorded() {
+        let store = EventStore::open_in_memory().unwrap();
+        let nine = local(1, 9, 0);
+        record(
+            &store,
+            EventKind::Work,
+            EventAction::Start,
+            nine.with_timezone(&Utc),
+        );
+
+        let imported = |kind, action, timestamp| ImportedEvent {
+            kind,
+            action,
+            timestamp,
+        };
+        let events = [
+            imported(EventKind::Work, EventAction::Start, nine),
+            imported(EventKind::Break, EventAction::Start, local(1, 12, 0)),
+            imported(EventKind::Break, EventAction::Start, local(1, 12, 0)),
+            imported(EventKind::Break, EventAction::End, local(1, 12, 45)),
+            imported(EventKind::Work, EventAction::End, local(1, 18, 0)),
+        ];
+        // すでにある業務開始と、ファイルの中で重複した休憩の開始は取り込まない
+        assert_eq!(store.import(&events).unwrap(), 3);
+        assert_eq!(store.events().unwrap().len(), 4);
+        // 取り込んだ終了イベントにも長さを付ける
+        assert_eq!(
+            store.totals_on(nine.date_naive()).unwrap(),
+            (minutes(9 * 60 - 45), minutes(45), Duration::ZERO)
+        );
+
+        // 同じファイルをもう一度取り込んでも増えない
+        assert_eq!(store.import(&events).unwrap(), 0);
+        assert_eq!(store.events().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn json_backup_round_trips_into_an_empty_store() {
+        let store = EventStore::open_in_memory().unwrap();
+        record(&store, EventKind::Work, EventAction::Start, at(9, 0));
+        store
+            .record(
+                EventKind::Break,
+                EventAction::Start,
+                &at(12, 0),
+                Some("昼食"),
+            )
+            .unwrap();
+        record(&store, EventKind::Break, EventAction::End, at(13, 0));
+        record(&store, EventKind::Meeting, EventAction::Start, at(14, 0));
+        record(&store, EventKind::Meeting, EventAction::End, at(15, 0));
+        record(&store, EventKind::Work, EventAction::End, at(18, 0));
+        // 修正・削除の履歴も戻す
+        let meeting = store
+            .last_id(EventKind::Meeting, EventAction::Start)
+            .unwrap()
+            .unwrap();
+        store
+            .update_timestamp(meeting, &at(14, 15), &at(19, 0))
+            .unwrap();
+        let meeting_end = store
+            .last_id(EventKind::Meeting, EventAction::End)
+            .unwrap()
+            .unwrap();
+        store.delete(meeting_end, &at(19, 5)).unwrap();
+
+        let path = env::temp_dir()
+            .join(format!("kintai-store-backup-{}", std::process::id()))
+            .join("backup.json");
+        let exported = store.backup().unwrap();
+        backup::write_json(&path, &exported).unwrap();
+
+        let restored = EventStore::open_in_memory().unwrap();
+        restored
+            .restore(&backup::read_json(&path).unwrap())
+            .unwrap();
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+
+        let reexported = restored.backup().unwrap();
+        assert_eq!(reexported.events, exported.events);
+        assert_eq!(reexported.corrections, exported.corrections);
+        assert_eq!(reexported.corrections.len(), 2);
+        assert_eq!(restored.events().unwrap(), store.events().unwrap());
+        assert_eq!(
+            restored
+                .totals_on(at(9, 0).with_timezone(&Local).date_naive())
+                .unwrap(),
+            store
+                .totals_on(at(9, 0).with_timezone(&Local).date_naive())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn restore_replaces_what_was_recorded() {
+        let store = EventStore::open_in_memory().unwrap();
+        record(&store, EventKind::Work, EventAction::Start, at(9, 0));
+        let exported = store.backup().unwrap();
+
+        record(&store, EventKind::Work, EventAction::End, at(17, 0));
+        store
+            .record(
+                EventKind::Work,
+                EventAction::Start,
+                &at(18, 0),
+                Some("残業"),
+            )
+            .unwrap();
+        store.restore(&exported).unwrap();
+        assert_eq!(store.backup().unwrap().events, exported.events);
+        assert_eq!(store.events().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,1726 @@
+use std::sync::{atomic::Ordering, mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use tauri::api::dialog;
+use tauri::{AppHandle, Manager};
+use tracing::info;
+
+use crate::config::{Config, MidnightPolicy, SleepPolicy, TitleMode};
+use crate::flusher::{record_event, send_heartbeat, send_status};
+use crate::format::{
+    ceil_minutes, format_clock, format_countdown, format_duration, format_duration_minutes,
+    format_duration_with_days, format_hours_minutes, format_title,
+};
+use crate::i18n::{t, t_with, Text};
+use crate::notification::{notify, notify_session_summary, quiet, Urgency};
+use crate::store::today_totals;
+use crate::store::{EventAction, EventKind};
+use crate::tray::{self, TrayIcon};
+use crate::work::{
+    begin_work, elapsed_since, save_state, set_started_at, time_ago, transition, try_transition,
+    WorkState,
+};
+use crate::{idle, report};
+
+// 経過時間を状態ファイルに書き出す間隔 (秒)
+const SAVE_INTERVAL_SECS: u64 = 10;
+
+// 実時間がこれ以上多く進んでいたらスリープしていたとみなす
+const SLEEP_DETECT_THRESHOLD: Duration = Duration::from_secs(30);
+
+// タイマーのスレッドと、それを止めるためのチャンネル
+pub struct Timer {
+    stop: mpsc::Sender<()>,
+    handle: thread::JoinHandle<()>,
+}
+
+// タイマーを開始
+// (古いタイマーが終わるのを待ってから起動するので、タイマーのスレッドは常に 1 つ)
+pub fn start_timer(app: &AppHandle, work: WorkState) {
+    let app_clone = app.clone();
+    let timer = work.timer.clone();
+    replace_timer(&timer, move |stopped| {
+        // 表示を更新する間隔だけ待つ (止められたら false)
+        // 経過時間は毎回時刻の差から計算するので、間隔を延ばしてもずれない
+        let wait = || {
+            let interval = app_clone
+                .state::<Mutex<Config>>()
+                .lock()
+                .unwrap()
+                .tick_interval();
+            matches!(
+                stopped.recv_timeout(interval),
+                Err(mpsc::RecvTimeoutError::Timeout)
+            )
+        };
+
+        // 長すぎる休憩を通知済みか
+        let mut break_warned = false;
+        // 今日の休憩の合計と、休憩の上限を超えたことを通知済みか (起動前に超えていれば通知しない)
+        let mut budget = BreakBudget::new(
+            Local::now().date_naive(),
+            break_budget(&app_clone).is_some_and(|limit| today_break_time(&app_clone) > limit),
+        );
+        // 休憩せずに働き続けていることを通知済みか (休憩したら改めて通知する)
+        let mut stretch_warned = false;
+        // 休憩が必要な時間を超えたときの通知と点滅の状態
+        let mut mandatory_break = MandatoryBreak::default();
+        // 日付が変わっても業務中であることを通知済みか
+        let mut midnight_warned = false;
+        // 業務中であることをサーバーに知らせる周期
+        let mut heartbeat = Heartbeat::new(Instant::now());
+        // 目標勤務時間に達したことを通知済みか (再起動で復元したときに重ねて通知しない)
+        let mut goal_notified =
+            daily_goal(&app_clone).is_some_and(|goal| work.work_duration() >= goal);
+        // 今回の業務で終えた集中時間の数のうち、通知したもの (再起動で復元したときに重ねて通知しない)
+        let mut focus_cycles = FocusCycles::new(focus_block(&app_clone), work.work_duration());
+        // 今日この業務より前に働いた時間と、今日の所定労働時間を超えたことを通知済みか
+        let today = Local::now().date_naive();
+        let mut day_total = DayTotal::new(
+            today,
+            today_totals(&app_clone)
+                .map(|(worked, _, _)| worked)
+                .unwrap_or_default(),
+            work.work_duration(),
+            scheduled_time(&app_clone, today),
+        );
+        let mut last_saved = Instant::now();
+        let mut last_tick = (Instant::now(), SystemTime::now());
+        // 最後に操作した時刻と、それをもとに業務の終了を頼んだか
+        let mut last_activity = Local::now();
+        let mut clock_out_requested = false;
+        loop {
+            if !work.is_working.load(Ordering::Relaxed) {
+                break;
+            }
+
+            // 業務開始の猶予中は残り時間を表示するだけで、過ぎたら時間を数え始める
+            // (他の状態遷移の途中ならロックを待たずに次の更新で数え始める)
+            if let Some(left) = work.grace_left() {
+                if left.is_zero() {
+                    if let Ok(_guard) = work.transition_lock.try_lock() {
+                        begin_work(&app_clone, &work);
+                    }
+                } else {
+                    show_elapsed(
+                        &app_clone,
+                        &t_with(Text::GraceCountdown, &[&format_countdown(left)]),
+                        None,
+                    );
+                }
+                last_tick = (Instant::now(), SystemTime::now());
+                last_activity = Local::now();
+                if !wait() {
+                    break;
+                }
+                continue;
+            }
+
+            // macOS の Instant はスリープ中に進まないので、実時間と比べて復帰を検出する
+            let now = (Instant::now(), SystemTime::now());
+            let wall_elapsed = now.1.duration_since(last_tick.1).unwrap_or_default();
+            if let Some(gap) = sleep_gap(wall_elapsed, now.0 - last_tick.0) {
+                handle_wake(&app_clone, &work, gap);
+            }
+            last_tick = now;
+
+            // 操作がないまま長い時間が経っていたら、最後に操作した時刻で業務を終了する
+            // (スリープしていた時間も含めるため、操作した時刻を更新する前に実時間で比べる)
+            if !clock_out_requested
+                && idle_too_long(
+                    auto_clock_out_threshold(&app_clone),
+                    last_activity,
+                    Local::now(),
+                )
+            {
+                clock_out_requested = true;
+                auto_clock_out(&app_clone, &work, last_activity);
+            }
+            // 休憩中は休憩を始めた時点を最後に操作した時刻とする
+            if !work.is_on_break.load(Ordering::Relaxed) {
+                last_activity = idle::system_idle_time().map_or_else(Local::now, time_ago);
+            }
+
+            let tick = tick(&work);
+            if !matches!(tick, Tick::Break(_)) {
+                break_warned = false;
+                // 休憩を終えたら、次の休憩を始めたときに今日の休憩の合計を読み込み直す
+                // (アイコンは休憩を終えたときに業務中のものに戻っている)
+                budget.end_break();
+            }
+            let time = match tick {
+                Tick::Break(break_time) => {
+                    // 今日の休憩の合計が上限を超えたら通知し、休憩の残りをツールチップに表示する
+                    let budget_left = break_budget(&app_clone).map(|limit| {
+                        match budget.check(
+                            limit,
+                            Local::now().date_naive(),
+                            break_time,
+                            || today_break_time(&app_clone),
+                            tint_over_break_budget(&app_clone),
+                        ) {
+                            BudgetUse::Left(left) => {
+                                t_with(Text::BreakBudgetLeft, &[&ceil_minutes(left)])
+                            }
+                            BudgetUse::Over {
+                                over,
+                                notify_now,
+                                tint,
+                            } => {
+                                if notify_now {
+                                    notify(
+                                        &app_clone,
+                                        &t_with(
+                                            Text::BreakBudgetExceeded,
+                                            &[&format_hours_minutes(limit)],
+                                        ),
+                                    );
+                                }
+                                if tint {
+                                    tray::set_icon(&app_clone, TrayIcon::OverBudget);
+                                }
+                                t_with(Text::BreakBudgetOver, &[&ceil_minutes(over)])
+                            }
+                        }
+                    });
+                    show_elapsed(
+                        &app_clone,
+                        &format_break_title(&app_clone, break_time),
+                        budget_left.as_deref(),
+                    );
+
+                    if !break_warned && break_time >= break_warning_threshold(&app_clone) {
+                        notify(&app_clone, t(Text::BreakTooLong));
+                        break_warned = true;
+                    }
+
+                    stretch_warned = false;
+                    mandatory_break.reset();
+                    // 休憩中は知らせず、休憩を終えてから改めて間隔を数える
+                    heartbeat.pause(Instant::now());
+
+                    // 休憩中は経過時間を進めずに待機する (待たないと CPU を使い切ってしまう)
+                    if !wait() {
+                        break;
+                    }
+                    continue;
+                }
+                // 会議中も経過時間を進めずに待機する
+                Tick::Meeting(meeting_time) => {
+                    show_elapsed(
+                        &app_clone,
+                        &format_meeting_title(&app_clone, meeting_time),
+                        None,
+                    );
+                    heartbeat.pause(Instant::now());
+                    if !wait() {
+                        break;
+                    }
+                    continue;
+                }
+                // 一時停止中も経過時間を進めずに待機する (離席の検出で休憩にもしない)
+                Tick::Hold(hold_time) => {
+                    show_elapsed(&app_clone, &format_hold_title(&app_clone, hold_time), None);
+                    heartbeat.pause(Instant::now());
+                    if !wait() {
+                        break;
+                    }
+                    continue;
+                }
+                Tick::Working(time) => time,
+            };
+
+            // 日付が変わっても業務中なら通知する (設定によっては 0:00 で業務を分ける)
+            let started_at = *work.started_at.lock().unwrap();
+            if let Some(midnight) = passed_midnight(started_at, Local::now().date_naive()) {
+                let policy = app_clone
+                    .state::<Mutex<Config>>()
+                    .lock()
+                    .unwrap()
+                    .midnight_policy;
+                match policy {
+                    MidnightPolicy::Warn if !midnight_warned => {
+                        notify(&app_clone, t(Text::PastMidnight));
+                        midnight_warned = true;
+                    }
+                    MidnightPolicy::Warn => {}
+                    // 他の操作の途中なら次の周期でやり直す
+                    // (分けたら新しい業務の経過時間で表示し直す)
+                    MidnightPolicy::Split => {
+                        if split_at_midnight(&app_clone, &work, midnight) {
+                            notify(&app_clone, t(Text::SplitAtMidnight));
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            // 入力がないまま時間が経っていたら離席していた時点から休憩を始める
+            if let Some(away) =
+                idle::away_time(idle::system_idle_time(), idle_threshold(&app_clone))
+            {
+                // 他の操作の途中なら次の周期でやり直す
+                if try_transition(&app_clone, &work, |state| state.start_break(away)) {
+                    notify(
+                        &app_clone,
+                        &t_with(Text::IdleBreakStarted, &[&format_duration(away)]),
+                    );
+                    continue;
+                }
+            }
+
+            // 一定間隔で経過時間を保存
+            if last_saved.elapsed() >= Duration::from_secs(SAVE_INTERVAL_SECS) {
+                save_state(&app_clone, &work);
+                last_saved = Instant::now();
+            }
+
+            // 日付が変わったら今日のそれまでの業務を数え直す
+            day_total.roll_over(Local::now().date_naive());
+
+            // 設定によっては今日のそれまでの業務も含めた実働時間を表示する
+            let shown = if show_day_total(&app_clone) {
+                day_total.total(time)
+            } else {
+                time
+            };
+            let mut formatted_duration = format_elapsed(&app_clone, shown);
+
+            // 目標勤務時間に対する進み具合を表示
+            let goal = daily_goal(&app_clone);
+            match goal {
+                Some(goal) if time >= goal => {
+                    formatted_duration.push_str(" ✓");
+                    if !goal_notified {
+                        notify(&app_clone, t(Text::GoalReached));
+                        goal_notified = true;
+                    }
+                }
+                // 設定で目標を延ばした場合は改めて通知する
+                _ => goal_notified = false,
+            }
+
+            // 今日の実働 (休憩・会議を除く) が所定労働時間を超えたら一度だけ通知する
+            let scheduled = scheduled_time(&app_clone, day_total.day);
+            if day_total.overtime_due(time, scheduled) {
+                notify(
+                    &app_clone,
+                    &t_with(Text::OvertimeStarted, &[&format_hours_minutes(scheduled)]),
+                );
+            }
+
+            // 一定間隔で業務中であることをサーバーに知らせる
+            if heartbeat.due(heartbeat_interval(&app_clone), Instant::now()) {
+                send_heartbeat(&app_clone, time);
+            }
+
+            // 休憩せずに長く働いていたら休憩を促す
+            if let Some(threshold) = stretch_warning_threshold(&app_clone) {
+                let stretch = work.stretch_duration();
+                if !stretch_warned && stretch >= threshold {
+                    notify(
+                        &app_clone,
+                        &t_with(Text::LongStretch, &[&format_hours_minutes(stretch)]),
+                    );
+                    stretch_warned = true;
+                }
+            }
+
+            // 休憩が必要な時間を超えたら目立つように知らせ、設定によっては休憩するまでアイコンを点滅させる
+            let threshold = mandatory_break_threshold(&app_clone);
+            let (due, icon) = mandatory_break.check(
+                work.stretch_duration(),
+                threshold,
+                flash_for_mandatory_break(&app_clone),
+            );
+            if let Some(threshold) = threshold.filter(|_| due) {
+                let message = t_with(Text::MandatoryBreak, &[&format_hours_minutes(threshold)]);
+                notify(&app_clone, &message);
+                dialog::message(None::<&tauri::Window>, "72時間", message);
+            }
+            if let Some(icon) = icon {
+                tray::set_icon(&app_clone, icon);
+            }
+
+            // ポモドーロの集中時間が終わったら休憩を勧める (休憩中・会議中は数えない)
+            let (focus, finished) = focus_cycles.check(focus_block(&app_clone), time);
+            if finished {
+                suggest_focus_break(&app_clone, &work);
+            }
+
+            // トレイに経過時間と、目標に対する進み具合・終えた集中時間の数を表示
+            let progress = [
+                goal.map(|goal| {
+                    format!(
+                        "{} / {}",
+                        format_hours_minutes(time),
+                        format_hours_minutes(goal)
+                    )
+                }),
+                focus.map(|blocks| format!("🍅 {}", blocks)),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+            show_elapsed(
+                &app_clone,
+                &formatted_duration,
+                Some(progress.as_str()).filter(|progress| !progress.is_empty()),
+            );
+
+            if !wait() {
+                break;
+            }
+        }
+    });
+}
+
+// 動いているタイマーを止めて終わるのを待ってから、run を新しいタイマーのスレッドで動かす
+// (run に渡すチャンネルはタイマーを止めると切断される)
+fn replace_timer<F>(timer: &Mutex<Option<Timer>>, run: F)
+where
+    F: FnOnce(mpsc::Receiver<()>) + Send + 'static,
+{
+    let mut timer = timer.lock().unwrap();
+    stop_timer(&mut timer);
+
+    let (stop, stopped) = mpsc::channel::<()>();
+    let handle = thread::spawn(move || run(stopped));
+    *timer = Some(Timer { stop, handle });
+}
+
+// タイマーの 1 回の更新で表示する勤務状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tick {
+    // 休憩中 (休憩の経過時間)
+    Break(Duration),
+    // 会議中 (会議の経過時間)
+    Meeting(Duration),
+    // 一時停止中 (一時停止の経過時間)
+    Hold(Duration),
+    // 業務中 (休憩・会議・一時停止を除いた業務の経過時間)
+    Working(Duration),
+}
+
+// 勤務状態から今回の更新で表示するものを決め、業務中なら業務の経過時間を更新する
+// (休憩・会議・一時停止の間は経過時間を進めない)
+fn tick(work: &WorkState) -> Tick {
+    if work.is_on_break.load(Ordering::Relaxed) {
+        return Tick::Break(work.break_duration().unwrap_or_default());
+    }
+    if let Some(meeting_time) = work.meeting_duration() {
+        return Tick::Meeting(meeting_time);
+    }
+    if let Some(hold_time) = work.hold_duration() {
+        return Tick::Hold(hold_time);
+    }
+    let time = work.work_duration();
+    work.elapsed_secs.store(time.as_secs(), Ordering::Relaxed);
+    Tick::Working(time)
+}
+
+// 動いているタイマーを止めて、スレッドが終わるまで待つ
+pub fn stop_timer(timer: &mut Option<Timer>) {
+    if let Some(Timer { stop, handle }) = timer.take() {
+        drop(stop);
+        let _ = handle.join();
+    }
+}
+
+// 業務を開始した日より後の日 (today) になっていれば today の 0:00
+fn passed_midnight(
+    started_at: Option<DateTime<Local>>,
+    today: NaiveDate,
+) -> Option<DateTime<Local>> {
+    let started_at = started_at?;
+    if started_at.date_naive() >= today {
+        return None;
+    }
+    report::start_of_day(today)
+}
+
+// 前日の業務を midnight で終了し、同じ時刻から新しい業務を開始する (タイマーのスレッドから呼ぶ)
+// (他の状態遷移の途中のときは何もせずに false を返す)
+fn split_at_midnight(app: &AppHandle, work: &WorkState, midnight: DateTime<Local>) -> bool {
+    let Ok(_guard) = work.transition_lock.try_lock() else {
+        return false;
+    };
+
+    info!("日付が変わったので {} で業務を分けます", midnight);
+    notify_session_summary(app, work);
+    // 0:00 は丸めない (丸めると前日の終了と翌日の開始が日付の境目からずれ、どちらかの日に食い込む)
+    for (kind, action, time) in midnight_split(midnight) {
+        record_event(app, kind, action, time, None);
+        send_status(app, kind, action, time, None);
+    }
+
+    restart_at_midnight(work, midnight, Local::now());
+    set_started_at(app, work, Some(midnight));
+    save_state(app, work);
+    true
+}
+
+// 0:00 で業務を分けるときに記録するイベント (前日の業務の終了と新しい業務の開始)
+fn midnight_split(midnight: DateTime<Local>) -> [(EventKind, EventAction, DateTime<Local>); 2] {
+    [
+        (EventKind::Work, EventAction::End, midnight),
+        (EventKind::Work, EventAction::Start, midnight),
+    ]
+}
+
+// 新しい業務として 0:00 から数え直す (休憩・会議・一時停止の合計は前日の業務のもの)
+fn restart_at_midnight(work: &WorkState, midnight: DateTime<Local>, now: DateTime<Local>) {
+    work.total_break_secs.store(0, Ordering::Relaxed);
+    work.total_meeting_secs.store(0, Ordering::Relaxed);
+    work.total_hold_secs.store(0, Ordering::Relaxed);
+    work.resume_from((now - midnight).to_std().unwrap_or_default());
+}
+
+// 最後に操作してから自動で業務を終了するまでの時間が経ったか
+fn idle_too_long(
+    threshold: Option<Duration>,
+    last_activity: DateTime<Local>,
+    now: DateTime<Local>,
+) -> bool {
+    threshold.is_some_and(|threshold| {
+        (now - last_activity)
+            .to_std()
+            .is_ok_and(|idle| idle >= threshold)
+    })
+}
+
+// 最後に操作した時刻で業務を終了して通知する
+// (業務の終了はタイマーを止めて待つので、タイマーのスレッドからは別のスレッドで行う)
+fn auto_clock_out(app: &AppHandle, work: &WorkState, last_activity: DateTime<Local>) {
+    let (app, work) = (app.clone(), work.clone());
+    thread::spawn(move || {
+        let mut clocked_out = false;
+        transition(&app, &work, |state| {
+            let effects = state.auto_clock_out(elapsed_since(Some(last_activity)));
+            clocked_out = !effects.is_empty();
+            effects
+        });
+        if clocked_out {
+            info!("操作がなかったので {} で業務を終了しました", last_activity);
+            let clock_24h = app.state::<Mutex<Config>>().lock().unwrap().clock_24h;
+            notify(
+                &app,
+                &t_with(
+                    Text::AutoClockedOut,
+                    &[&format_clock(&last_activity, clock_24h)],
+                ),
+            );
+        }
+    });
+}
+
+// スリープしていた時間 (実時間の経過が単調時計の経過より大きく進んだ分)
+fn sleep_gap(wall_elapsed: Duration, monotonic_elapsed: Duration) -> Option<Duration> {
+    let gap = wall_elapsed.checked_sub(monotonic_elapsed)?;
+    (gap >= SLEEP_DETECT_THRESHOLD).then_some(gap)
+}
+
+// スリープから復帰したときの処理
+fn handle_wake(app: &AppHandle, work: &WorkState, gap: Duration) {
+    let policy = app.state::<Mutex<Config>>().lock().unwrap().sleep_policy;
+    match policy {
+        SleepPolicy::Count => work.shift_back(gap),
+        SleepPolicy::Pause => notify(app, &t_with(Text::SleepExcluded, &[&format_duration(gap)])),
+    }
+}
+
+// 自動で業務を終了するまでの時間 (0 分なら終了しない)
+fn auto_clock_out_threshold(app: &AppHandle) -> Option<Duration> {
+    let minutes = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .auto_clock_out_minutes;
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+// その日の所定労働時間 (休日なら 0)
+fn scheduled_time(app: &AppHandle, date: NaiveDate) -> Duration {
+    app.state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .schedule
+        .expected(date.weekday())
+}
+
+// 1 日の目標勤務時間 (0 分なら目標なし)
+pub fn daily_goal(app: &AppHandle) -> Option<Duration> {
+    let minutes = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .daily_goal_minutes;
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+// 離席とみなすまでの時間 (0 分なら検出しない)
+fn idle_threshold(app: &AppHandle) -> Option<Duration> {
+    let minutes = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .idle_break_minutes;
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+// 業務中であることをサーバーに知らせる周期 (休憩・会議・一時停止の間は数えない)
+struct Heartbeat {
+    // 最後に知らせた (または休憩などを終えた) 時刻
+    last: Instant,
+}
+
+impl Heartbeat {
+    fn new(now: Instant) -> Self {
+        Self { last: now }
+    }
+
+    // 休憩中などは知らせず、終えてから改めて間隔を数える
+    fn pause(&mut self, now: Instant) {
+        self.last = now;
+    }
+
+    // 前回から interval 経っていれば知らせる (interval が None なら知らせない)
+    fn due(&mut self, interval: Option<Duration>, now: Instant) -> bool {
+        let due =
+            interval.is_some_and(|interval| now.saturating_duration_since(self.last) >= interval);
+        if due {
+            self.last = now;
+        }
+        due
+    }
+}
+
+// 休憩が必要な時間を超えたことを知らせたか (休憩したら改めて知らせる) と、アイコンの点滅の状態
+#[derive(Debug, Default)]
+struct MandatoryBreak {
+    notified: bool,
+    flashing: bool,
+}
+
+impl MandatoryBreak {
+    // 休憩せずに働いた時間 stretch を確かめて、知らせるかと切り替えるアイコンを返す
+    // (threshold が None なら知らせない設定, flash なら休憩するまでアイコンを点滅させる)
+    fn check(
+        &mut self,
+        stretch: Duration,
+        threshold: Option<Duration>,
+        flash: bool,
+    ) -> (bool, Option<TrayIcon>) {
+        match threshold {
+            Some(threshold) if stretch >= threshold => {
+                let due = !self.notified;
+                self.notified = true;
+                let icon = (flash || self.flashing).then(|| {
+                    self.flashing = !self.flashing;
+                    if self.flashing {
+                        TrayIcon::Paused
+                    } else {
+                        TrayIcon::Working
+                    }
+                });
+                (due, icon)
+            }
+            // 設定で時間を延ばした場合は改めて知らせる
+            _ => (false, self.reset().then_some(TrayIcon::Working)),
+        }
+    }
+
+    // 休憩したら改めて知らせる (点滅していたかを返す)
+    fn reset(&mut self) -> bool {
+        self.notified = false;
+        std::mem::take(&mut self.flashing)
+    }
+}
+
+// 今回の業務で終えたポモドーロの集中時間の数のうち、知らせたもの
+#[derive(Debug)]
+struct FocusCycles {
+    notified: u64,
+}
+
+impl FocusCycles {
+    // 復元した業務の経過時間 worked までに終えていた集中時間は知らせない
+    fn new(block: Option<Duration>, worked: Duration) -> Self {
+        Self {
+            notified: completed_blocks(block, worked).unwrap_or(0),
+        }
+    }
+
+    // 業務の経過時間 worked までに終えた集中時間の数と、新しく終えたので休憩を勧めるかを返す
+    // (block が None なら使わない設定)
+    fn check(&mut self, block: Option<Duration>, worked: Duration) -> (Option<u64>, bool) {
+        let blocks = completed_blocks(block, worked);
+        match blocks {
+            Some(blocks) if blocks > self.notified => {
+                self.notified = blocks;
+                (Some(blocks), true)
+            }
+            _ => (blocks, false),
+        }
+    }
+}
+
+// 業務の経過時間 worked のうちに終えた集中時間の数 (使わない設定なら None)
+fn completed_blocks(block: Option<Duration>, worked: Duration) -> Option<u64> {
+    block.map(|block| worked.as_secs() / block.as_secs())
+}
+
+// 業務中であることをサーバーに知らせる間隔 (設定で 0 なら None)
+fn heartbeat_interval(app: &AppHandle) -> Option<Duration> {
+    let minutes = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .heartbeat_minutes;
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+// 休憩せずに働き続けたら休憩を促すまでの時間 (設定で 0 なら None)
+fn stretch_warning_threshold(app: &AppHandle) -> Option<Duration> {
+    let minutes = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .stretch_warning_minutes;
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+// ポモドーロの集中時間 (使わない設定なら None)
+fn focus_block(app: &AppHandle) -> Option<Duration> {
+    app.state::<Mutex<Config>>().lock().unwrap().focus_block()
+}
+
+// 集中時間が終わったので休憩を勧める (設定によっては休憩を始めるか確認する)
+fn suggest_focus_break(app: &AppHandle, work: &WorkState) {
+    let (break_minutes, ask) = {
+        let config = app.state::<Mutex<Config>>();
+        let config = config.lock().unwrap();
+        (config.focus_break_minutes, config.ask_focus_break)
+    };
+    let message = t_with(Text::FocusBlockEnded, &[&break_minutes.to_string()]);
+    if !ask || quiet(app, Urgency::Normal) {
+        notify(app, &message);
+        return;
+    }
+
+    let (app, work) = (app.clone(), work.clone());
+    dialog::ask(
+        None::<&tauri::Window>,
+        "72時間",
+        format!("{}\n{}", message, t(Text::FocusBreakPrompt)),
+        move |start_break| {
+            if start_break {
+                transition(&app, &work, |state| state.start_break(Duration::ZERO));
+            }
+        },
+    );
+}
+
+// トレイに今日のそれまでの業務も含めた実働時間を表示するか
+fn show_day_total(app: &AppHandle) -> bool {
+    app.state::<Mutex<Config>>().lock().unwrap().show_day_total
+}
+
+// 休憩が必要な連続勤務時間 (0 分なら知らせない)
+fn mandatory_break_threshold(app: &AppHandle) -> Option<Duration> {
+    let minutes = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .mandatory_break_after_minutes;
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+// 休憩が必要な時間を超えたら休憩するまでアイコンを点滅させるか
+fn flash_for_mandatory_break(app: &AppHandle) -> bool {
+    app.state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .flash_for_mandatory_break
+}
+
+// 今日の業務すべての実働時間 (今回の業務より前に終えた業務も含める)
+struct DayTotal {
+    // 数えている日 (日付が変わったら数え直す)
+    day: NaiveDate,
+    // 今日この業務より前に終えた業務の実働時間 (業務を始めたときに読み込む)
+    earlier: Duration,
+    // 所定労働時間を超えたことを通知済みか
+    overtime_notified: bool,
+}
+
+impl DayTotal {
+    // time は今回の業務のここまでの実働時間 (起動前に所定労働時間 scheduled を超えていれば通知しない)
+    fn new(today: NaiveDate, earlier: Duration, time: Duration, scheduled: Duration) -> Self {
+        Self {
+            day: today,
+            earlier,
+            overtime_notified: earlier + time > scheduled,
+        }
+    }
+
+    // 日付が変わっていたら、それまでの業務を数えないようにする
+    fn roll_over(&mut self, today: NaiveDate) {
+        if today != self.day {
+            self.day = today;
+            self.earlier = Duration::ZERO;
+            self.overtime_notified = false;
+        }
+    }
+
+    // 今回の業務の実働時間 time を含めた今日の実働時間
+    fn total(&self, time: Duration) -> Duration {
+        self.earlier + time
+    }
+
+    // 今日の実働時間が所定労働時間 scheduled を超えたので通知するか (1 日 1 回だけ)
+    fn overtime_due(&mut self, time: Duration, scheduled: Duration) -> bool {
+        let due = !self.overtime_notified && self.total(time) > scheduled;
+        self.overtime_notified |= due;
+        due
+    }
+}
+
+// 1 日の休憩の上限に対する今日の休憩の合計
+struct BreakBudget {
+    // 合計を数えている日 (日付が変わったら数え直す, 日をまたいだ休憩は終えた日に数える)
+    day: NaiveDate,
+    // 今日のこの休憩より前に終えた休憩の合計 (休憩を始めたときに読み込む)
+    earlier: Option<Duration>,
+    // 上限を超えたことを通知済みか
+    notified: bool,
+    // この休憩でアイコンを赤くしたか
+    tinted: bool,
+}
+
+// 休憩中に確かめた休憩の上限に対する使い方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BudgetUse {
+    // 上限までの残り
+    Left(Duration),
+    // 上限を超えた時間と、通知するか・アイコンを赤くするか
+    Over {
+        over: Duration,
+        notify_now: bool,
+        tint: bool,
+    },
+}
+
+impl BreakBudget {
+    fn new(today: NaiveDate, notified: bool) -> Self {
+        Self {
+            day: today,
+            earlier: None,
+            notified,
+            tinted: false,
+        }
+    }
+
+    // 休憩を終えたら、次の休憩を始めたときに今日の休憩の合計を読み込み直す
+    fn end_break(&mut self) {
+        self.earlier = None;
+        self.tinted = false;
+    }
+
+    // 今の休憩 break_time と今日終えた休憩の合計 (earlier で読み込む) を上限 limit と比べる
+    // 通知は 1 日に 1 回、アイコンを赤くするのは (tint の設定なら) 休憩ごとに 1 回
+    fn check(
+        &mut self,
+        limit: Duration,
+        today: NaiveDate,
+        break_time: Duration,
+        earlier: impl FnOnce() -> Duration,
+        tint: bool,
+    ) -> BudgetUse {
+        if today != self.day {
+            self.day = today;
+            self.earlier = None;
+            self.notified = false;
+        }
+        let used = *self.earlier.get_or_insert_with(earlier) + break_time;
+        if used <= limit {
+            return BudgetUse::Left(limit - used);
+        }
+        let notify_now = !std::mem::replace(&mut self.notified, true);
+        let tint = tint && !std::mem::replace(&mut self.tinted, true);
+        BudgetUse::Over {
+            over: used - limit,
+            notify_now,
+            tint,
+        }
+    }
+}
+
+// 1 日に取れる休憩の合計 (0 分なら上限なし)
+fn break_budget(app: &AppHandle) -> Option<Duration> {
+    let minutes = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .daily_break_budget_minutes;
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+// 休憩の合計が上限を超えたら休憩中のアイコンを赤くするか
+fn tint_over_break_budget(app: &AppHandle) -> bool {
+    app.state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .tint_over_break_budget
+}
+
+// 今日終えた休憩の合計 (読み込めなければ 0)
+fn today_break_time(app: &AppHandle) -> Duration {
+    today_totals(app)
+        .map(|(_, break_time, _)| break_time)
+        .unwrap_or_default()
+}
+
+// 休憩が長すぎると通知するまでの時間
+fn break_warning_threshold(app: &AppHandle) -> Duration {
+    let minutes = app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .break_warning_minutes;
+    Duration::from_secs(minutes * 60)
+}
+
+// 休憩中のタイトル
+pub fn format_break_title(app: &AppHandle, duration: Duration) -> String {
+    format!("{} {}", t(Text::OnBreak), format_tick(app, duration))
+}
+
+// 会議中のタイトル
+pub fn format_meeting_title(app: &AppHandle, duration: Duration) -> String {
+    format!("{} {}", t(Text::InMeeting), format_tick(app, duration))
+}
+
+// 一時停止中のタイトル
+pub fn format_hold_title(app: &AppHandle, duration: Duration) -> String {
+    format!("{} {}", t(Text::OnHold), format_tick(app, duration))
+}
+
+// 休憩・会議の経過時間を整形 (表示を 1 分ごとに更新するなら秒を表示しない)
+fn format_tick(app: &AppHandle, duration: Duration) -> String {
+    if app
+        .state::<Mutex<Config>>()
+        .lock()
+        .unwrap()
+        .minute_precision()
+    {
+        format_duration_minutes(duration, false)
+    } else {
+        format_duration(duration)
+    }
+}
+
+// トレイに経過時間などを表示する (アイコンだけの表示ならツールチップに表示する)
+// progress があればツールチップに目標に対する進み具合として表示する
+pub fn show_elapsed(app: &AppHandle, text: &str, progress: Option<&str>) {
+    let title_mode = app.state::<Mutex<Config>>().lock().unwrap().title_mode;
+    let (title, tooltip) = title_and_tooltip(title_mode, text, progress);
+    tray::set_text(app, &title);
+    // macOS 以外では上でツールチップも書き換わるので、ツールチップは後から設定する
+    if let Some(tooltip) = tooltip {
+        tray::set_tooltip(app, &tooltip);
+    }
+}
+
+// 表示形式に合わせたトレイのタイトルとツールチップ (ツールチップを変えなければ None)
+fn title_and_tooltip(
+    title_mode: TitleMode,
+    text: &str,
+    progress: Option<&str>,
+) -> (String, Option<String>) {
+    if title_mode == TitleMode::Icon {
+        let tooltip = match progress {
+            Some(progress) => format!("{} ({})", text, progress),
+            None => text.to_string(),
+        };
+        return (String::new(), Some(tooltip));
+    }
+    (text.to_string(), progress.map(str::to_string))
+}
+
+// トレイの表示を今の勤務状態に合わせてすぐに書き換える (業務中でなければ何もしない)
+pub fn refresh_title(app: &AppHandle, work: &WorkState) {
+    if !work.is_working.load(Ordering::Relaxed) {
+        return;
+    }
+    let text = if let Some(left) = work.grace_left() {
+        t_with(Text::GraceCountdown, &[&format_countdown(left)])
+    } else if let Some(break_time) = work.break_duration() {
+        format_break_title(app, break_time)
+    } else if let Some(meeting_time) = work.meeting_duration() {
+        format_meeting_title(app, meeting_time)
+    } else if let Some(hold_time) = work.hold_duration() {
+        format_hold_title(app, hold_time)
+    } else if show_day_total(app) {
+        let worked_earlier = today_totals(app)
+            .map(|(worked, _, _)| worked)
+            .unwrap_or_default();
+        format_elapsed(app, worked_earlier + work.work_duration())
+    } else {
+        format_elapsed(app, work.work_duration())
+    };
+    show_elapsed(app, &text, None);
+}
+
+// トレイに表示する経過時間 (表示形式が指定されていなければ設定に従って日数を分ける)
+pub fn format_elapsed(app: &AppHandle, duration: Duration) -> String {
+    elapsed_text(&app.state::<Mutex<Config>>().lock().unwrap(), duration)
+}
+
+// config の表示形式で整形した経過時間 (表示形式が不正ならデフォルトの形式)
+fn elapsed_text(config: &Config, duration: Duration) -> String {
+    if !config.title_format.is_empty() {
+        if let Some(title) = format_title(&config.title_format, duration) {
+            return title;
+        }
+    }
+    if config.minute_precision() {
+        return format_duration_minutes(duration, config.show_days);
+    }
+    format_duration_with_days(duration, config.show_days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+
+    use chrono::TimeZone;
+
+    use crate::store::EventStore;
+    use crate::tray::{Effect, TrayState};
+
+    // secs 秒前の時刻
+    fn secs_ago(secs: u64) -> Option<Instant> {
+        Instant::now().checked_sub(Duration::from_secs(secs))
+    }
+
+    // secs 秒前に業務を開始した勤務状態
+    fn working_since(secs: u64) -> WorkState {
+        let work = WorkState::default();
+        work.is_working.store(true, Ordering::Relaxed);
+        *work.started.lock().unwrap() = secs_ago(secs);
+        work
+    }
+
+    #[test]
+    fn break_time_is_excluded_from_elapsed() {
+        let work = working_since(90);
+        assert!(matches!(tick(&work), Tick::Working(time) if time.as_secs() == 90));
+
+        // 30 秒前に休憩を始めた
+        work.start_break(Duration::from_secs(30));
+        work.is_on_break.store(true, Ordering::Relaxed);
+        let Tick::Break(break_time) = tick(&work) else {
+            panic!("休憩中なのに休憩の表示になりません");
+        };
+        assert_eq!(break_time.as_secs(), 30);
+        assert!(work.stretch_duration().is_zero());
+
+        work.is_on_break.store(false, Ordering::Relaxed);
+        let ended = work.end_break(|break_time| break_time);
+        assert_eq!(ended.map(|(break_time, _)| break_time.as_secs()), Some(30));
+        assert_eq!(work.total_break_secs.load(Ordering::Relaxed), 30);
+
+        let Tick::Working(time) = tick(&work) else {
+            panic!("休憩を終えたのに業務中の表示になりません");
+        };
+        assert_eq!(time.as_secs(), 60);
+        assert_eq!(work.elapsed_secs.load(Ordering::Relaxed), 60);
+    }
+
+    #[test]
+    fn elapsed_does_not_advance_during_break() {
+        let work = working_since(120);
+        tick(&work);
+        assert_eq!(work.elapsed_secs.load(Ordering::Relaxed), 120);
+
+        work.start_break(Duration::ZERO);
+        work.is_on_break.store(true, Ordering::Relaxed);
+        // 休憩を始めた後に業務を開始した時刻が前にずれても (スリープなど)、休憩中は経過時間を書き換えない
+        work.shift_back(Duration::from_secs(60));
+        assert!(matches!(tick(&work), Tick::Break(_)));
+        assert!(matches!(tick(&work), Tick::Break(_)));
+        assert_eq!(work.elapsed_secs.load(Ordering::Relaxed), 120);
+    }
+
+    #[test]
+    fn rounded_break_is_what_gets_subtracted() {
+        let work = working_since(20 * 60);
+        *work.break_started.lock().unwrap() = secs_ago(7 * 60);
+        let ended = work.end_break(|_| Duration::from_secs(15 * 60));
+        assert_eq!(
+            ended.map(|(break_time, rounded)| (break_time.as_secs(), rounded.as_secs())),
+            Some((7 * 60, 15 * 60))
+        );
+        assert_eq!(work.work_duration().as_secs(), 5 * 60);
+        // 休憩していなければ何も足さない
+        assert_eq!(work.end_break(|_| Duration::from_secs(60)), None);
+        assert_eq!(work.total_break_secs.load(Ordering::Relaxed), 15 * 60);
+    }
+
+    #[test]
+    fn delayed_tick_matches_wall_clock() {
+        let work = working_since(10);
+        assert!(matches!(tick(&work), Tick::Working(time) if time.as_secs() == 10));
+
+        // スレッドが遅れて 1 回の tick の間に 5 秒経っても、1 秒ずつ足さずに実時間で数える
+        *work.started.lock().unwrap() = secs_ago(15);
+        assert!(matches!(tick(&work), Tick::Working(time) if time.as_secs() == 15));
+        assert_eq!(work.elapsed_secs.load(Ordering::Relaxed), 15);
+    }
+
+    #[test]
+    fn sleep_is_detected_from_wall_clock_gap() {
+        let second = Duration::from_secs(1);
+        // 2 時間スリープした (実時間だけ進み、スレッドの時間は 1 秒しか進んでいない)
+        assert_eq!(
+            sleep_gap(Duration::from_secs(2 * 60 * 60 + 1), second),
+            Some(Duration::from_secs(2 * 60 * 60))
+        );
+        // 少し遅れただけならスリープとみなさない
+        assert_eq!(sleep_gap(Duration::from_secs(29), second), None);
+        assert_eq!(
+            sleep_gap(Duration::from_secs(31), second),
+            Some(Duration::from_secs(30))
+        );
+        // 時計が戻った場合もスリープとみなさない
+        assert_eq!(sleep_gap(Duration::ZERO, second), None);
+    }
+
+    #[test]
+    fn sleep_is_counted_as_work_after_shift_back() {
+        let work = working_since(60);
+        work.shift_back(Duration::from_secs(2 * 60 * 60));
+        assert!(matches!(tick(&work), Tick::Working(time) if time.as_secs() == 2 * 60 * 60 + 60));
+    }
+
+    #[test]
+    fn sleep_during_break_is_counted_as_break() {
+        let work = working_since(60);
+        work.start_break(Duration::ZERO);
+        work.is_on_break.store(true, Ordering::Relaxed);
+        work.shift_back(Duration::from_secs(60 * 60));
+        let Tick::Break(break_time) = tick(&work) else {
+            panic!("休憩中なのに休憩の表示になりません");
+        };
+        assert_eq!(break_time.as_secs(), 60 * 60);
+
+        // 休憩を終えても業務の経過時間はスリープの前のまま
+        work.is_on_break.store(false, Ordering::Relaxed);
+        work.end_break(|break_time| break_time);
+        assert!(matches!(tick(&work), Tick::Working(time) if time.as_secs() == 60));
+    }
+
+    #[test]
+    fn custom_title_format_replaces_default() {
+        let duration = Duration::from_secs(6 * 60 * 60 + 30 * 60 + 5);
+        let mut config = Config::default();
+        assert_eq!(elapsed_text(&config, duration), "06:30:05");
+
+        config.title_format = "⏱ {hh}:{m}".to_string();
+        assert_eq!(elapsed_text(&config, duration), "⏱ 06:30");
+        config.title_format = "{h}h{m}m".to_string();
+        assert_eq!(elapsed_text(&config, duration), "6h30m");
+    }
+
+    #[test]
+    fn invalid_title_format_falls_back_to_default() {
+        let duration = Duration::from_secs(90);
+        let mut config = Config::default();
+        for template in ["{x}", "{h", "h}", "{h}{"] {
+            config.title_format = template.to_string();
+            assert_eq!(elapsed_text(&config, duration), "00:01:30", "{}", template);
+        }
+    }
+
+    // 止められるまで running を数えながら動き、動いている間は last に id を書き続けるタイマー
+    fn counting_timer(
+        id: usize,
+        running: &Arc<AtomicUsize>,
+        most: &Arc<AtomicUsize>,
+        last: &Arc<AtomicUsize>,
+    ) -> impl FnOnce(mpsc::Receiver<()>) + Send + 'static {
+        let (running, most, last) = (running.clone(), most.clone(), last.clone());
+        move |stopped| {
+            let now = running.fetch_add(1, SeqCst) + 1;
+            most.fetch_max(now, SeqCst);
+            while let Err(mpsc::RecvTimeoutError::Timeout) =
+                stopped.recv_timeout(Duration::from_millis(1))
+            {
+                last.store(id, SeqCst);
+            }
+            running.fetch_sub(1, SeqCst);
+        }
+    }
+
+    #[test]
+    fn concurrent_toggles_keep_one_timer_thread() {
+        let timer = Arc::new(Mutex::new(None));
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let last = Arc::new(AtomicUsize::new(0));
+
+        let toggles: Vec<_> = (0..8)
+            .map(|i| {
+                let (timer, running, most, last) =
+                    (timer.clone(), running.clone(), most.clone(), last.clone());
+                thread::spawn(move || {
+                    for j in 0..25 {
+                        let id = i * 100 + j + 1;
+                        replace_timer(&timer, counting_timer(id, &running, &most, &last));
+                    }
+                })
+            })
+            .collect();
+        for toggle in toggles {
+            toggle.join().unwrap();
+        }
+
+        assert_eq!(most.load(SeqCst), 1);
+        assert_eq!(running.load(SeqCst), 1);
+        stop_timer(&mut timer.lock().unwrap());
+        assert_eq!(running.load(SeqCst), 0);
+    }
+
+    #[test]
+    fn only_latest_timer_updates_after_rapid_toggles() {
+        let timer = Mutex::new(None);
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let last = Arc::new(AtomicUsize::new(0));
+
+        for id in 1..=50 {
+            replace_timer(&timer, counting_timer(id, &running, &most, &last));
+        }
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(last.load(SeqCst), 50);
+
+        // 止めた後はどのタイマーも書き込まない
+        stop_timer(&mut timer.lock().unwrap());
+        last.store(0, SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(last.load(SeqCst), 0);
+        assert_eq!(most.load(SeqCst), 1);
+    }
+
+    // start から 1 分ごとに minutes 分まで確かめて、知らせた時点 (分) を返す
+    fn heartbeats(
+        heartbeat: &mut Heartbeat,
+        interval: Option<Duration>,
+        start: Instant,
+        minutes: u64,
+    ) -> Vec<u64> {
+        (1..=minutes)
+            .filter(|&m| heartbeat.due(interval, start + Duration::from_secs(m * 60)))
+            .collect()
+    }
+
+    #[test]
+    fn heartbeat_fires_every_interval() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(start);
+        let interval = Some(Duration::from_secs(15 * 60));
+        assert_eq!(
+            heartbeats(&mut heartbeat, interval, start, 60),
+            [15, 30, 45, 60]
+        );
+    }
+
+    #[test]
+    fn heartbeat_counts_again_after_a_break() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(start);
+        let interval = Some(Duration::from_secs(15 * 60));
+        assert_eq!(heartbeats(&mut heartbeat, interval, start, 20), [15]);
+
+        // 20 分から 50 分まで休憩
+        for m in 20..=50 {
+            heartbeat.pause(start + Duration::from_secs(m * 60));
+        }
+        let resumed = start + Duration::from_secs(50 * 60);
+        assert_eq!(heartbeats(&mut heartbeat, interval, resumed, 30), [15, 30]);
+    }
+
+    #[test]
+    fn heartbeat_is_off_without_interval() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(start);
+        assert!(heartbeats(&mut heartbeat, None, start, 120).is_empty());
+    }
+
+    // 2024 年 4 月 day 日 h 時 m 分 (ローカル時刻)
+    fn local(day: u32, h: u32, m: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 4, day, h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn midnight_is_passed_only_on_a_later_day() {
+        let today = NaiveDate::from_ymd_opt(2024, 4, 2).unwrap();
+        assert_eq!(passed_midnight(None, today), None);
+        assert_eq!(passed_midnight(Some(local(2, 0, 0)), today), None);
+        assert_eq!(
+            passed_midnight(Some(local(1, 22, 0)), today),
+            Some(local(2, 0, 0))
+        );
+        // 何日も前から業務中なら今日の 0:00 で分ける
+        assert_eq!(
+            passed_midnight(Some(local(1, 22, 0)), today.succ_opt().unwrap()),
+            Some(local(3, 0, 0))
+        );
+    }
+
+    #[test]
+    fn midnight_split_produces_two_day_records() {
+        let store = crate::store::EventStore::open_in_memory().unwrap();
+        let mut events = vec![
+            (EventKind::Work, EventAction::Start, local(1, 22, 0)),
+            (EventKind::Break, EventAction::Start, local(1, 23, 0)),
+            (EventKind::Break, EventAction::End, local(1, 23, 30)),
+        ];
+        events.extend(midnight_split(local(2, 0, 0)));
+        events.push((EventKind::Work, EventAction::End, local(2, 3, 0)));
+        for (kind, action, time) in events {
+            store.record(kind, action, &time, None).unwrap();
+        }
+
+        let days = report::daily_summaries(&store.events().unwrap());
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].date, NaiveDate::from_ymd_opt(2024, 4, 1).unwrap());
+        assert_eq!(
+            (days[0].start, days[0].end),
+            (local(1, 22, 0), local(2, 0, 0))
+        );
+        assert_eq!(days[0].worked, Duration::from_secs(90 * 60));
+        assert_eq!(days[0].break_time, Duration::from_secs(30 * 60));
+        assert_eq!(days[1].date, NaiveDate::from_ymd_opt(2024, 4, 2).unwrap());
+        assert_eq!(
+            (days[1].start, days[1].end),
+            (local(2, 0, 0), local(2, 3, 0))
+        );
+        assert_eq!(days[1].worked, Duration::from_secs(3 * 60 * 60));
+        assert_eq!(days[1].break_time, Duration::ZERO);
+    }
+
+    #[test]
+    fn new_session_counts_from_midnight() {
+        let work = working_since(4 * 60 * 60);
+        work.total_break_secs.store(30 * 60, Ordering::Relaxed);
+        work.total_meeting_secs.store(10 * 60, Ordering::Relaxed);
+
+        restart_at_midnight(&work, local(2, 0, 0), local(2, 1, 30));
+        assert_eq!(work.total_break_secs.load(Ordering::Relaxed), 0);
+        assert_eq!(work.total_meeting_secs.load(Ordering::Relaxed), 0);
+        assert_eq!(work.work_duration().as_secs(), 90 * 60);
+    }
+
+    #[test]
+    fn minute_ticks_keep_elapsed_exact() {
+        let config = Config {
+            tick_interval_secs: 60,
+            ..Default::default()
+        };
+        assert_eq!(config.tick_interval(), Duration::from_secs(60));
+
+        // 開始から 59 秒後に最初の tick があり、その後は 60 秒ごと
+        let work = working_since(59);
+        for minutes in 0..5 {
+            let expected = 59 + minutes * 60;
+            assert!(matches!(tick(&work), Tick::Working(time) if time.as_secs() == expected));
+            work.shift_back(config.tick_interval());
+        }
+        assert_eq!(work.elapsed_secs.load(Ordering::Relaxed), 59 + 4 * 60);
+
+        // 分単位の間隔なら秒を表示しない
+        assert_eq!(elapsed_text(&config, work.work_duration()), "00:05");
+    }
+
+    #[test]
+    fn tick_interval_is_at_least_a_second() {
+        let config = Config {
+            tick_interval_secs: 0,
+            ..Default::default()
+        };
+        assert_eq!(config.tick_interval(), Duration::from_secs(1));
+        assert_eq!(elapsed_text(&config, Duration::from_secs(61)), "00:01:01");
+    }
+
+    #[test]
+    fn title_stays_cleared_after_stop() {
+        let timer = Mutex::new(None);
+        let title = Arc::new(Mutex::new(String::new()));
+        let updates = Arc::new(AtomicUsize::new(0));
+        let (thread_title, thread_updates) = (title.clone(), updates.clone());
+        replace_timer(&timer, move |stopped| loop {
+            *thread_title.lock().unwrap() = "01:23:45".to_string();
+            thread_updates.fetch_add(1, SeqCst);
+            if !matches!(
+                stopped.recv_timeout(Duration::from_millis(1)),
+                Err(mpsc::RecvTimeoutError::Timeout)
+            ) {
+                break;
+            }
+        });
+        while updates.load(SeqCst) < 3 {
+            thread::yield_now();
+        }
+
+        // 業務終了と同じ順番で、タイマーを止めてからタイトルを消す
+        stop_timer(&mut timer.lock().unwrap());
+        title.lock().unwrap().clear();
+        let stopped_at = updates.load(SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(updates.load(SeqCst), stopped_at);
+        assert_eq!(title.lock().unwrap().as_str(), "");
+    }
+
+    #[test]
+    fn idle_too_long_waits_for_the_threshold() {
+        let last_activity = local(1, 12, 0);
+        let threshold = Some(Duration::from_secs(30 * 60));
+        assert!(!idle_too_long(threshold, last_activity, local(1, 12, 29)));
+        assert!(idle_too_long(threshold, last_activity, local(1, 12, 30)));
+        // スリープしていた時間も操作がなかった時間に含める
+        assert!(idle_too_long(threshold, last_activity, local(2, 9, 0)));
+        // 無効なら何時間経っても終了しない
+        assert!(!idle_too_long(None, last_activity, local(2, 9, 0)));
+        // 時計が戻っても終了しない
+        assert!(!idle_too_long(threshold, last_activity, local(1, 11, 0)));
+    }
+
+    #[test]
+    fn auto_clock_out_is_recorded_at_the_last_activity() {
+        let last_activity = Local::now() - chrono::Duration::minutes(45);
+        let mut state = TrayState::default();
+        state.toggle_work();
+        let effects = state.auto_clock_out(elapsed_since(Some(last_activity)));
+        let ends: Vec<_> = effects
+            .iter()
+            .filter_map(|effect| match effect {
+                Effect::Report(EventKind::Work, EventAction::End, ago) => Some(time_ago(*ago)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ends.len(), 1);
+        // 終了時刻は処理した時刻ではなく最後に操作した時刻になる
+        assert!((ends[0] - last_activity).num_seconds().abs() <= 1);
+    }
+
+    #[test]
+    fn each_title_mode_formats_the_elapsed_time() {
+        let elapsed = Duration::from_secs(2 * 3600 + 5 * 60 + 9);
+        let mut config = Config::default();
+        assert_eq!(config.title_mode, TitleMode::Full);
+        assert_eq!(elapsed_text(&config, elapsed), "02:05:09");
+
+        config.title_mode = TitleMode::Minutes;
+        assert_eq!(elapsed_text(&config, elapsed), "02:05");
+
+        // アイコンだけでもツールチップには秒まで表示する
+        config.title_mode = TitleMode::Icon;
+        assert_eq!(elapsed_text(&config, elapsed), "02:05:09");
+    }
+
+    #[test]
+    fn each_title_mode_places_the_text() {
+        assert_eq!(
+            title_and_tooltip(TitleMode::Full, "02:05:09", None),
+            ("02:05:09".to_string(), None)
+        );
+        assert_eq!(
+            title_and_tooltip(TitleMode::Minutes, "02:05", Some("26%")),
+            ("02:05".to_string(), Some("26%".to_string()))
+        );
+        // アイコンだけならタイトルを消してツールチップに表示する
+        assert_eq!(
+            title_and_tooltip(TitleMode::Icon, "02:05:09", None),
+            (String::new(), Some("02:05:09".to_string()))
+        );
+        assert_eq!(
+            title_and_tooltip(TitleMode::Icon, "02:05:09", Some("26%")),
+            (String::new(), Some("02:05:09 (26%)".to_string()))
+        );
+    }
+
+    #[test]
+    fn mandatory_break_is_notified_once_until_a_break() {
+        let threshold = Some(Duration::from_secs(4 * 3600));
+        let hours = |h: u64| Duration::from_secs(h * 3600);
+        let mut mandatory_break = MandatoryBreak::default();
+        assert_eq!(
+            mandatory_break.check(hours(3), threshold, false),
+            (false, None)
+        );
+        assert_eq!(
+            mandatory_break.check(hours(4), threshold, false),
+            (true, None)
+        );
+        // 休憩するまで重ねて知らせない
+        assert_eq!(
+            mandatory_break.check(hours(5), threshold, false),
+            (false, None)
+        );
+
+        // 休憩したら改めて知らせる
+        assert!(!mandatory_break.reset());
+        assert_eq!(
+            mandatory_break.check(hours(4), threshold, false),
+            (true, None)
+        );
+
+        // 知らせない設定なら何もしない
+        let mut off = MandatoryBreak::default();
+        assert_eq!(off.check(hours(12), None, true), (false, None));
+    }
+
+    #[test]
+    fn mandatory_break_flashes_until_a_break() {
+        let threshold = Some(Duration::from_secs(60));
+        let over = Duration::from_secs(90);
+        let mut mandatory_break = MandatoryBreak::default();
+        let icons: Vec<_> = (0..4)
+            .map(|_| mandatory_break.check(over, threshold, true).1)
+            .collect();
+        assert_eq!(
+            icons,
+            [
+                Some(TrayIcon::Paused),
+                Some(TrayIcon::Working),
+                Some(TrayIcon::Paused),
+                Some(TrayIcon::Working),
+            ]
+        );
+
+        // 点滅中に休憩したら点滅をやめ、アイコンは休憩の表示に任せる
+        mandatory_break.check(over, threshold, true);
+        assert!(mandatory_break.reset());
+        assert_eq!(
+            mandatory_break.check(Duration::ZERO, threshold, true),
+            (false, None)
+        );
+    }
+
+    #[test]
+    fn mandatory_break_stops_flashing_when_the_threshold_is_raised() {
+        let mut mandatory_break = MandatoryBreak::default();
+        let over = Duration::from_secs(90);
+        mandatory_break.check(over, Some(Duration::from_secs(60)), true);
+        // 設定で時間を延ばしたら元のアイコンに戻し、超えたら改めて知らせる
+        assert_eq!(
+            mandatory_break.check(over, Some(Duration::from_secs(120)), true),
+            (false, Some(TrayIcon::Working))
+        );
+        assert_eq!(
+            mandatory_break.check(
+                Duration::from_secs(120),
+                Some(Duration::from_secs(120)),
+                false
+            ),
+            (true, None)
+        );
+    }
+
+    #[test]
+    fn break_budget_adds_earlier_breaks_read_once_per_break() {
+        let today = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let mut budget = BreakBudget::new(today, false);
+        let reads = AtomicUsize::new(0);
+        let earlier = || {
+            reads.fetch_add(1, SeqCst);
+            minutes(40)
+        };
+
+        // 今日すでに 40 分休憩していて、上限は 60 分
+        assert_eq!(
+            budget.check(minutes(60), today, minutes(5), earlier, false),
+            BudgetUse::Left(minutes(15))
+        );
+        assert_eq!(
+            budget.check(minutes(60), today, minutes(20), earlier, false),
+            BudgetUse::Left(Duration::ZERO)
+        );
+        // 休憩中は合計を読み込み直さない
+        assert_eq!(reads.load(SeqCst), 1);
+
+        budget.end_break();
+        budget.check(minutes(60), today, Duration::ZERO, earlier, false);
+        assert_eq!(reads.load(SeqCst), 2);
+    }
+
+    #[test]
+    fn break_budget_is_notified_once_a_day_and_tinted_once_a_break() {
+        let today = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let limit = minutes(60);
+        let mut budget = BreakBudget::new(today, false);
+
+        let over = |over, notify_now, tint| BudgetUse::Over {
+            over,
+            notify_now,
+            tint,
+        };
+        assert_eq!(
+            budget.check(limit, today, minutes(61), || minutes(0), true),
+            over(minutes(1), true, true)
+        );
+        assert_eq!(
+            budget.check(limit, today, minutes(70), || minutes(0), true),
+            over(minutes(10), false, false)
+        );
+
+        // 次の休憩ではアイコンだけ赤くし直す
+        budget.end_break();
+        assert_eq!(
+            budget.check(limit, today, minutes(5), || minutes(70), true),
+            over(minutes(15), false, true)
+        );
+
+        // 日付が変わったら数え直して改めて通知する
+        let tomorrow = today.succ_opt().unwrap();
+        assert_eq!(
+            budget.check(limit, tomorrow, minutes(10), || minutes(0), true),
+            BudgetUse::Left(minutes(50))
+        );
+        assert_eq!(
+            budget.check(limit, tomorrow, minutes(75), || minutes(0), false),
+            over(minutes(15), true, false)
+        );
+    }
+
+    #[test]
+    fn break_budget_exceeded_before_launch_is_not_notified_again() {
+        let today = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        let limit = Duration::from_secs(3600);
+        let mut budget = BreakBudget::new(today, true);
+        assert!(matches!(
+            budget.check(limit, today, Duration::from_secs(60), || limit, true),
+            BudgetUse::Over {
+                notify_now: false,
+                tint: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn focus_cycles_count_each_finished_block_once() {
+        let block = Some(Duration::from_secs(25 * 60));
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let mut cycles = FocusCycles::new(block, Duration::ZERO);
+
+        // 1 つ目の集中時間の途中
+        assert_eq!(cycles.check(block, minutes(24)), (Some(0), false));
+        // 終えたら 1 回だけ休憩を勧める
+        assert_eq!(cycles.check(block, minutes(25)), (Some(1), true));
+        assert_eq!(cycles.check(block, minutes(26)), (Some(1), false));
+        assert_eq!(cycles.check(block, minutes(49)), (Some(1), false));
+        // 次の集中時間を終えたら数が増える
+        assert_eq!(cycles.check(block, minutes(50)), (Some(2), true));
+        // スリープなどで 2 つ分進んでも勧めるのは 1 回
+        assert_eq!(cycles.check(block, minutes(101)), (Some(4), true));
+        assert_eq!(cycles.check(block, minutes(101)), (Some(4), false));
+    }
+
+    #[test]
+    fn focus_cycles_restored_mid_session_are_not_repeated() {
+        let block = Some(Duration::from_secs(25 * 60));
+        // 再起動で 1 時間の業務を復元したら、それまでの 2 つは知らせない
+        let mut cycles = FocusCycles::new(block, Duration::from_secs(3600));
+        assert_eq!(
+            cycles.check(block, Duration::from_secs(3600)),
+            (Some(2), false)
+        );
+        assert_eq!(
+            cycles.check(block, Duration::from_secs(75 * 60)),
+            (Some(3), true)
+        );
+    }
+
+    #[test]
+    fn focus_cycles_follow_the_configured_block() {
+        let mut config = Config {
+            pomodoro: true,
+            focus_minutes: 50,
+            ..Config::default()
+        };
+        let hour = Duration::from_secs(3600);
+        let mut cycles = FocusCycles::new(config.focus_block(), Duration::ZERO);
+        assert_eq!(cycles.check(config.focus_block(), hour), (Some(1), true));
+
+        // 使わない設定なら数えない
+        config.pomodoro = false;
+        assert_eq!(cycles.check(config.focus_block(), hour * 2), (None, false));
+        config.pomodoro = true;
+        config.focus_minutes = 0;
+        assert_eq!(cycles.check(config.focus_block(), hour * 2), (None, false));
+    }
+
+    #[test]
+    fn afternoon_total_includes_the_morning() {
+        let store = EventStore::open_in_memory().unwrap();
+        let at = |h: u32, m: u32| Local.with_ymd_and_hms(2024, 4, 1, h, m, 0).unwrap();
+        let hours = |h: u64| Duration::from_secs(h * 3600);
+        // 午前に 3 時間 (休憩 30 分を除く) 働いて昼に業務を終了した
+        for (kind, action, time) in [
+            (EventKind::Work, EventAction::Start, at(8, 30)),
+            (EventKind::Break, EventAction::Start, at(10, 0)),
+            (EventKind::Break, EventAction::End, at(10, 30)),
+            (EventKind::Work, EventAction::End, at(12, 0)),
+        ] {
+            store.record(kind, action, &time, None).unwrap();
+        }
+
+        // 午後の業務を始めたときに午前の業務を読み込む
+        let today = at(13, 0).date_naive();
+        let (morning, _, _) = store.totals_on(today).unwrap();
+        let mut day_total = DayTotal::new(today, morning, Duration::ZERO, hours(8));
+        assert_eq!(day_total.total(Duration::ZERO), hours(3));
+        let config = Config::default();
+        assert_eq!(
+            elapsed_text(
+                &config,
+                day_total.total(Duration::from_secs(2 * 3600 + 15 * 60))
+            ),
+            "05:15:00"
+        );
+
+        // 午前と合わせて所定労働時間を超えたら 1 回だけ通知する
+        assert!(!day_total.overtime_due(hours(5), hours(8)));
+        assert!(day_total.overtime_due(hours(5) + Duration::from_secs(1), hours(8)));
+        assert!(!day_total.overtime_due(hours(6), hours(8)));
+    }
+
+    #[test]
+    fn day_total_starts_over_on_a_new_day() {
+        let today = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        let hours = |h: u64| Duration::from_secs(h * 3600);
+        // 起動前にもう超えていたら通知しない
+        let mut day_total = DayTotal::new(today, hours(6), hours(3), hours(8));
+        assert!(!day_total.overtime_due(hours(4), hours(8)));
+
+        // 日付が変わったら前日の業務は数えず、また通知する
+        day_total.roll_over(today);
+        assert_eq!(day_total.total(hours(1)), hours(7));
+        day_total.roll_over(today.succ_opt().unwrap());
+        assert_eq!(day_total.day, today.succ_opt().unwrap());
+        assert_eq!(day_total.total(hours(1)), hours(1));
+        assert!(!day_total.overtime_due(hours(8), hours(8)));
+        assert!(day_total.overtime_due(hours(9), hours(8)));
+    }
+}
@@ -0,0 +1,807 @@
+use std::time::Duration;
+
+use tauri::{AppHandle, Icon, SystemTrayMenuItemHandle};
+use tracing::{error, warn};
+
+use crate::config::Config;
+use crate::i18n::{t, Text};
+use crate::store::{EventAction, EventKind};
+
+// トレイのツールチップに常に表示するアプリ名
+const DEFAULT_TOOLTIP: &str = "72時間";
+
+// トレイに経過時間などを表示する (空文字で消す)
+// macOS はメニューバーにタイトルを表示し、それ以外の OS はツールチップに表示する
+#[cfg(target_os = "macos")]
+pub fn set_text(app: &AppHandle, text: &str) {
+    let _ = app.tray_handle().set_title(text);
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn set_text(app: &AppHandle, text: &str) {
+    set_tooltip(app, text);
+}
+
+// トレイのツールチップを設定する (空文字でアプリ名だけに戻す, Linux では表示されない)
+pub fn set_tooltip(app: &AppHandle, text: &str) {
+    let tooltip = if text.is_empty() {
+        DEFAULT_TOOLTIP.to_string()
+    } else {
+        format!("{} {}", DEFAULT_TOOLTIP, text)
+    };
+    let _ = app.tray_handle().set_tooltip(&tooltip);
+}
+
+// メニュー項目を変更する (トレイの準備ができていないなどで失敗したら 1 回だけやり直す)
+// やり直しても失敗したら false を返す
+pub fn update_item<F>(app: &AppHandle, id: &str, f: F) -> bool
+where
+    F: Fn(&SystemTrayMenuItemHandle) -> tauri::Result<()>,
+{
+    let item = app.tray_handle().get_item(id);
+    let mut result = f(&item);
+    if let Err(e) = &result {
+        warn!(
+            "メニュー項目 {} を変更できませんでした (やり直します): {}",
+            id, e
+        );
+        result = f(&item);
+    }
+    if let Err(e) = &result {
+        error!("メニュー項目 {} を変更できませんでした: {}", id, e);
+    }
+    result.is_ok()
+}
+
+// 勤務状態に合わせたトレイのアイコン
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayIcon {
+    Idle,
+    Working,
+    // 休憩中・会議中
+    Paused,
+    // 今日の休憩が上限を超えた休憩中 (色を付けて表示する)
+    OverBudget,
+}
+
+impl TrayIcon {
+    // アプリに同梱しているアイコンのパス
+    fn resource(self) -> &'static str {
+        match self {
+            TrayIcon::Idle => "icons/tray/idle.png",
+            TrayIcon::Working => "icons/tray/working.png",
+            TrayIcon::Paused => "icons/tray/break.png",
+            TrayIcon::OverBudget => "icons/tray/over_budget.png",
+        }
+    }
+}
+
+// トレイのアイコンを変える (アイコンが見つからなければ今のアイコンのまま)
+pub fn set_icon(app: &AppHandle, icon: TrayIcon) {
+    let path = app
+        .path_resolver()
+        .resolve_resource(icon.resource())
+        .filter(|path| path.is_file());
+    let Some(path) = path else {
+        warn!("トレイのアイコンが見つかりません: {}", icon.resource());
+        return;
+    };
+
+    let tray = app.tray_handle();
+    let _ = tray.set_icon(Icon::File(path));
+    // macOS ではメニューバーの明暗に合わせて色を変える (色を付けたアイコンはそのまま表示する)
+    #[cfg(target_os = "macos")]
+    let _ = tray.set_icon_as_template(icon != TrayIcon::OverBudget);
+}
+
+// トレイの操作で変わる勤務状態
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TrayState {
+    pub is_working: bool,
+    pub is_on_break: bool,
+    pub is_in_meeting: bool,
+    // 一時停止中 (休憩と違ってサーバーには送らない)
+    pub is_on_hold: bool,
+}
+
+// 状態が変わったときに行う処理 (実際の反映は呼び出し側が行う)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    // メニュー項目のタイトルを変える
+    SetMenuTitle(&'static str, Text),
+    // メニュー項目の有効/無効を切り替える
+    SetMenuEnabled(&'static str, bool),
+    // 業務のタイマーを開始する
+    StartWork,
+    // 取り消しで業務に戻す (猶予なしでタイマーを開始する, 時間は操作前のものに戻す)
+    ResumeWork,
+    // 業務のタイマーを止めてトレイの表示を消す
+    EndWork,
+    // 休憩を開始する (指定した時間だけ前から休憩していたことにする)
+    StartBreak(Duration),
+    // 休憩を終了して休憩時間を合計に加える
+    EndBreak,
+    // 会議を開始する
+    StartMeeting,
+    // 会議を終了して会議の時間を合計に加える
+    EndMeeting,
+    // 一時停止する
+    StartHold,
+    // 一時停止を終えて止めていた時間を合計に加える
+    EndHold,
+    // イベントを記録して送信する (指定した時間だけ前の時刻で)
+    Report(EventKind, EventAction, Duration),
+    // イベントをローカルにだけ記録する (サーバーには送らない)
+    Record(EventKind, EventAction, Duration),
+    // 取り消した操作の記録を消し、サーバーには打ち消すイベントを送信する
+    Retract(EventKind, EventAction),
+    // 今回の業務の集計を知らせる
+    Summarize,
+}
+
+impl TrayState {
+    // この状態で表示するアイコン
+    pub fn icon(&self) -> TrayIcon {
+        if !self.is_working {
+            TrayIcon::Idle
+        } else if self.is_paused() || self.is_on_hold {
+            TrayIcon::Paused
+        } else {
+            TrayIcon::Working
+        }
+    }
+
+    // この状態に合わせたメニュー項目のタイトルと有効/無効
+    // (起動時の復元や、メニューを変更できなかったときに表示を状態に合わせ直す用)
+    pub fn menu(&self) -> Vec<Effect> {
+        vec![
+            Effect::SetMenuTitle(
+                "attendance",
+                if self.is_working {
+                    Text::ClockOut
+                } else {
+                    Text::ClockIn
+                },
+            ),
+            Effect::SetMenuTitle(
+                "break_time",
+                if self.is_on_break {
+                    Text::EndBreak
+                } else {
+                    Text::Break
+                },
+            ),
+            Effect::SetMenuTitle(
+                "meeting",
+                if self.is_in_meeting {
+                    Text::EndMeeting
+                } else {
+                    Text::Meeting
+                },
+            ),
+            Effect::SetMenuTitle(
+                "hold",
+                if self.is_on_hold {
+                    Text::EndHold
+                } else {
+                    Text::Hold
+                },
+            ),
+            Effect::SetMenuEnabled("attendance", !self.is_paused()),
+            Effect::SetMenuEnabled("break_time", self.is_working && !self.is_in_meeting),
+            Effect::SetMenuEnabled("meeting", self.is_working && !self.is_on_break),
+            Effect::SetMenuEnabled("hold", self.is_working && !self.is_paused()),
+        ]
+    }
+
+    // 休憩中か会議中 (業務の時間を止めている)
+    fn is_paused(&self) -> bool {
+        self.is_on_break || self.is_in_meeting
+    }
+
+    // 決まった時刻に休憩を勧めてよいか (業務中で、休憩中・会議中でない)
+    pub fn accepts_break_reminder(&self) -> bool {
+        self.is_working && !self.is_paused()
+    }
+
+    // 業務開始/業務終了を切り替える (休憩中・会議中は何もしない, 一時停止中なら再開してから終了する)
+    pub fn toggle_work(&mut self) -> Vec<Effect> {
+        if self.is_paused() {
+            return Vec::new();
+        }
+
+        let mut effects = self.end_hold();
+        self.is_working = !self.is_working;
+        if self.is_working {
+            effects.extend([
+                Effect::SetMenuTitle("attendance", Text::ClockOut),
+                Effect::StartWork,
+                Effect::SetMenuEnabled("break_time", true),
+                Effect::SetMenuEnabled("meeting", true),
+                Effect::SetMenuEnabled("hold", true),
+                Effect::Report(EventKind::Work, EventAction::Start, Duration::ZERO),
+            ]);
+        } else {
+            effects.extend([
+                Effect::SetMenuTitle("attendance", Text::ClockIn),
+                Effect::SetMenuEnabled("break_time", false),
+                Effect::SetMenuEnabled("meeting", false),
+                Effect::SetMenuEnabled("hold", false),
+                Effect::EndWork,
+                Effect::Report(EventKind::Work, EventAction::End, Duration::ZERO),
+                Effect::Summarize,
+            ]);
+        }
+        effects
+    }
+
+    // 操作がないまま時間が経ったので業務を終了する (休憩中・会議中ならそれも終える)
+    // 記録する時刻はすべて ago だけ前 (最後に操作した時刻) にする
+    pub fn auto_clock_out(&mut self, ago: Duration) -> Vec<Effect> {
+        if !self.is_working {
+            return Vec::new();
+        }
+
+        let mut effects = Vec::new();
+        if self.is_on_break {
+            effects.extend(self.end_break());
+        }
+        if self.is_in_meeting {
+            effects.extend(self.toggle_meeting());
+        }
+        effects.extend(self.toggle_work());
+        effects
+            .into_iter()
+            .map(|effect| match effect {
+                Effect::Report(kind, action, _) => Effect::Report(kind, action, ago),
+                Effect::Record(kind, action, _) => Effect::Record(kind, action, ago),
+                effect => effect,
+            })
+            .collect()
+    }
+
+    // 直前の操作を取り消して before の状態に戻す (変わったものが 1 つでなければ何もしない)
+    // 記録はし直さずに Retract で取り消し、業務終了を取り消したときは集計を知らせない
+    // (業務終了を取り消しても業務開始の猶予は始めない)
+    pub fn undo(&mut self, before: TrayState) -> Vec<Effect> {
+        let working = self.is_working != before.is_working;
+        let on_break = self.is_on_break != before.is_on_break;
+        let in_meeting = self.is_in_meeting != before.is_in_meeting;
+        let on_hold = self.is_on_hold != before.is_on_hold;
+        let effects = match (working, on_break, in_meeting, on_hold) {
+            (true, false, false, false) => self.toggle_work(),
+            (false, true, false, false) => self.toggle_break(),
+            (false, false, true, false) => self.toggle_meeting(),
+            (false, false, false, true) => self.toggle_hold(),
+            _ => Vec::new(),
+        };
+        effects
+            .into_iter()
+            .filter(|effect| *effect != Effect::Summarize)
+            .map(|effect| match effect {
+                Effect::Report(kind, action, _) | Effect::Record(kind, action, _) => {
+                    Effect::Retract(kind, action)
+                }
+                Effect::StartWork => Effect::ResumeWork,
+                effect => effect,
+            })
+            .collect()
+    }
+
+    // 休憩の開始/終了を切り替える (業務中でなければ何もしない)
+    pub fn toggle_break(&mut self) -> Vec<Effect> {
+        if self.is_on_break {
+            self.end_break()
+        } else {
+            self.start_break(Duration::ZERO)
+        }
+    }
+
+    // 休憩を開始する (ago だけ前から休憩していたことにする, 会議中は何もしない)
+    // 一時停止中なら再開してから休憩にする
+    pub fn start_break(&mut self, ago: Duration) -> Vec<Effect> {
+        if !self.is_working || self.is_paused() {
+            return Vec::new();
+        }
+
+        let mut effects = self.end_hold();
+        self.is_on_break = true;
+        effects.extend([
+            Effect::SetMenuTitle("break_time", Text::EndBreak),
+            Effect::StartBreak(ago),
+            Effect::SetMenuEnabled("attendance", false),
+            Effect::SetMenuEnabled("meeting", false),
+            Effect::SetMenuEnabled("hold", false),
+            Effect::Report(EventKind::Break, EventAction::Start, ago),
+        ]);
+        effects
+    }
+
+    // 休憩を終了する
+    fn end_break(&mut self) -> Vec<Effect> {
+        self.is_on_break = false;
+        vec![
+            Effect::SetMenuTitle("break_time", Text::Break),
+            Effect::SetMenuEnabled("attendance", true),
+            Effect::SetMenuEnabled("meeting", true),
+            Effect::SetMenuEnabled("hold", true),
+            Effect::EndBreak,
+            Effect::Report(EventKind::Break, EventAction::End, Duration::ZERO),
+        ]
+    }
+
+    // 会議の開始/終了を切り替える (業務中でなければ、また休憩中は何もしない)
+    // 一時停止中なら再開してから会議にする
+    pub fn toggle_meeting(&mut self) -> Vec<Effect> {
+        if self.is_in_meeting {
+            self.is_in_meeting = false;
+            return vec![
+                Effect::SetMenuTitle("meeting", Text::Meeting),
+                Effect::SetMenuEnabled("attendance", true),
+                Effect::SetMenuEnabled("break_time", true),
+                Effect::SetMenuEnabled("hold", true),
+                Effect::EndMeeting,
+                Effect::Report(EventKind::Meeting, EventAction::End, Duration::ZERO),
+            ];
+        }
+        if !self.is_working || self.is_on_break {
+            return Vec::new();
+        }
+
+        let mut effects = self.end_hold();
+        self.is_in_meeting = true;
+        effects.extend([
+            Effect::SetMenuTitle("meeting", Text::EndMeeting),
+            Effect::StartMeeting,
+            Effect::SetMenuEnabled("attendance", false),
+            Effect::SetMenuEnabled("break_time", false),
+            Effect::SetMenuEnabled("hold", false),
+            Effect::Report(EventKind::Meeting, EventAction::Start, Duration::ZERO),
+        ]);
+        effects
+    }
+
+    // 一時停止/再開を切り替える (業務中でなければ、また休憩中・会議中は何もしない)
+    // 一時停止はローカルにだけ記録し、サーバーには送らない
+    pub fn toggle_hold(&mut self) -> Vec<Effect> {
+        if self.is_on_hold {
+            return self.end_hold();
+        }
+        if !self.is_working || self.is_paused() {
+            return Vec::new();
+        }
+
+        self.is_on_hold = true;
+        vec![
+            Effect::SetMenuTitle("hold", Text::EndHold),
+            Effect::StartHold,
+            Effect::Record(EventKind::Hold, EventAction::Start, Duration::ZERO),
+        ]
+    }
+
+    // 一時停止中なら再開する (一時停止中でなければ何もしない)
+    fn end_hold(&mut self) -> Vec<Effect> {
+        if !self.is_on_hold {
+            return Vec::new();
+        }
+
+        self.is_on_hold = false;
+        vec![
+            Effect::SetMenuTitle("hold", Text::Hold),
+            Effect::EndHold,
+            Effect::Record(EventKind::Hold, EventAction::End, Duration::ZERO),
+        ]
+    }
+
+    // トレイアイコンのクリック (休憩中・会議中・一時停止中ならそれを終え、それ以外は業務を切り替える)
+    pub fn left_click(&mut self) -> Vec<Effect> {
+        if self.is_on_break {
+            self.toggle_break()
+        } else if self.is_in_meeting {
+            self.toggle_meeting()
+        } else if self.is_on_hold {
+            self.toggle_hold()
+        } else {
+            self.toggle_work()
+        }
+    }
+}
+
+// メニューに表示する文言 (設定の labels で変えられるものはそれを使う)
+pub fn menu_label(config: &Config, text: Text) -> &str {
+    config.labels.menu(text).unwrap_or(t(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn break_cannot_start_when_not_working() {
+        let mut state = TrayState::default();
+        assert!(state.toggle_break().is_empty());
+        assert!(state.start_break(Duration::from_secs(60)).is_empty());
+        assert_eq!(state, TrayState::default());
+    }
+
+    #[test]
+    fn break_starts_while_working() {
+        let mut state = TrayState {
+            is_working: true,
+            ..Default::default()
+        };
+        let effects = state.toggle_break();
+        assert!(state.is_on_break);
+        assert!(effects.contains(&Effect::StartBreak(Duration::ZERO)));
+    }
+
+    // 業務中の状態
+    fn working() -> TrayState {
+        TrayState {
+            is_working: true,
+            ..Default::default()
+        }
+    }
+
+    // サーバーに送るイベント
+    fn reports(effects: &[Effect]) -> Vec<(EventKind, EventAction)> {
+        effects
+            .iter()
+            .filter_map(|effect| match *effect {
+                Effect::Report(kind, action, _) => Some((kind, action)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clock_in_and_out() {
+        let mut state = TrayState::default();
+        let effects = state.toggle_work();
+        assert_eq!(state, working());
+        assert_eq!(
+            effects,
+            [
+                Effect::SetMenuTitle("attendance", Text::ClockOut),
+                Effect::StartWork,
+                Effect::SetMenuEnabled("break_time", true),
+                Effect::SetMenuEnabled("meeting", true),
+                Effect::SetMenuEnabled("hold", true),
+                Effect::Report(EventKind::Work, EventAction::Start, Duration::ZERO),
+            ]
+        );
+
+        let effects = state.toggle_work();
+        assert_eq!(state, TrayState::default());
+        assert_eq!(
+            effects,
+            [
+                Effect::SetMenuTitle("attendance", Text::ClockIn),
+                Effect::SetMenuEnabled("break_time", false),
+                Effect::SetMenuEnabled("meeting", false),
+                Effect::SetMenuEnabled("hold", false),
+                Effect::EndWork,
+                Effect::Report(EventKind::Work, EventAction::End, Duration::ZERO),
+                Effect::Summarize,
+            ]
+        );
+    }
+
+    #[test]
+    fn break_and_resume() {
+        let mut state = working();
+        let effects = state.toggle_break();
+        assert!(state.is_on_break);
+        assert_eq!(
+            effects,
+            [
+                Effect::SetMenuTitle("break_time", Text::EndBreak),
+                Effect::StartBreak(Duration::ZERO),
+                Effect::SetMenuEnabled("attendance", false),
+                Effect::SetMenuEnabled("meeting", false),
+                Effect::SetMenuEnabled("hold", false),
+                Effect::Report(EventKind::Break, EventAction::Start, Duration::ZERO),
+            ]
+        );
+
+        // 休憩中は業務を終了できない
+        assert!(state.toggle_work().is_empty());
+        assert!(state.toggle_meeting().is_empty());
+
+        let effects = state.toggle_break();
+        assert_eq!(state, working());
+        assert_eq!(
+            effects,
+            [
+                Effect::SetMenuTitle("break_time", Text::Break),
+                Effect::SetMenuEnabled("attendance", true),
+                Effect::SetMenuEnabled("meeting", true),
+                Effect::SetMenuEnabled("hold", true),
+                Effect::EndBreak,
+                Effect::Report(EventKind::Break, EventAction::End, Duration::ZERO),
+            ]
+        );
+    }
+
+    #[test]
+    fn meeting_start_and_end() {
+        let mut state = working();
+        let effects = state.toggle_meeting();
+        assert!(state.is_in_meeting);
+        assert!(effects.contains(&Effect::StartMeeting));
+        assert_eq!(
+            reports(&effects),
+            [(EventKind::Meeting, EventAction::Start)]
+        );
+
+        // 会議中は休憩も業務の終了もできない
+        assert!(state.toggle_break().is_empty());
+        assert!(state.toggle_work().is_empty());
+
+        let effects = state.toggle_meeting();
+        assert_eq!(state, working());
+        assert!(effects.contains(&Effect::EndMeeting));
+        assert_eq!(reports(&effects), [(EventKind::Meeting, EventAction::End)]);
+    }
+
+    #[test]
+    fn meeting_cannot_start_when_not_working() {
+        let mut state = TrayState::default();
+        assert!(state.toggle_meeting().is_empty());
+        assert_eq!(state, TrayState::default());
+    }
+
+    #[test]
+    fn hold_is_recorded_but_never_reported() {
+        let mut state = working();
+        let effects = state.toggle_hold();
+        assert!(state.is_on_hold);
+        assert_eq!(
+            effects,
+            [
+                Effect::SetMenuTitle("hold", Text::EndHold),
+                Effect::StartHold,
+                Effect::Record(EventKind::Hold, EventAction::Start, Duration::ZERO),
+            ]
+        );
+
+        let effects = state.toggle_hold();
+        assert_eq!(state, working());
+        assert_eq!(
+            effects,
+            [
+                Effect::SetMenuTitle("hold", Text::Hold),
+                Effect::EndHold,
+                Effect::Record(EventKind::Hold, EventAction::End, Duration::ZERO),
+            ]
+        );
+        assert!(reports(&effects).is_empty());
+    }
+
+    #[test]
+    fn break_meeting_or_clock_out_ends_hold_first() {
+        for transition in [
+            TrayState::toggle_break,
+            TrayState::toggle_meeting,
+            TrayState::toggle_work,
+        ] {
+            let mut state = working();
+            state.toggle_hold();
+            let effects = transition(&mut state);
+            assert!(!state.is_on_hold);
+            assert_eq!(effects[1], Effect::EndHold);
+            assert!(effects.contains(&Effect::Record(
+                EventKind::Hold,
+                EventAction::End,
+                Duration::ZERO
+            )));
+            // 一時停止はサーバーに送らない
+            assert!(reports(&effects)
+                .iter()
+                .all(|(kind, _)| *kind != EventKind::Hold));
+        }
+    }
+
+    #[test]
+    fn left_click_ends_pause_or_toggles_work() {
+        let mut state = TrayState::default();
+        assert!(state.left_click().contains(&Effect::StartWork));
+
+        state.toggle_break();
+        assert!(state.left_click().contains(&Effect::EndBreak));
+        assert_eq!(state, working());
+
+        state.toggle_meeting();
+        assert!(state.left_click().contains(&Effect::EndMeeting));
+        assert_eq!(state, working());
+
+        state.toggle_hold();
+        assert!(state.left_click().contains(&Effect::EndHold));
+        assert_eq!(state, working());
+
+        assert!(state.left_click().contains(&Effect::EndWork));
+        assert_eq!(state, TrayState::default());
+    }
+
+    #[test]
+    fn auto_clock_out_backdates_every_event() {
+        let ago = Duration::from_secs(30 * 60);
+        let mut state = working();
+        state.toggle_break();
+        let effects = state.auto_clock_out(ago);
+        assert_eq!(state, TrayState::default());
+        assert_eq!(
+            reports(&effects),
+            [
+                (EventKind::Break, EventAction::End),
+                (EventKind::Work, EventAction::End),
+            ]
+        );
+        assert!(effects.iter().all(|effect| match effect {
+            Effect::Report(_, _, at) | Effect::Record(_, _, at) => *at == ago,
+            _ => true,
+        }));
+        // 業務中でなければ何もしない
+        assert!(state.auto_clock_out(ago).is_empty());
+    }
+
+    #[test]
+    fn icon_follows_state() {
+        assert_eq!(TrayState::default().icon(), TrayIcon::Idle);
+        let mut state = working();
+        assert_eq!(state.icon(), TrayIcon::Working);
+        state.toggle_hold();
+        assert_eq!(state.icon(), TrayIcon::Paused);
+        state.toggle_break();
+        assert_eq!(state.icon(), TrayIcon::Paused);
+    }
+
+    // 表示中のメニューに effects のメニューの変更を反映する
+    fn apply_menu(menu: &mut [Effect], effects: &[Effect]) {
+        for effect in effects {
+            let same_item = |shown: &Effect| match (shown, effect) {
+                (Effect::SetMenuTitle(a, _), Effect::SetMenuTitle(b, _))
+                | (Effect::SetMenuEnabled(a, _), Effect::SetMenuEnabled(b, _)) => a == b,
+                _ => false,
+            };
+            if let Some(shown) = menu.iter_mut().find(|shown| same_item(shown)) {
+                *shown = *effect;
+            }
+        }
+    }
+
+    #[test]
+    fn menu_changes_keep_the_menu_in_sync_with_the_state() {
+        type Step = fn(&mut TrayState) -> Vec<Effect>;
+        let steps: [(&str, Step); 7] = [
+            ("toggle_work", TrayState::toggle_work),
+            ("toggle_break", TrayState::toggle_break),
+            ("toggle_meeting", TrayState::toggle_meeting),
+            ("toggle_hold", TrayState::toggle_hold),
+            ("left_click", TrayState::left_click),
+            ("start_break", |state| {
+                state.start_break(Duration::from_secs(60))
+            }),
+            ("auto_clock_out", |state| {
+                state.auto_clock_out(Duration::ZERO)
+            }),
+        ];
+
+        // 業務を開始していない状態から 4 回までの操作でたどり着くすべての状態で確かめる
+        let mut paths = vec![(
+            Vec::new(),
+            TrayState::default(),
+            TrayState::default().menu(),
+        )];
+        for _ in 0..4 {
+            let mut next = Vec::new();
+            for (path, state, menu) in &paths {
+                for (name, step) in steps {
+                    let (mut state, mut menu) = (*state, menu.clone());
+                    let before = state;
+                    apply_menu(&mut menu, &step(&mut state));
+                    let mut path = path.clone();
+                    path.push(name);
+                    assert_eq!(menu, state.menu(), "{:?}", path);
+
+                    // 取り消しても表示は状態と一致する
+                    let (mut undone, mut undone_menu) = (state, menu.clone());
+                    apply_menu(&mut undone_menu, &undone.undo(before));
+                    assert_eq!(undone_menu, undone.menu(), "{:?} → undo", path);
+
+                    next.push((path, state, menu));
+                }
+            }
+            paths = next;
+        }
+    }
+
+    #[test]
+    fn undo_reverts_each_transition_without_recording_again() {
+        let mut on_break = working();
+        on_break.toggle_break();
+        let mut in_meeting = working();
+        in_meeting.toggle_meeting();
+        let mut on_hold = working();
+        on_hold.toggle_hold();
+
+        type Step = fn(&mut TrayState) -> Vec<Effect>;
+        let cases: [(TrayState, Step); 8] = [
+            (TrayState::default(), TrayState::toggle_work),
+            (working(), TrayState::toggle_work),
+            (working(), TrayState::toggle_break),
+            (on_break, TrayState::toggle_break),
+            (working(), TrayState::toggle_meeting),
+            (in_meeting, TrayState::toggle_meeting),
+            (working(), TrayState::toggle_hold),
+            (on_hold, TrayState::toggle_hold),
+        ];
+        for (before, step) in cases {
+            let mut state = before;
+            let done = step(&mut state);
+            let undone = state.undo(before);
+            assert_eq!(state, before);
+
+            // 記録したイベントを新しく記録せずに、打ち消すイベントで取り消す
+            let recorded: Vec<_> = done
+                .iter()
+                .filter_map(|effect| match *effect {
+                    Effect::Report(kind, action, _) | Effect::Record(kind, action, _) => {
+                        Some(Effect::Retract(kind, action.opposite()))
+                    }
+                    _ => None,
+                })
+                .collect();
+            let retracted: Vec<_> = undone
+                .iter()
+                .filter(|effect| matches!(effect, Effect::Retract(..)))
+                .copied()
+                .collect();
+            assert_eq!(retracted.len(), 1, "{:?}", before);
+            assert!(recorded.contains(&retracted[0]), "{:?}", before);
+            assert!(reports(&undone).is_empty());
+            // 業務終了を取り消しても集計は知らせず、猶予も始めない
+            assert!(!undone.contains(&Effect::Summarize));
+            assert!(!undone.contains(&Effect::StartWork));
+
+            // 続けて取り消しても何もしない
+            assert!(state.undo(before).is_empty());
+            assert_eq!(state, before);
+        }
+    }
+
+    #[test]
+    fn undo_does_nothing_after_more_than_one_change() {
+        // 一時停止中に業務を終了すると一時停止も終わるので取り消せない
+        let mut before = working();
+        before.toggle_hold();
+        let mut state = before;
+        state.toggle_work();
+        assert!(state.undo(before).is_empty());
+        assert_eq!(state, TrayState::default());
+    }
+
+    #[test]
+    fn break_reminders_are_suppressed_unless_working() {
+        let mut state = TrayState::default();
+        assert!(!state.accepts_break_reminder());
+        state.toggle_work();
+        assert!(state.accepts_break_reminder());
+
+        // 休憩中・会議中は勧めない
+        state.toggle_break();
+        assert!(!state.accepts_break_reminder());
+        state.toggle_break();
+        assert!(state.accepts_break_reminder());
+        state.toggle_meeting();
+        assert!(!state.accepts_break_reminder());
+        state.toggle_meeting();
+
+        // 一時停止中は休憩を勧める
+        state.toggle_hold();
+        assert!(state.accepts_break_reminder());
+        state.toggle_hold();
+        state.toggle_work();
+        assert!(!state.accepts_break_reminder());
+    }
+}
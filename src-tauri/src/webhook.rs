@@ -0,0 +1,101 @@
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+use crate::config::{redact_url, Config};
+use crate::sender::{self, Sender};
+
+// Webhook に送るメッセージ ("多田さんが業務開始しました 09:12")
+pub fn message(name: &str, status: &str, at: &DateTime<Local>) -> String {
+    format!(
+        "{}さんが{}しました {}",
+        name,
+        status.replace(' ', ""),
+        at.format("%H:%M")
+    )
+}
+
+// 設定された Webhook にメッセージを送る (設定されていなければ何もしない)
+// 記録の送信とは別のスレッドで送るので、失敗しても記録の送信は止めない
+pub fn post_all(app: &AppHandle, config: &Config, text: String) {
+    let urls = targets(config);
+    if urls.is_empty() {
+        return;
+    }
+    if config.dry_run {
+        for url in &urls {
+            info!("[dry run] Webhook {} {:?}", redact_url(url), text);
+        }
+        return;
+    }
+
+    let proxy = match config.proxy() {
+        Ok(proxy) => proxy.and_then(|url| sender::proxy(config, &url).ok()),
+        Err(e) => {
+            warn!("Webhook に送信しませんでした: {}", e);
+            return;
+        }
+    };
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let app = app.clone();
+    thread::spawn(move || {
+        let Some(sender) = app.try_state::<Sender>() else {
+            return;
+        };
+        for url in urls {
+            if let Err(e) = sender.post_webhook(&url, &text, timeout, proxy.clone()) {
+                warn!(
+                    "Webhook に送信できませんでした ({}): {}",
+                    redact_url(&url),
+                    e
+                );
+            }
+        }
+    });
+}
+
+// 送信先の Webhook (空のものは除く)
+fn targets(config: &Config) -> Vec<String> {
+    config
+        .webhook_urls
+        .iter()
+        .filter(|url| !url.trim().is_empty())
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    #[test]
+    fn message_names_person_status_and_time() {
+        let at = Local.with_ymd_and_hms(2024, 4, 1, 9, 12, 30).unwrap();
+        assert_eq!(
+            message("多田", "業務 開始", &at),
+            "多田さんが業務開始しました 09:12"
+        );
+        assert_eq!(
+            message("多田", "業務 終了", &at),
+            "多田さんが業務終了しました 09:12"
+        );
+    }
+
+    #[test]
+    fn nothing_is_posted_without_webhooks() {
+        let mut config = Config::default();
+        assert!(targets(&config).is_empty());
+        config.webhook_urls = vec![String::new(), "  ".to_string()];
+        assert!(targets(&config).is_empty());
+
+        config
+            .webhook_urls
+            .push("https://hooks.slack.com/services/T/B/X".to_string());
+        assert_eq!(targets(&config), ["https://hooks.slack.com/services/T/B/X"]);
+    }
+}
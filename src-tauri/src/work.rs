@@ -0,0 +1,1265 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::flusher::{publish_event, record_event, send_status};
+use crate::format::{format_clock, format_duration};
+use crate::i18n::{t_with, Text};
+use crate::notification::notify_session_summary;
+use crate::report;
+use crate::state::{self, PersistedState};
+use crate::store::{today_totals, EventAction, EventKind, EventStore};
+use crate::timer::{
+    format_break_title, format_elapsed, format_hold_title, format_meeting_title, refresh_title,
+    show_elapsed, start_timer, stop_timer, Timer,
+};
+use crate::tray::{self, menu_label, Effect, TrayState};
+
+// トレイのイベントとタイマーのスレッドで共有する勤務状態
+#[derive(Clone, Default)]
+pub struct WorkState {
+    pub is_working: Arc<AtomicBool>,    // 業務開始状態のフラグ
+    pub is_on_break: Arc<AtomicBool>,   // 休憩状態のフラグ
+    pub is_in_meeting: Arc<AtomicBool>, // 会議中のフラグ
+    pub is_on_hold: Arc<AtomicBool>,    // 一時停止中のフラグ
+    pub elapsed_secs: Arc<AtomicU64>,   // 業務の経過時間 (秒)
+    pub break_started: Arc<Mutex<Option<Instant>>>, // 休憩の開始時刻
+    pub meeting_started: Arc<Mutex<Option<Instant>>>, // 会議の開始時刻
+    pub hold_started: Arc<Mutex<Option<Instant>>>, // 一時停止した時刻
+    pub started: Arc<Mutex<Option<Instant>>>, // 業務の開始時刻
+    pub started_at: Arc<Mutex<Option<DateTime<Local>>>>, // 業務を開始した時刻 (表示用)
+    pub total_break_secs: Arc<AtomicU64>, // 業務中の休憩の合計 (秒)
+    pub total_meeting_secs: Arc<AtomicU64>, // 業務中の会議の合計 (秒)
+    pub total_hold_secs: Arc<AtomicU64>, // 業務中の一時停止の合計 (秒)
+    pub transition_lock: Arc<Mutex<()>>, // 状態遷移を 1 つずつ行うためのロック
+    pub timer: Arc<Mutex<Option<Timer>>>, // 動いているタイマーのスレッド
+    pub stretch_started: Arc<Mutex<Option<Instant>>>, // 休憩せずに働き始めた時刻
+    pub break_ended_at: Arc<Mutex<Option<DateTime<Local>>>>, // 丸めた休憩の終了時刻 (記録用)
+    pub break_reason: Arc<Mutex<Option<String>>>, // メニューで選んだ休憩の理由
+    pub note: Arc<Mutex<Option<String>>>, // 次の業務開始に付けるメモ
+    pub lock_break: Arc<AtomicBool>,    // 画面のロックで始めた休憩中か
+    pub undo: Arc<Mutex<Option<Snapshot>>>, // 直前の操作の前の状態 (元に戻す用)
+    pub grace: Arc<Mutex<Option<Grace>>>, // 業務開始の猶予 (猶予中と、猶予中に終了した後)
+}
+
+// 今回の業務の時間の内訳 (業務終了時の集計用)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionTimes {
+    pub worked: Duration,
+    pub break_time: Duration,
+    pub meeting_time: Duration,
+    pub hold_time: Duration,
+}
+
+impl SessionTimes {
+    // 業務を開始してからの時間 (実働・休憩・会議・一時停止の合計)
+    pub fn total(&self) -> Duration {
+        self.worked + self.break_time + self.meeting_time + self.hold_time
+    }
+}
+
+// 業務開始の猶予 (猶予が過ぎるまで業務の開始を記録・送信しない)
+#[derive(Debug, Clone)]
+pub struct Grace {
+    pub until: Instant,       // 時間を数え始める時刻
+    pub note: Option<String>, // 業務開始に付けるメモ
+    pub cancelled: bool,      // 猶予中に業務を終了した (業務の終了も記録しない)
+}
+
+// 元に戻すために覚えておく、操作する前の勤務状態と時間
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub state: TrayState,
+    pub started: Option<Instant>,
+    pub started_at: Option<DateTime<Local>>,
+    pub break_started: Option<Instant>,
+    pub meeting_started: Option<Instant>,
+    pub hold_started: Option<Instant>,
+    pub stretch_started: Option<Instant>,
+    pub break_reason: Option<String>,
+    pub total_break_secs: u64,
+    pub total_meeting_secs: u64,
+    pub total_hold_secs: u64,
+    pub elapsed_secs: u64,
+}
+
+impl WorkState {
+    // 現在の休憩の経過時間 (休憩中でなければ None)
+    pub fn break_duration(&self) -> Option<Duration> {
+        self.break_started
+            .lock()
+            .unwrap()
+            .map(|started| started.elapsed())
+    }
+
+    // 現在の会議の経過時間 (会議中でなければ None)
+    pub fn meeting_duration(&self) -> Option<Duration> {
+        self.meeting_started
+            .lock()
+            .unwrap()
+            .map(|started| started.elapsed())
+    }
+
+    // 現在の一時停止の経過時間 (一時停止中でなければ None)
+    pub fn hold_duration(&self) -> Option<Duration> {
+        self.hold_started
+            .lock()
+            .unwrap()
+            .map(|started| started.elapsed())
+    }
+
+    // 業務開始の猶予の残り (猶予中でなければ None)
+    pub fn grace_left(&self) -> Option<Duration> {
+        self.grace
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|grace| !grace.cancelled)
+            .map(|grace| grace.until.saturating_duration_since(Instant::now()))
+    }
+
+    // トレイの操作で変わる勤務状態
+    pub fn tray_state(&self) -> TrayState {
+        TrayState {
+            is_working: self.is_working.load(Ordering::Relaxed),
+            is_on_break: self.is_on_break.load(Ordering::Relaxed),
+            is_in_meeting: self.is_in_meeting.load(Ordering::Relaxed),
+            is_on_hold: self.is_on_hold.load(Ordering::Relaxed),
+        }
+    }
+
+    // 休憩せずに働き続けている時間 (休憩中は 0)
+    pub fn stretch_duration(&self) -> Duration {
+        self.stretch_started
+            .lock()
+            .unwrap()
+            .map(|started| started.elapsed())
+            .unwrap_or_default()
+    }
+
+    // 休憩・会議・一時停止の合計 (現在の休憩・会議・一時停止を含む)
+    pub fn paused_duration(&self) -> Duration {
+        let total_break = Duration::from_secs(self.total_break_secs.load(Ordering::Relaxed));
+        let total_meeting = Duration::from_secs(self.total_meeting_secs.load(Ordering::Relaxed));
+        let total_hold = Duration::from_secs(self.total_hold_secs.load(Ordering::Relaxed));
+        total_break
+            + total_meeting
+            + total_hold
+            + self.break_duration().unwrap_or_default()
+            + self.meeting_duration().unwrap_or_default()
+            + self.hold_duration().unwrap_or_default()
+    }
+
+    // 休憩・会議・一時停止を除いた業務の経過時間
+    // (1 秒ずつ足していくと遅延でずれるので、開始時刻からの実時間で計算する)
+    pub fn work_duration(&self) -> Duration {
+        let Some(started) = *self.started.lock().unwrap() else {
+            return Duration::ZERO;
+        };
+        started.elapsed().saturating_sub(self.paused_duration())
+    }
+
+    // 今回の業務の時間の内訳 (終えていない休憩・会議・一時停止も含める)
+    // 経過時間は休憩中・会議中・一時停止中に止まっているので、そのまま実働時間になる
+    pub fn session_times(&self) -> SessionTimes {
+        let total = |secs: &AtomicU64, current: Option<Duration>| {
+            Duration::from_secs(secs.load(Ordering::Relaxed)) + current.unwrap_or_default()
+        };
+        SessionTimes {
+            worked: Duration::from_secs(self.elapsed_secs.load(Ordering::Relaxed)),
+            break_time: total(&self.total_break_secs, self.break_duration()),
+            meeting_time: total(&self.total_meeting_secs, self.meeting_duration()),
+            hold_time: total(&self.total_hold_secs, self.hold_duration()),
+        }
+    }
+
+    // 今の時間の記録を state と一緒に覚えておく
+    pub fn snapshot(&self, state: TrayState) -> Snapshot {
+        Snapshot {
+            state,
+            started: *self.started.lock().unwrap(),
+            started_at: *self.started_at.lock().unwrap(),
+            break_started: *self.break_started.lock().unwrap(),
+            meeting_started: *self.meeting_started.lock().unwrap(),
+            hold_started: *self.hold_started.lock().unwrap(),
+            stretch_started: *self.stretch_started.lock().unwrap(),
+            break_reason: self.break_reason.lock().unwrap().clone(),
+            total_break_secs: self.total_break_secs.load(Ordering::Relaxed),
+            total_meeting_secs: self.total_meeting_secs.load(Ordering::Relaxed),
+            total_hold_secs: self.total_hold_secs.load(Ordering::Relaxed),
+            elapsed_secs: self.elapsed_secs.load(Ordering::Relaxed),
+        }
+    }
+
+    // 取り消せる操作を取り出す (続けて取り消そうとしても 2 回目は None)
+    pub fn take_undo(&self) -> Option<Snapshot> {
+        self.undo.lock().unwrap().take()
+    }
+
+    // 覚えておいた時間の記録に戻す (取り消した操作の間の時間も業務を続けていたことになる)
+    pub fn restore(&self, snapshot: &Snapshot) {
+        *self.started.lock().unwrap() = snapshot.started;
+        *self.break_started.lock().unwrap() = snapshot.break_started;
+        *self.meeting_started.lock().unwrap() = snapshot.meeting_started;
+        *self.hold_started.lock().unwrap() = snapshot.hold_started;
+        *self.stretch_started.lock().unwrap() = snapshot.stretch_started;
+        *self.break_reason.lock().unwrap() = snapshot.break_reason.clone();
+        self.total_break_secs
+            .store(snapshot.total_break_secs, Ordering::Relaxed);
+        self.total_meeting_secs
+            .store(snapshot.total_meeting_secs, Ordering::Relaxed);
+        self.total_hold_secs
+            .store(snapshot.total_hold_secs, Ordering::Relaxed);
+        self.elapsed_secs
+            .store(snapshot.elapsed_secs, Ordering::Relaxed);
+    }
+
+    // 開始時刻を gap だけ前にずらす (スリープ中も時間が経っていたことにする)
+    // 休憩中・会議中・一時停止中ならその開始時刻も一緒にずらすので、そちらの時間のほうが伸びる
+    pub fn shift_back(&self, gap: Duration) {
+        for instant in [
+            &self.started,
+            &self.break_started,
+            &self.meeting_started,
+            &self.hold_started,
+            &self.stretch_started,
+        ] {
+            let mut instant = instant.lock().unwrap();
+            if let Some(shifted) = instant.and_then(|i| i.checked_sub(gap)) {
+                *instant = Some(shifted);
+            }
+        }
+    }
+
+    // 業務の開始時刻を delta_secs 秒だけ前 (負なら後) にずらして経過時間を調整する
+    // (業務中でない場合や経過時間が負になる場合は調整せずに false を返す)
+    pub fn adjust(&self, delta_secs: i64) -> bool {
+        let paused = self.paused_duration();
+        let mut started = self.started.lock().unwrap();
+        let Some(instant) = *started else {
+            return false;
+        };
+
+        let delta = Duration::from_secs(delta_secs.unsigned_abs());
+        let adjusted = if delta_secs >= 0 {
+            instant.checked_sub(delta)
+        } else if instant.elapsed().saturating_sub(paused) >= delta {
+            instant.checked_add(delta)
+        } else {
+            None
+        };
+        let Some(adjusted) = adjusted else {
+            return false;
+        };
+        *started = Some(adjusted);
+
+        if let Some(started_at) = self.started_at.lock().unwrap().as_mut() {
+            *started_at -= chrono::Duration::seconds(delta_secs);
+        }
+        self.elapsed_secs.store(
+            adjusted.elapsed().saturating_sub(paused).as_secs(),
+            Ordering::Relaxed,
+        );
+        true
+    }
+
+    // ago だけ前から休憩を始める (休憩中は続けて働いている時間を数えない)
+    pub fn start_break(&self, ago: Duration) {
+        let started = Instant::now().checked_sub(ago).unwrap_or_else(Instant::now);
+        *self.break_started.lock().unwrap() = Some(started);
+        *self.stretch_started.lock().unwrap() = None;
+    }
+
+    // 休憩を終え、round で丸めた休憩の時間を休憩の合計に足す (休憩の時間と丸めた時間を返す)
+    // 続けて働いている時間はここから数え直す
+    pub fn end_break(
+        &self,
+        round: impl FnOnce(Duration) -> Duration,
+    ) -> Option<(Duration, Duration)> {
+        *self.stretch_started.lock().unwrap() = Some(Instant::now());
+        let break_time = self.break_started.lock().unwrap().take()?.elapsed();
+        let rounded = round(break_time);
+        self.total_break_secs
+            .fetch_add(rounded.as_secs(), Ordering::Relaxed);
+        Some((break_time, rounded))
+    }
+
+    // 業務を開始する (grace があれば猶予が過ぎて end_grace を呼ぶまで時間を数えない)
+    pub fn start_work(&self, grace: Option<Duration>) {
+        self.total_break_secs.store(0, Ordering::Relaxed);
+        self.total_meeting_secs.store(0, Ordering::Relaxed);
+        self.total_hold_secs.store(0, Ordering::Relaxed);
+        *self.grace.lock().unwrap() = grace.map(|grace| Grace {
+            until: Instant::now() + grace,
+            note: None,
+            cancelled: false,
+        });
+        if grace.is_some() {
+            *self.started.lock().unwrap() = None;
+            *self.stretch_started.lock().unwrap() = None;
+            self.elapsed_secs.store(0, Ordering::Relaxed);
+        } else {
+            self.resume_from(Duration::ZERO);
+            *self.stretch_started.lock().unwrap() = Some(Instant::now());
+        }
+    }
+
+    // 猶予中に業務を終了したので猶予を取り消す (猶予中だったかを返す)
+    pub fn cancel_grace(&self) -> bool {
+        match self.grace.lock().unwrap().as_mut() {
+            Some(grace) => {
+                grace.cancelled = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 業務の開始・終了の記録と送信を猶予が過ぎるまで待つか
+    // (猶予中の業務の開始は猶予が過ぎてから記録し、猶予中に終了したら終了も記録しない)
+    pub fn defer_to_grace(&self, kind: EventKind, action: EventAction) -> bool {
+        if kind != EventKind::Work {
+            return false;
+        }
+        let mut grace = self.grace.lock().unwrap();
+        let Some(grace) = grace.as_mut() else {
+            return false;
+        };
+        // 業務開始に付けるメモは猶予が過ぎたときに使う
+        if action == EventAction::Start {
+            grace.note = self.note.lock().unwrap().take();
+        }
+        true
+    }
+
+    // 猶予が過ぎたので、猶予が終わった時刻から時間を数え始める
+    // (猶予が終わってから経った時間と業務開始に付けるメモを返す, 猶予中でないか取り消していたら None)
+    pub fn end_grace(&self) -> Option<(Duration, Option<String>)> {
+        let grace = self
+            .grace
+            .lock()
+            .unwrap()
+            .take_if(|grace| !grace.cancelled)?;
+        let late = Instant::now().saturating_duration_since(grace.until);
+        self.resume_from(late);
+        *self.stretch_started.lock().unwrap() = Instant::now().checked_sub(late);
+        Some((late, grace.note))
+    }
+
+    // 記録・送信するイベントに付けるメモ
+    // (メモは業務開始にだけ付け、休憩には選んだ理由を付ける, 休憩を終えたら理由を忘れる)
+    pub fn event_note(&self, kind: EventKind, action: EventAction) -> Option<String> {
+        match (kind, action) {
+            (EventKind::Work, EventAction::Start) => self.note.lock().unwrap().take(),
+            (EventKind::Break, EventAction::Start) => self.break_reason.lock().unwrap().clone(),
+            (EventKind::Break, EventAction::End) => self.break_reason.lock().unwrap().take(),
+            _ => None,
+        }
+    }
+
+    // 経過時間 elapsed から業務を再開する (開始時刻を逆算しておく)
+    pub fn resume_from(&self, elapsed: Duration) {
+        let started = Instant::now()
+            .checked_sub(elapsed + self.paused_duration())
+            .unwrap_or_else(Instant::now);
+        *self.started.lock().unwrap() = Some(started);
+        self.elapsed_secs
+            .store(elapsed.as_secs(), Ordering::Relaxed);
+    }
+
+    // 再起動をまたいで保存する勤務状態
+    pub fn persisted(&self) -> PersistedState {
+        PersistedState {
+            elapsed_secs: self.elapsed_secs.load(Ordering::Relaxed),
+            // 猶予中はまだ何も記録していないので、再起動したら業務中でない状態に戻す
+            is_working: self.is_working.load(Ordering::Relaxed) && self.grace_left().is_none(),
+            is_on_break: self.is_on_break.load(Ordering::Relaxed),
+            total_break_secs: self.total_break_secs.load(Ordering::Relaxed),
+            is_in_meeting: self.is_in_meeting.load(Ordering::Relaxed),
+            total_meeting_secs: self.total_meeting_secs.load(Ordering::Relaxed),
+            started_at: *self.started_at.lock().unwrap(),
+            break_started_at: self.break_duration().map(time_ago),
+            meeting_started_at: self.meeting_duration().map(time_ago),
+            break_reason: self.break_reason.lock().unwrap().clone(),
+            is_on_hold: self.is_on_hold.load(Ordering::Relaxed),
+            total_hold_secs: self.total_hold_secs.load(Ordering::Relaxed),
+            hold_started_at: self.hold_duration().map(time_ago),
+        }
+    }
+
+    // 保存した勤務状態に戻し、戻したトレイの状態を返す (業務中でなければ何もせずに None)
+    pub fn restore_persisted(&self, state: &PersistedState) -> Option<TrayState> {
+        if !state.is_working {
+            return None;
+        }
+
+        self.is_working.store(true, Ordering::Relaxed);
+        self.is_on_break.store(state.is_on_break, Ordering::Relaxed);
+        self.total_break_secs
+            .store(state.total_break_secs, Ordering::Relaxed);
+        self.is_in_meeting
+            .store(state.is_in_meeting, Ordering::Relaxed);
+        self.total_meeting_secs
+            .store(state.total_meeting_secs, Ordering::Relaxed);
+        self.is_on_hold.store(state.is_on_hold, Ordering::Relaxed);
+        self.total_hold_secs
+            .store(state.total_hold_secs, Ordering::Relaxed);
+        // 休憩・会議・一時停止の経過時間は保存した開始時刻から数える (古い状態ファイルなら再開した時点から)
+        // 業務の経過時間から除くので、再開する前に設定しておく
+        let ago = |time| {
+            Some(
+                Instant::now()
+                    .checked_sub(elapsed_since(time))
+                    .unwrap_or_else(Instant::now),
+            )
+        };
+        if state.is_on_break {
+            *self.break_reason.lock().unwrap() = state.break_reason.clone();
+            *self.break_started.lock().unwrap() = ago(state.break_started_at);
+        } else if state.is_in_meeting {
+            *self.meeting_started.lock().unwrap() = ago(state.meeting_started_at);
+        } else if state.is_on_hold {
+            *self.hold_started.lock().unwrap() = ago(state.hold_started_at);
+        }
+        self.resume_from(Duration::from_secs(state.elapsed_secs));
+        // 続けて働いている時間は保存していないので再開した時点から数える
+        if !state.is_on_break {
+            *self.stretch_started.lock().unwrap() = Some(Instant::now());
+        }
+        *self.started_at.lock().unwrap() = state.started_at;
+
+        Some(TrayState {
+            is_working: true,
+            is_on_break: state.is_on_break,
+            is_in_meeting: state.is_in_meeting,
+            is_on_hold: state.is_on_hold,
+        })
+    }
+}
+
+// 勤務状態を遷移させ、その結果をトレイや記録に反映する
+// (トレイとショートカットから同時に操作されても、読み取りから反映までを 1 つずつ行う)
+pub fn transition(
+    app: &AppHandle,
+    work: &WorkState,
+    f: impl FnOnce(&mut TrayState) -> Vec<Effect>,
+) {
+    let _guard = work.transition_lock.lock().unwrap();
+    apply_transition(app, work, f);
+}
+
+// 他の状態遷移の途中でなければ状態を遷移させる (タイマーのスレッドから呼ぶ)
+// (業務の開始は古いタイマーの終了を待つので、タイマーのスレッドではロックを待たない)
+pub fn try_transition(
+    app: &AppHandle,
+    work: &WorkState,
+    f: impl FnOnce(&mut TrayState) -> Vec<Effect>,
+) -> bool {
+    let Ok(_guard) = work.transition_lock.try_lock() else {
+        return false;
+    };
+    apply_transition(app, work, f);
+    true
+}
+
+// ロックを取った状態で状態を遷移させ、発生した処理を反映する (何も起きなければ false)
+pub fn apply_transition(
+    app: &AppHandle,
+    work: &WorkState,
+    f: impl FnOnce(&mut TrayState) -> Vec<Effect>,
+) -> bool {
+    let mut state = work.tray_state();
+    let before = state;
+    let snapshot = work.snapshot(before);
+    let effects = f(&mut state);
+    if effects.is_empty() {
+        return false;
+    }
+    info!("勤務状態を変更します: {:?} -> {:?}", before, state);
+    set_undo(app, work, Some(snapshot));
+
+    // タイマーのスレッドが参照するので、処理を反映する前にフラグを更新しておく
+    work.is_working.store(state.is_working, Ordering::Relaxed);
+    work.is_on_break.store(state.is_on_break, Ordering::Relaxed);
+    work.is_in_meeting
+        .store(state.is_in_meeting, Ordering::Relaxed);
+    work.is_on_hold.store(state.is_on_hold, Ordering::Relaxed);
+    let mut menu_updated = true;
+    for effect in effects {
+        menu_updated &= apply_effect(app, work, effect);
+    }
+    // 状態はもう記録・送信しているので戻さず、メニューのほうを状態に合わせ直す
+    if !menu_updated {
+        warn!("メニューの表示を勤務状態に合わせ直します: {:?}", state);
+        sync_menu(app, &state);
+    } else {
+        sync_break_reasons(app, &state);
+    }
+    tray::set_icon(app, state.icon());
+    save_state(app, work);
+
+    // 開いているウィンドウにも変わったことを知らせる
+    let _ = app.emit_all("status-changed", work_status(app, work));
+    true
+}
+
+// 取り消せる操作を覚える (None なら取り消せなくする)
+pub fn set_undo(app: &AppHandle, work: &WorkState, snapshot: Option<Snapshot>) {
+    tray::update_item(app, "undo", |item| item.set_enabled(snapshot.is_some()));
+    *work.undo.lock().unwrap() = snapshot;
+}
+
+// 状態遷移で発生した処理を 1 つ反映する (メニューを変更できなかったときだけ false)
+fn apply_effect(app: &AppHandle, work: &WorkState, effect: Effect) -> bool {
+    match effect {
+        Effect::SetMenuTitle(id, title) => return set_menu_title(app, id, title),
+        Effect::SetMenuEnabled(id, enabled) => {
+            return tray::update_item(app, id, |item| item.set_enabled(enabled));
+        }
+        Effect::StartWork => {
+            // 猶予があれば、猶予が過ぎたらタイマーのスレッドが begin_work で数え始める
+            let grace = app.state::<Mutex<Config>>().lock().unwrap().start_grace();
+            work.start_work(grace);
+            if grace.is_none() {
+                set_started_at(app, work, Some(Local::now()));
+            }
+            start_timer(app, work.clone());
+        }
+        Effect::ResumeWork => {
+            // 経過時間は handle_undo が操作前のものに戻す
+            work.start_work(None);
+            set_started_at(app, work, Some(Local::now()));
+            start_timer(app, work.clone());
+        }
+        Effect::EndWork => {
+            if work.cancel_grace() {
+                info!("猶予中に業務を終了したので何も記録しません");
+                // 業務の終了も記録しないので、取り消せないようにする
+                set_undo(app, work, None);
+            }
+            // 古いタイマーが表示を書き戻さないように止めてから消す
+            // (スレッドが終わるまで待つので、この後にタイマーがタイトルを書き換えることはない)
+            stop_timer(&mut work.timer.lock().unwrap());
+            tray::set_text(app, "");
+            tray::set_tooltip(app, "");
+            set_started_at(app, work, None);
+            work.elapsed_secs
+                .store(work.work_duration().as_secs(), Ordering::Relaxed);
+        }
+        Effect::StartBreak(ago) => {
+            begin_work(app, work);
+            work.start_break(ago);
+            show_elapsed(app, &format_break_title(app, ago), None);
+        }
+        Effect::EndBreak => {
+            // 記録する休憩の時間は設定に従って丸め、終了時刻もそれに合わせる
+            if let Some((break_time, rounded)) =
+                work.end_break(|break_time| round_break(app, break_time))
+            {
+                *work.break_ended_at.lock().unwrap() =
+                    Some(time_ago(break_time) + chrono_duration(rounded));
+                info!(
+                    "休憩時間: {} (記録は {})",
+                    format_duration(break_time),
+                    format_duration(rounded)
+                );
+            }
+        }
+        Effect::StartMeeting => {
+            begin_work(app, work);
+            *work.meeting_started.lock().unwrap() = Some(Instant::now());
+            show_elapsed(app, &format_meeting_title(app, Duration::ZERO), None);
+        }
+        Effect::EndMeeting => {
+            if let Some(started) = work.meeting_started.lock().unwrap().take() {
+                let meeting_time = started.elapsed();
+                work.total_meeting_secs
+                    .fetch_add(meeting_time.as_secs(), Ordering::Relaxed);
+                info!("会議の時間: {}", format_duration(meeting_time));
+            }
+        }
+        Effect::StartHold => {
+            begin_work(app, work);
+            *work.hold_started.lock().unwrap() = Some(Instant::now());
+            show_elapsed(app, &format_hold_title(app, Duration::ZERO), None);
+        }
+        Effect::EndHold => {
+            if let Some(started) = work.hold_started.lock().unwrap().take() {
+                let hold_time = started.elapsed();
+                work.total_hold_secs
+                    .fetch_add(hold_time.as_secs(), Ordering::Relaxed);
+                info!("一時停止していた時間: {}", format_duration(hold_time));
+            }
+        }
+        Effect::Report(kind, action, ago) => {
+            // 猶予中の業務の開始は猶予が過ぎてから記録し、猶予中に終了したら終了も記録しない
+            if work.defer_to_grace(kind, action) {
+                return true;
+            }
+            let mut at = time_ago(ago);
+            if (kind, action) == (EventKind::Break, EventAction::End) {
+                // 丸めた終了時刻を使う (自動で業務を終了したときなど、時刻を指定した場合は除く)
+                let ended_at = work.break_ended_at.lock().unwrap().take();
+                if let Some(ended_at) = ended_at.filter(|_| ago.is_zero()) {
+                    at = ended_at;
+                }
+            }
+            // 業務の開始・終了の時刻は設定に従って丸める
+            if kind == EventKind::Work {
+                at = round_clock(app, at);
+            }
+            let note = work.event_note(kind, action);
+            record_event(app, kind, action, at, note.as_deref());
+            send_status(app, kind, action, at, note.as_deref());
+        }
+        Effect::Record(kind, action, ago) => {
+            record_event(app, kind, action, time_ago(ago), None);
+        }
+        Effect::Retract(kind, action) => {
+            if (kind, action) == (EventKind::Break, EventAction::End) {
+                work.break_reason.lock().unwrap().take();
+            }
+            // 猶予中の業務の開始は記録していないので、取り消すものもない
+            if kind == EventKind::Work && work.grace.lock().unwrap().is_some() {
+                return true;
+            }
+            work.break_ended_at.lock().unwrap().take();
+            // 取り消した操作の記録は修正の履歴を残して消す
+            let store = app.state::<EventStore>();
+            let retracted = store
+                .last_id(kind, action.opposite())
+                .and_then(|id| match id {
+                    Some(id) => store.delete(id, &Local::now()),
+                    None => Ok(false),
+                });
+            if let Err(e) = retracted {
+                warn!("取り消した操作の記録を消せませんでした: {}", e);
+            }
+            // サーバーには打ち消すイベントを送る (まだ送っていなければ送信前にまとめられる)
+            publish_event(app, kind, action, Local::now(), None);
+            send_status(app, kind, action, Local::now(), None);
+        }
+        Effect::Summarize => {
+            if work.grace.lock().unwrap().is_some() {
+                return true;
+            }
+            notify_session_summary(app, work);
+            print_today_summary(app);
+        }
+    }
+    true
+}
+
+// 業務開始の猶予が過ぎたので、猶予が終わった時刻から時間を数え、業務の開始を記録・送信する
+// (猶予中に休憩などを始めたときはその時点で開始したことにする, 猶予中でなければ何もしない)
+pub fn begin_work(app: &AppHandle, work: &WorkState) {
+    let Some((late, note)) = work.end_grace() else {
+        return;
+    };
+    let started_at = time_ago(late);
+    set_started_at(app, work, Some(started_at));
+    info!("猶予が過ぎたので業務の時間を数え始めます");
+
+    let started_at = round_clock(app, started_at);
+    let note = note.as_deref();
+    record_event(app, EventKind::Work, EventAction::Start, started_at, note);
+    send_status(app, EventKind::Work, EventAction::Start, started_at, note);
+    refresh_title(app, work);
+    save_state(app, work);
+    let _ = app.emit_all("status-changed", work_status(app, work));
+}
+
+// 業務を開始した時刻を記録してメニューに表示する (None で消す)
+pub fn set_started_at(app: &AppHandle, work: &WorkState, started_at: Option<DateTime<Local>>) {
+    *work.started_at.lock().unwrap() = started_at;
+    let clock_24h = app.state::<Mutex<Config>>().lock().unwrap().clock_24h;
+    let _ = app
+        .tray_handle()
+        .get_item("started_at")
+        .set_title(started_at_label(started_at, clock_24h));
+}
+
+// 業務を開始した時刻のメニューの表示 ("開始: 09:12")
+pub fn started_at_label(started_at: Option<DateTime<Local>>, clock_24h: bool) -> String {
+    let time = match started_at {
+        Some(time) => format_clock(&time, clock_24h),
+        None => "--:--".to_string(),
+    };
+    t_with(Text::StartedAt, &[&time])
+}
+
+// メニュー項目のタイトルを変える (変更できなければ false)
+fn set_menu_title(app: &AppHandle, id: &str, text: Text) -> bool {
+    let title = menu_label(&app.state::<Mutex<Config>>().lock().unwrap(), text).to_string();
+    tray::update_item(app, id, |item| item.set_title(title.clone()))
+}
+
+// メニュー項目のタイトルと有効/無効をすべて状態に合わせる
+pub fn sync_menu(app: &AppHandle, state: &TrayState) {
+    for effect in state.menu() {
+        match effect {
+            Effect::SetMenuTitle(id, title) => {
+                set_menu_title(app, id, title);
+            }
+            Effect::SetMenuEnabled(id, enabled) => {
+                tray::update_item(app, id, |item| item.set_enabled(enabled));
+            }
+            _ => {}
+        }
+    }
+    sync_break_reasons(app, state);
+}
+
+// 休憩の理由のメニュー項目を、休憩を始められるときだけ有効にする (理由を設定していなければ何もしない)
+fn sync_break_reasons(app: &AppHandle, state: &TrayState) {
+    let enabled = state.is_working && !state.is_on_break && !state.is_in_meeting;
+    let tray = app.tray_handle();
+    for item in (0..).map_while(|index| tray.try_get_item(&format!("break_reason:{}", index))) {
+        let _ = item.set_enabled(enabled);
+    }
+}
+
+// ウィンドウに渡す勤務状態
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkStatus {
+    is_working: bool,
+    is_on_break: bool,
+    is_in_meeting: bool,
+    // 休憩と会議を除いた業務の経過時間
+    elapsed_secs: u64,
+    elapsed: String,
+    started_at: Option<DateTime<Local>>,
+}
+
+// 現在の勤務状態
+pub fn work_status(app: &AppHandle, work: &WorkState) -> WorkStatus {
+    let elapsed = work.work_duration();
+    WorkStatus {
+        is_working: work.is_working.load(Ordering::Relaxed),
+        is_on_break: work.is_on_break.load(Ordering::Relaxed),
+        is_in_meeting: work.is_in_meeting.load(Ordering::Relaxed),
+        elapsed_secs: elapsed.as_secs(),
+        elapsed: format_elapsed(app, elapsed),
+        started_at: *work.started_at.lock().unwrap(),
+    }
+}
+
+// 現在の勤務状態を状態ファイルに保存
+pub fn save_state(app: &AppHandle, work: &WorkState) {
+    let Some(path) = state::state_path(app) else {
+        return;
+    };
+    let state = work.persisted();
+    if let Err(e) = state.save(&path) {
+        error!("状態の保存に失敗しました: {}", e);
+    }
+}
+
+// 記録する休憩の時間 (設定に従って丸める)
+fn round_break(app: &AppHandle, duration: Duration) -> Duration {
+    let config = app.state::<Mutex<Config>>();
+    let config = config.lock().unwrap();
+    let step = Duration::from_secs(config.break_rounding_minutes * 60);
+    config.break_rounding.round(duration, step)
+}
+
+// 記録・送信する業務の開始・終了の時刻を設定に従って丸める
+fn round_clock(app: &AppHandle, at: DateTime<Local>) -> DateTime<Local> {
+    let config = app.state::<Mutex<Config>>();
+    let config = config.lock().unwrap();
+    let step = Duration::from_secs(config.clock_rounding_minutes * 60);
+    config.clock_rounding.round(at, step)
+}
+
+// 今日の実働時間と休憩時間を出力
+fn print_today_summary(app: &AppHandle) {
+    let today = Local::now().date_naive();
+    match today_totals(app) {
+        Ok((worked, break_time, meeting_time)) => info!(
+            "本日の実働時間: {} (休憩 {}, 会議 {})",
+            format_duration(worked),
+            format_duration(break_time),
+            format_duration(meeting_time)
+        ),
+        Err(e) => warn!("実働時間を集計できませんでした: {}", e),
+    }
+    match today_break_reasons(app) {
+        Ok(reasons) if !reasons.is_empty() => info!(
+            "本日の休憩の内訳: {}",
+            reasons
+                .iter()
+                .map(|(reason, time)| format!("{} {}", reason, format_duration(*time)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Ok(_) => {}
+        Err(e) => warn!("休憩の内訳を集計できませんでした: {}", e),
+    }
+
+    let store = app.state::<EventStore>();
+
+    let longest_stretch = store.events().map(|events| {
+        report::daily_summaries(&events)
+            .into_iter()
+            .find(|day| day.date == today)
+            .map(|day| day.longest_stretch)
+            .unwrap_or_default()
+    });
+    match longest_stretch {
+        Ok(stretch) => info!("本日の最長連続勤務: {}", format_duration(stretch)),
+        Err(e) => warn!("最長連続勤務を集計できませんでした: {}", e),
+    }
+}
+
+// 今日終わった休憩の理由ごとの時間
+fn today_break_reasons(app: &AppHandle) -> rusqlite::Result<Vec<(String, Duration)>> {
+    let today = Local::now().date_naive();
+    let (Some(from), Some(to)) = (
+        report::start_of_day(today),
+        today.succ_opt().and_then(report::start_of_day),
+    ) else {
+        return Ok(Vec::new());
+    };
+    app.state::<EventStore>().break_time_by_reason(&from, &to)
+}
+
+// duration だけ前の時刻
+pub fn time_ago(duration: Duration) -> DateTime<Local> {
+    Local::now() - chrono_duration(duration)
+}
+
+// chrono の時刻の計算に使える長さ (大きすぎる場合は 0)
+fn chrono_duration(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration).unwrap_or_else(|_| chrono::Duration::zero())
+}
+
+// 保存した時刻からの経過時間 (時刻がなければ 0)
+pub fn elapsed_since(time: Option<DateTime<Local>>) -> Duration {
+    time.and_then(|time| (Local::now() - time).to_std().ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    use crate::store::EventStore;
+    use crate::tray::Effect;
+
+    // secs 秒前の時刻
+    fn secs_ago(secs: u64) -> Instant {
+        Instant::now()
+            .checked_sub(Duration::from_secs(secs))
+            .unwrap()
+    }
+
+    #[test]
+    fn persisted_state_round_trips_while_working() {
+        let work = WorkState::default();
+        work.is_working.store(true, Ordering::Relaxed);
+        work.total_break_secs.store(600, Ordering::Relaxed);
+        work.total_meeting_secs.store(300, Ordering::Relaxed);
+        work.total_hold_secs.store(120, Ordering::Relaxed);
+        work.resume_from(Duration::from_secs(3600));
+        let state = work.persisted();
+
+        let restored = WorkState::default();
+        let tray = restored.restore_persisted(&state).unwrap();
+        assert!(tray.is_working && !tray.is_on_break);
+        assert_eq!(restored.total_break_secs.load(Ordering::Relaxed), 600);
+        assert_eq!(restored.total_meeting_secs.load(Ordering::Relaxed), 300);
+        assert_eq!(restored.total_hold_secs.load(Ordering::Relaxed), 120);
+        assert_eq!(restored.work_duration().as_secs(), 3600);
+        assert_eq!(restored.persisted(), state);
+    }
+
+    #[test]
+    fn restores_mid_break_and_keeps_counting_it() {
+        let work = WorkState::default();
+        work.is_working.store(true, Ordering::Relaxed);
+        work.is_on_break.store(true, Ordering::Relaxed);
+        *work.break_started.lock().unwrap() = Some(secs_ago(300));
+        *work.break_reason.lock().unwrap() = Some("lunch".to_string());
+        work.resume_from(Duration::from_secs(1800));
+        let state = work.persisted();
+        assert!(state.is_on_break);
+
+        let restored = WorkState::default();
+        let tray = restored.restore_persisted(&state).unwrap();
+        assert!(tray.is_on_break);
+        assert_eq!(
+            restored.break_reason.lock().unwrap().as_deref(),
+            Some("lunch")
+        );
+        // 休憩は保存前から数え続け、業務の経過時間は休憩の分だけ止まっている
+        let break_time = restored.break_duration().unwrap().as_secs();
+        assert!((300..=301).contains(&break_time));
+        // (保存した時刻から数え直すので 1 秒未満ずれることがある)
+        assert!((1799..=1800).contains(&restored.work_duration().as_secs()));
+        // 休憩中は続けて働いている時間を数えない
+        assert!(restored.stretch_started.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn nothing_is_restored_when_not_working() {
+        let state = WorkState::default().persisted();
+        assert!(!state.is_working);
+        let restored = WorkState::default();
+        assert!(restored.restore_persisted(&state).is_none());
+        assert!(!restored.is_working.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn grace_period_is_not_persisted_as_working() {
+        let work = WorkState::default();
+        work.is_working.store(true, Ordering::Relaxed);
+        *work.grace.lock().unwrap() = Some(Grace {
+            until: Instant::now() + Duration::from_secs(60),
+            note: None,
+            cancelled: false,
+        });
+        assert!(!work.persisted().is_working);
+    }
+
+    #[test]
+    fn meeting_and_break_durations_are_tracked_separately() {
+        let work = WorkState::default();
+        work.resume_from(Duration::from_secs(3600));
+        *work.meeting_started.lock().unwrap() = Some(secs_ago(600));
+        assert_eq!(work.break_duration(), None);
+        assert_eq!(work.meeting_duration().unwrap().as_secs(), 600);
+
+        work.total_break_secs.store(300, Ordering::Relaxed);
+        assert_eq!(work.persisted().total_meeting_secs, 0);
+        assert_eq!(work.persisted().total_break_secs, 300);
+        // どちらも業務の経過時間からは除く
+        assert_eq!(work.paused_duration().as_secs(), 900);
+    }
+
+    // secs 秒前に業務を開始した勤務状態
+    fn working_since(secs: u64) -> WorkState {
+        let work = WorkState::default();
+        work.is_working.store(true, Ordering::Relaxed);
+        *work.started.lock().unwrap() = Some(secs_ago(secs));
+        work
+    }
+
+    #[test]
+    fn adjust_moves_start_back_and_forward() {
+        let work = working_since(600);
+        assert!(work.adjust(60 * 60));
+        assert_eq!(work.elapsed_secs.load(Ordering::Relaxed), 600 + 60 * 60);
+        assert!(work.adjust(-60 * 60));
+        assert_eq!(work.elapsed_secs.load(Ordering::Relaxed), 600);
+    }
+
+    #[test]
+    fn adjust_cannot_make_elapsed_negative() {
+        let work = working_since(600);
+        assert!(!work.adjust(-601));
+        assert_eq!(work.work_duration().as_secs(), 600);
+        // ちょうど 0 にはできる
+        assert!(work.adjust(-600));
+        assert_eq!(work.elapsed_secs.load(Ordering::Relaxed), 0);
+        assert!(!work.adjust(-1));
+    }
+
+    #[test]
+    fn adjust_counts_only_worked_time() {
+        // 10 分のうち 4 分は休憩していたので、戻せるのは 6 分まで
+        let work = working_since(600);
+        work.total_break_secs.store(240, Ordering::Relaxed);
+        assert!(!work.adjust(-361));
+        assert!(work.adjust(-360));
+        assert_eq!(work.work_duration().as_secs(), 0);
+    }
+
+    #[test]
+    fn adjust_does_nothing_when_not_working() {
+        let work = WorkState::default();
+        assert!(!work.adjust(60));
+        assert!(work.started.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn break_counts_up_while_work_time_stands_still() {
+        let work = working_since(3600);
+        work.start_break(Duration::ZERO);
+
+        // 休憩中に 10 分経つ
+        work.shift_back(Duration::from_secs(600));
+        assert_eq!(work.break_duration().unwrap().as_secs(), 600);
+        assert_eq!(work.work_duration().as_secs(), 3600);
+
+        let (break_time, rounded) = work.end_break(|time| time).unwrap();
+        assert_eq!((break_time.as_secs(), rounded.as_secs()), (600, 600));
+        assert_eq!(work.total_break_secs.load(Ordering::Relaxed), 600);
+        assert_eq!(work.work_duration().as_secs(), 3600);
+
+        // 休憩を終えると業務の時間がまた進む
+        work.shift_back(Duration::from_secs(60));
+        assert_eq!(work.work_duration().as_secs(), 3660);
+        assert_eq!(work.break_duration(), None);
+    }
+
+    #[test]
+    fn clones_share_the_same_state() {
+        // トレイとウィンドウのコマンドは同じ勤務状態の複製を使う
+        let tray = WorkState::default();
+        let command = tray.clone();
+        let mut state = tray.tray_state();
+        state.toggle_work();
+        tray.is_working.store(state.is_working, Ordering::Relaxed);
+        tray.resume_from(Duration::from_secs(600));
+        assert_eq!(command.tray_state(), state);
+        assert_eq!(command.work_duration().as_secs(), 600);
+
+        state.toggle_break();
+        command
+            .is_on_break
+            .store(state.is_on_break, Ordering::Relaxed);
+        command.start_break(Duration::ZERO);
+        assert!(tray.tray_state().is_on_break);
+        assert!(tray.break_duration().is_some());
+    }
+
+    #[test]
+    fn undo_restores_the_times_before_ending_a_break() {
+        let work = working_since(3600);
+        work.total_break_secs.store(600, Ordering::Relaxed);
+        work.start_break(Duration::from_secs(300));
+        let before = work.snapshot(work.tray_state());
+        *work.undo.lock().unwrap() = Some(before.clone());
+
+        // 5 分休憩して終えた
+        work.end_break(|time| time);
+        assert_eq!(work.total_break_secs.load(Ordering::Relaxed), 900);
+
+        // 取り消すと休憩の合計は元に戻り、休憩はそのまま続く
+        let snapshot = work.take_undo().unwrap();
+        work.restore(&snapshot);
+        assert_eq!(work.total_break_secs.load(Ordering::Relaxed), 600);
+        assert_eq!(*work.break_started.lock().unwrap(), before.break_started);
+        assert!(work.break_duration().unwrap() >= Duration::from_secs(300));
+        // 取り消した後はもう取り消せない
+        assert!(work.take_undo().is_none());
+    }
+
+    #[test]
+    fn session_times_include_hold_alongside_breaks() {
+        let work = working_since(4 * 3600);
+        work.elapsed_secs.store(3 * 3600, Ordering::Relaxed);
+        // 30 分休憩して、10 分一時停止した後、今も 5 分前から一時停止している
+        work.start_break(Duration::from_secs(30 * 60));
+        work.end_break(|time| time);
+        work.total_hold_secs.store(10 * 60, Ordering::Relaxed);
+        *work.hold_started.lock().unwrap() = Some(secs_ago(5 * 60));
+
+        let times = work.session_times();
+        assert_eq!(times.worked.as_secs(), 3 * 3600);
+        assert_eq!(times.break_time.as_secs() / 60, 30);
+        assert_eq!(times.meeting_time, Duration::ZERO);
+        assert_eq!(times.hold_time.as_secs() / 60, 15);
+        // 業務の時間は実働・休憩・一時停止をすべて足したもの
+        assert_eq!(times.total().as_secs() / 60, 3 * 60 + 30 + 15);
+    }
+
+    // 状態遷移の処理 (main の apply_effect) と同じように反映して、記録・取り消しするイベントを返す
+    fn apply_effects(
+        work: &WorkState,
+        effects: Vec<Effect>,
+        grace: Option<Duration>,
+    ) -> Vec<Effect> {
+        let mut recorded = Vec::new();
+        for effect in effects {
+            match effect {
+                Effect::StartWork => {
+                    work.is_working.store(true, Ordering::Relaxed);
+                    work.start_work(grace);
+                }
+                Effect::ResumeWork => {
+                    work.is_working.store(true, Ordering::Relaxed);
+                    work.start_work(None);
+                }
+                Effect::EndWork => {
+                    work.cancel_grace();
+                    work.is_working.store(false, Ordering::Relaxed);
+                }
+                Effect::Report(kind, action, _) if !work.defer_to_grace(kind, action) => {
+                    recorded.push(effect);
+                }
+                Effect::Retract(EventKind::Work, _) if work.grace.lock().unwrap().is_some() => {}
+                Effect::Retract(..) => recorded.push(effect),
+                _ => {}
+            }
+        }
+        recorded
+    }
+
+    // 業務を開始して終了するまでに記録するイベント
+    fn clock_in_and_out(
+        work: &WorkState,
+        grace: Option<Duration>,
+    ) -> Vec<(EventKind, EventAction)> {
+        let mut state = TrayState::default();
+        let effects = state.toggle_work().into_iter().chain(state.toggle_work());
+        apply_effects(work, effects.collect(), grace)
+            .into_iter()
+            .filter_map(|effect| match effect {
+                Effect::Report(kind, action, _) => Some((kind, action)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clock_out_during_grace_records_nothing() {
+        let work = WorkState::default();
+        *work.note.lock().unwrap() = Some("在宅".to_string());
+        let recorded = clock_in_and_out(&work, Some(Duration::from_secs(120)));
+
+        // 業務の開始も終了も記録・送信せず、時間も数えない
+        assert!(recorded.is_empty());
+        assert_eq!(work.work_duration(), Duration::ZERO);
+        assert_eq!(work.elapsed_secs.load(Ordering::Relaxed), 0);
+        assert_eq!(work.grace_left(), None);
+        // 取り消した猶予が後から過ぎても数え始めない
+        assert_eq!(work.end_grace(), None);
+        assert_eq!(work.work_duration(), Duration::ZERO);
+        assert!(!work.persisted().is_working);
+    }
+
+    #[test]
+    fn clock_out_without_grace_is_recorded() {
+        let work = WorkState::default();
+        let recorded = clock_in_and_out(&work, None);
+        assert_eq!(
+            recorded,
+            [
+                (EventKind::Work, EventAction::Start),
+                (EventKind::Work, EventAction::End)
+            ]
+        );
+        assert!(work.grace.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn time_is_counted_from_the_end_of_grace() {
+        let work = WorkState::default();
+        work.start_work(Some(Duration::from_secs(120)));
+        *work.note.lock().unwrap() = Some("在宅".to_string());
+        assert!(work.defer_to_grace(EventKind::Work, EventAction::Start));
+        // 休憩などは猶予中でも記録する
+        assert!(!work.defer_to_grace(EventKind::Break, EventAction::Start));
+        assert!(work.grace_left().is_some());
+        assert_eq!(work.work_duration(), Duration::ZERO);
+
+        // 猶予が 30 秒前に終わっていたら、そこから数える
+        work.grace.lock().unwrap().as_mut().unwrap().until = secs_ago(30);
+        let (late, note) = work.end_grace().unwrap();
+        assert_eq!(late.as_secs(), 30);
+        assert_eq!(note.as_deref(), Some("在宅"));
+        assert_eq!(work.work_duration().as_secs(), 30);
+        assert_eq!(work.stretch_duration().as_secs(), 30);
+        assert!(!work.defer_to_grace(EventKind::Work, EventAction::End));
+    }
+
+    #[test]
+    fn ending_a_break_records_its_reason_and_duration() {
+        let store = EventStore::open_in_memory().unwrap();
+        let work = WorkState::default();
+        let at = |h: u32, m: u32| Local.with_ymd_and_hms(2024, 4, 1, h, m, 0).unwrap();
+        let record = |kind, action, time| {
+            let note = work.event_note(kind, action);
+            store.record(kind, action, &time, note.as_deref()).unwrap()
+        };
+
+        *work.note.lock().unwrap() = Some("在宅".to_string());
+        record(EventKind::Work, EventAction::Start, at(9, 0));
+        // 昼食の休憩 45 分と、理由を選ばなかった休憩と、コーヒーの休憩 10 分
+        *work.break_reason.lock().unwrap() = Some("lunch".to_string());
+        record(EventKind::Break, EventAction::Start, at(12, 0));
+        let lunch = record(EventKind::Break, EventAction::End, at(12, 45));
+        record(EventKind::Break, EventAction::Start, at(14, 0));
+        record(EventKind::Break, EventAction::End, at(14, 5));
+        *work.break_reason.lock().unwrap() = Some("coffee".to_string());
+        record(EventKind::Break, EventAction::Start, at(15, 0));
+        record(EventKind::Break, EventAction::End, at(15, 10));
+        record(EventKind::Work, EventAction::End, at(18, 0));
+
+        assert_eq!(lunch, Some(Duration::from_secs(45 * 60)));
+        // 休憩を終えたら理由を忘れる
+        assert_eq!(*work.break_reason.lock().unwrap(), None);
+        let notes: Vec<Option<String>> = store
+            .events()
+            .unwrap()
+            .into_iter()
+            .map(|event| event.note)
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                Some("在宅"),
+                Some("lunch"),
+                Some("lunch"),
+                None,
+                None,
+                Some("coffee"),
+                Some("coffee"),
+                None
+            ]
+            .map(|note| note.map(str::to_string))
+        );
+        assert_eq!(
+            store.break_time_by_reason(&at(0, 0), &at(23, 59)).unwrap(),
+            [
+                ("coffee".to_string(), Duration::from_secs(10 * 60)),
+                ("lunch".to_string(), Duration::from_secs(45 * 60))
+            ]
+        );
+    }
+
+    #[test]
+    fn undoing_a_clock_out_does_not_start_grace() {
+        let grace = Some(Duration::from_secs(120));
+        let work = working_since(3600);
+        work.elapsed_secs.store(3600, Ordering::Relaxed);
+        let mut state = TrayState::default();
+        state.toggle_work();
+
+        let snapshot = work.snapshot(state);
+        let clocked_out = state.toggle_work();
+        assert_eq!(
+            apply_effects(&work, clocked_out, grace),
+            [Effect::Report(
+                EventKind::Work,
+                EventAction::End,
+                Duration::ZERO
+            )]
+        );
+
+        // 業務終了の記録を取り消して、猶予なしで操作前の時間から数え直す
+        let undone = state.undo(snapshot.state);
+        assert_eq!(
+            apply_effects(&work, undone, grace),
+            [Effect::Retract(EventKind::Work, EventAction::Start)]
+        );
+        work.restore(&snapshot);
+        assert!(work.grace.lock().unwrap().is_none());
+        assert_eq!(work.grace_left(), None);
+        assert!(work.work_duration() >= Duration::from_secs(3600));
+        // 後から猶予が過ぎて数え直すこともない
+        assert_eq!(work.end_grace(), None);
+        assert!(work.persisted().is_working);
+    }
+}